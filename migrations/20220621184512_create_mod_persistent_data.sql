@@ -0,0 +1,6 @@
+CREATE TABLE mod_persistent_data (
+    account_id INTEGER NOT NULL REFERENCES accounts(id),
+    mod_name TEXT NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (account_id, mod_name)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE leaderboard (
+    account_id INTEGER NOT NULL REFERENCES accounts(id),
+    metric TEXT NOT NULL,
+    value REAL NOT NULL,
+    PRIMARY KEY (account_id, metric)
+);
+
+CREATE INDEX leaderboard_ranking ON leaderboard (metric, value DESC);
@@ -0,0 +1,9 @@
+ALTER TABLE accounts ADD COLUMN username_updated DATETIME;
+
+CREATE TABLE username_history (
+    account_id INTEGER NOT NULL REFERENCES accounts(id),
+    username TEXT NOT NULL,
+    changed DATETIME NOT NULL
+);
+
+CREATE INDEX username_history_account ON username_history (account_id, changed);
@@ -0,0 +1,9 @@
+CREATE TABLE accounts (
+    id BIGINT PRIMARY KEY NOT NULL,
+    username TEXT,
+    token BYTEA,
+    token_created TIMESTAMPTZ,
+    current_server BYTEA,
+    last_auth_ip TEXT,
+    persistent_data BYTEA
+);
@@ -0,0 +1,16 @@
+CREATE TABLE servers (
+    id BYTEA PRIMARY KEY NOT NULL,
+    ip TEXT NOT NULL,
+    auth_token BYTEA NOT NULL,
+    port INTEGER NOT NULL,
+    auth_port INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    map TEXT NOT NULL,
+    playlist TEXT NOT NULL,
+    max_players INTEGER NOT NULL,
+    password TEXT,
+    player_count INTEGER,
+    mod_info TEXT,
+    last_seen TIMESTAMPTZ NOT NULL
+);
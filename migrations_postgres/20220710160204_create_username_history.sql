@@ -0,0 +1,9 @@
+ALTER TABLE accounts ADD COLUMN username_updated TIMESTAMPTZ;
+
+CREATE TABLE username_history (
+    account_id BIGINT NOT NULL REFERENCES accounts(id),
+    username TEXT NOT NULL,
+    changed TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX username_history_account ON username_history (account_id, changed);
@@ -0,0 +1,14 @@
+CREATE TABLE sessions (
+    token BYTEA PRIMARY KEY NOT NULL,
+    account_id BIGINT NOT NULL REFERENCES accounts(id),
+    created TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX sessions_account ON sessions (account_id);
+
+-- Keep the sessions of players that are currently logged in
+INSERT INTO sessions (token, account_id, created)
+SELECT token, id, token_created FROM accounts WHERE token IS NOT NULL AND token_created IS NOT NULL;
+
+ALTER TABLE accounts DROP COLUMN token;
+ALTER TABLE accounts DROP COLUMN token_created;
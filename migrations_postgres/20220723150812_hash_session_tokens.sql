@@ -0,0 +1,5 @@
+-- Tokens are stored as a keyed hash from now on, the plaintext tokens can't be converted and are removed.
+-- Players get a new token the next time they log in.
+DELETE FROM sessions;
+
+ALTER TABLE sessions RENAME COLUMN token TO token_hash;
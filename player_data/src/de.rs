@@ -1,12 +1,11 @@
-use std::{fmt, marker::PhantomData};
+use std::fmt;
 
 use serde::{
     de::{self, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor},
     Deserialize,
 };
-use serde_with::DeserializeAs;
 
-use super::error::{Error, Result};
+use super::error::{Error, PathSegment, Result};
 
 pub struct Deserializer<'de> {
     input: &'de [u8],
@@ -24,12 +23,63 @@ where
 {
     let mut deserializer = Deserializer::from_u8(s);
     let t = T::deserialize(&mut deserializer)?;
-    Ok(t)
+    if deserializer.input.is_empty() {
+        Ok(t)
+    } else {
+        Err(Error::TrailingBytes(deserializer.input.len()))
+    }
+}
+
+/// Returns the names of the variants of an enum, in the order they are encoded.
+pub fn enum_variants<'de, T>() -> &'static [&'static str]
+where
+    T: Deserialize<'de>,
+{
+    struct VariantsDeserializer<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> de::Deserializer<'de> for VariantsDeserializer<'a> {
+        type Error = Error;
+
+        fn deserialize_any<V>(self, _: V) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            Err(Error::UnknownStructure)
+        }
+
+        fn deserialize_enum<V>(
+            self,
+            _: &'static str,
+            variants: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value>
+        where
+            V: de::Visitor<'de>,
+        {
+            *self.0 = variants;
+            Err(Error::UnknownStructure)
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map struct identifier ignored_any
+        }
+    }
+
+    let mut variants: &'static [&'static str] = &[];
+    // Deserialization always fails, only the variant names are of interest
+    let _ = T::deserialize(VariantsDeserializer(&mut variants));
+    variants
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
     fn deserialize_any<V>(self, _: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
@@ -41,7 +91,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        let byte = *self.input.get(0).ok_or(Error::Eof)?;
+        let byte = *self.input.first().ok_or(Error::Eof)?;
         self.input = &self.input[1..];
         visitor.visit_bool(byte != 0u8)
     }
@@ -85,7 +135,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        let byte = *self.input.get(0).ok_or(Error::Eof)?;
+        let byte = *self.input.first().ok_or(Error::Eof)?;
         self.input = &self.input[1..];
         visitor.visit_u8(byte)
     }
@@ -203,13 +253,15 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         Err(Error::UnknownStructure)
     }
 
-    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: de::Visitor<'de>,
     {
         visitor.visit_seq(Array {
-            de: &mut self,
-            remaining: len,
+            de: self,
+            fields: None,
+            index: 0,
+            len,
         })
     }
 
@@ -241,7 +293,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        visitor.visit_seq(Array {
+            de: self,
+            fields: Some(fields),
+            index: 0,
+            len: fields.len(),
+        })
     }
 
     fn deserialize_enum<V>(
@@ -253,12 +310,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        let byte = *self.input.get(0).ok_or(Error::Eof)?;
+        let byte = *self.input.first().ok_or(Error::Eof)?;
         self.input = &self.input[1..];
         visitor.visit_enum(
             variants
                 .get(byte as usize)
-                .ok_or(Error::InvalidEnum)?
+                .ok_or(Error::InvalidEnum(byte.into()))?
                 .into_deserializer(),
         )
     }
@@ -280,7 +337,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
 struct Array<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
-    remaining: usize,
+    /// Field names if this is a struct, used to report where decoding failed
+    fields: Option<&'static [&'static str]>,
+    index: usize,
+    len: usize,
 }
 
 impl<'de, 'a> SeqAccess<'de> for Array<'a, 'de> {
@@ -290,35 +350,98 @@ impl<'de, 'a> SeqAccess<'de> for Array<'a, 'de> {
     where
         T: DeserializeSeed<'de>,
     {
-        if self.remaining == 0 {
+        if self.index == self.len {
             return Ok(None);
         }
-        self.remaining -= 1;
-        seed.deserialize(&mut *self.de).map(Some)
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(&mut *self.de).map(Some).map_err(|err| {
+            let segment = match self.fields.and_then(|f| f.get(index)) {
+                Some(field) => PathSegment::Field(field),
+                None => PathSegment::Index(index),
+            };
+            err.within(segment)
+        })
     }
 
     fn size_hint(&self) -> Option<usize> {
-        Some(self.remaining)
+        Some(self.len - self.index)
     }
 }
 
-pub struct FixedString<const N: usize>;
+/// A string stored in `N` bytes, terminated and padded with zeros.
+///
+/// The game doesn't clear the bytes after the terminator when a string gets shorter (for example a renamed loadout),
+/// so the raw bytes are kept and encoded again unchanged. Only the text before the terminator is exposed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FixedString<const N: usize> {
+    raw: [u8; N],
+}
 
-impl<'de, T, const N: usize> DeserializeAs<'de, T> for FixedString<N>
-where
-    for<'a> T: From<String>,
-{
-    fn deserialize_as<D>(deserializer: D) -> std::result::Result<T, D::Error>
+impl<const N: usize> FixedString<N> {
+    /// Creates a zero padded string, `None` if it is longer than `N` bytes or contains a zero byte.
+    pub fn new(value: &str) -> Option<Self> {
+        if value.len() > N || value.as_bytes().contains(&0) {
+            return None;
+        }
+        let mut raw = [0u8; N];
+        raw[..value.len()].copy_from_slice(value.as_bytes());
+        Some(Self { raw })
+    }
+
+    fn from_raw(raw: [u8; N]) -> Option<Self> {
+        let length = raw.iter().position(|&b| b == 0).unwrap_or(N);
+        std::str::from_utf8(&raw[..length]).ok()?;
+        Some(Self { raw })
+    }
+
+    pub fn as_str(&self) -> &str {
+        let length = self.raw.iter().position(|&b| b == 0).unwrap_or(N);
+        // Checked when created
+        std::str::from_utf8(&self.raw[..length]).unwrap()
+    }
+
+    /// The stored bytes, including any left after the terminator.
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.raw
+    }
+}
+
+impl<const N: usize> std::ops::Deref for FixedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for FixedString<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Display for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedString<N> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: serde::de::Deserializer<'de>,
     {
-        struct Helper<S, const N: usize>(PhantomData<S>);
+        struct Helper<const N: usize>;
 
-        impl<'de, S, const N: usize> Visitor<'de> for Helper<S, N>
-        where
-            for<'a> S: From<String>,
-        {
-            type Value = S;
+        impl<'de, const N: usize> Visitor<'de> for Helper<N> {
+            type Value = FixedString<N>;
 
             fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
                 write!(formatter, "a string of length {}", N)
@@ -329,18 +452,60 @@ where
                 A: SeqAccess<'de>,
             {
                 let mut raw = [0u8; N];
-                let mut index = 0;
-                while let Some(element) = seq.next_element::<u8>()? {
-                    raw[index] = element;
-                    index += 1;
+                for (index, byte) in raw.iter_mut().enumerate() {
+                    *byte = seq
+                        .next_element::<u8>()?
+                        .ok_or_else(|| de::Error::invalid_length(index, &self))?;
                 }
-                assert_eq!(index, N);
 
-                String::from_utf8(raw.into_iter().take_while(|&b| b != 0u8).collect())
-                    .map(|s| s.into())
-                    .map_err(|_| de::Error::custom("Failed decoding string"))
+                FixedString::from_raw(raw)
+                    .ok_or_else(|| de::Error::custom("Failed decoding string"))
+            }
+
+            fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                FixedString::new(v).ok_or_else(|| de::Error::invalid_length(v.len(), &self))
             }
         }
-        deserializer.deserialize_tuple(N, Helper::<T, N>(Default::default()))
+
+        // Self-describing formats store the string without padding
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Helper::<N>)
+        } else {
+            deserializer.deserialize_tuple(N, Helper::<N>)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_string_keeps_bytes_after_terminator() {
+        let raw = b"ab\0stale";
+        let string: FixedString<8> = from_u8(raw).unwrap();
+        assert_eq!(string.as_str(), "ab");
+        assert_eq!(crate::ser::to_vec(&string).unwrap(), raw);
+    }
+
+    #[test]
+    fn fixed_string_without_terminator() {
+        let string: FixedString<4> = from_u8(b"abcd").unwrap();
+        assert_eq!(string.as_str(), "abcd");
+    }
+
+    #[test]
+    fn fixed_string_rejects_invalid_utf8() {
+        assert!(from_u8::<FixedString<4>>(b"a\xffb\0").is_err());
+    }
+
+    #[test]
+    fn new_fixed_string_is_zero_padded() {
+        let string = FixedString::<6>::new("abc").unwrap();
+        assert_eq!(string.as_bytes(), b"abc\0\0\0");
+        assert!(FixedString::<2>::new("abc").is_none());
     }
 }
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::fmt::{self, Display};
 
 use serde::{de, ser};
 
@@ -16,8 +16,44 @@ pub enum Error {
     UnsupportedType,
     #[error("Unexpected end of input.")]
     Eof,
-    #[error("Encoded enum does not match structure.")]
-    InvalidEnum,
+    #[error("Encoded enum value {0} does not match structure.")]
+    InvalidEnum(u32),
+    #[error("Invalid data at {path}: {source}")]
+    Field { path: String, source: Box<Error> },
+}
+
+/// Part of the location of a value in the player data.
+pub(crate) enum PathSegment {
+    Field(&'static str),
+    Index(usize),
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Field(name) => f.write_str(name),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+impl Error {
+    /// Prefixes the location of the error with the containing field or index.
+    pub(crate) fn within(self, segment: PathSegment) -> Self {
+        match self {
+            Error::Field { path, source } => {
+                let separator = if path.starts_with('[') { "" } else { "." };
+                Error::Field {
+                    path: format!("{}{}{}", segment, separator, path),
+                    source,
+                }
+            }
+            other => Error::Field {
+                path: segment.to_string(),
+                source: Box::new(other),
+            },
+        }
+    }
 }
 
 impl de::Error for Error {
@@ -25,3 +61,9 @@ impl de::Error for Error {
         Error::Message(msg.to_string())
     }
 }
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
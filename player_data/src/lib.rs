@@ -1,203 +1,232 @@
+//! Titanfall 2 persistent player data.
+//!
+//! [`from_u8`] and [`to_vec`] convert between [`PlayerData`] and the binary layout used by the game.
+//!
+//! All types also implement [`serde::Serialize`] for self-describing formats like JSON,
+//! which produces a stable shape:
+//! - Structs are objects whose keys are the field names of the game's persistent data definition
+//!   (for example `killStats.totalPVP`), these names never change.
+//! - Enums are the name of their value (`"mp_forwardbase_kodai"`), not the index stored by the game.
+//! - Fixed length strings are strings without their zero padding.
+//! - Floats that are not finite (the game stores NaN in some fields) are `null` in JSON.
+//! - Fixed length arrays, including those indexed by an enum, are arrays with all entries.
+//!   For example `mapStats[i]` contains the stats of the `i`-th [`Map`] value.
+//! - `unmappedTail` contains the raw bytes at the end of the data that aren't mapped to fields yet.
+
 #![allow(dead_code)]
+// Names mirror the game's persistent data definition
+#![allow(non_snake_case, clippy::upper_case_acronyms)]
 
 mod de;
 mod error;
+pub mod pdiff;
+mod ser;
+mod validate;
 
+pub use de::enum_variants;
 pub use de::Deserializer;
-use de::FixedString;
+pub use de::FixedString;
 pub use error::{Error, Result};
-use serde::Deserialize;
+pub use ser::Serializer;
+use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+pub use validate::{Limits, ValidationError};
 
 pub fn from_u8(s: &[u8]) -> Result<Box<PlayerData>> {
     de::from_u8(s)
 }
 
+/// Encodes player data into the binary layout used by the game.
+pub fn to_vec(data: &PlayerData) -> Result<Vec<u8>> {
+    ser::to_vec(data)
+}
+
 #[allow(non_snake_case)] // The naming is so inconsistent I can't be bothered to put renames on every field
 #[serde_as]
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct PlayerData {
-    initializedVersion: i32,
-    announcementVersionSeen: i32,
+    pub initializedVersion: i32,
+    pub announcementVersionSeen: i32,
     pub xp: i32,
-    previousXP: i32,
-    credits: i32,
-    xp_match: [i32; 20],
-    xp_count: [i32; 20],
+    pub previousXP: i32,
+    pub credits: i32,
+    pub xp_match: [i32; 20],
+    pub xp_count: [i32; 20],
     pub netWorth: i32,
-    matchWin: bool,
-    matchScoreEvent: bool,
-    matchComplete: bool,
-    matchSquadBonus: bool,
-    showGameSummary: bool,
-    regenShowNew: bool,
-    spawnAsTitan: bool,
-    haveSeenCustomCoop: bool,
-    factionGiftsFixed: bool,
-    isACheater: bool,
-    spendDoubleColiseumTickets: bool,
-    privateMatchState: i32,
-    playlistShuffle_seed: i32,
-    playlistShuffle_seedFlip: bool,
-    playlistShuffle_curIndex: i32,
-    #[serde_as(as = "FixedString<16>")]
-    lastFDTitanRef: String,
-    lastFDDifficulty: i32,
-    ultimateEdition: bool,
-    randomColiseumUnlocks: i32,
-    randomPlayerLevelUnlocks: i32,
-    randomTitanLevelUnlocks: [i32; TITAN_COUNT],
+    pub matchWin: bool,
+    pub matchScoreEvent: bool,
+    pub matchComplete: bool,
+    pub matchSquadBonus: bool,
+    pub showGameSummary: bool,
+    pub regenShowNew: bool,
+    pub spawnAsTitan: bool,
+    pub haveSeenCustomCoop: bool,
+    pub factionGiftsFixed: bool,
+    pub isACheater: bool,
+    pub spendDoubleColiseumTickets: bool,
+    pub privateMatchState: i32,
+    pub playlistShuffle_seed: i32,
+    pub playlistShuffle_seedFlip: bool,
+    pub playlistShuffle_curIndex: i32,
+    pub lastFDTitanRef: FixedString<16>,
+    pub lastFDDifficulty: i32,
+    pub ultimateEdition: bool,
+    pub randomColiseumUnlocks: i32,
+    pub randomPlayerLevelUnlocks: i32,
+    pub randomTitanLevelUnlocks: [i32; TITAN_COUNT],
     #[serde_as(as = "[_; WEAPONS_ABILITIES_COUNT]")]
-    randomWeaponLevelUnlocks: [i32; WEAPONS_ABILITIES_COUNT],
-    randomFactionLevelUnlocks: [i32; 7],
-    doubleXP: i32,
-    coliseumTickets: i32,
-    coliseumWinStreak: i32,
-    coliseumBestStreak: i32,
-    coliseumTotalWins: i32,
-    coliseumTotalLosses: i32,
-    reventUnlocks: [RecentUnlock; 10],
-    hasBeenIntroducedToComms: bool,
-    lastCommsUseDate: i32,
-    numTimesUsedComms: i32,
-    custom_emoji_initialized: bool,
-    custom_emoji: [i32; 4],
-    burnmeterSlot: i32,
-    pve: PveData,
-    factionChoice: Faction,
-    enemyFaction: Faction,
-    persistentRewards: [bool; 32],
-    consumableRewards: [i32; 32],
-    pilotSpawnLoadout: SpawnLoadout,
-    titanSpawnLoadout: SpawnLoadout,
-    activePilotLoadout: PilotLoadout,
-    activeTitanLoadout: TitanLoadout,
-    activeTitanLoadoutIndex: i32,
-    pilotLoadouts: [PilotLoadout; 10],
-    titanLoadouts: [TitanLoadout; 10],
-    pinTrackedEntitlements: [bool; 9],
-    newPinTrackedEntitlements: [bool; 9],
-    activeBCID: i32,
+    pub randomWeaponLevelUnlocks: [i32; WEAPONS_ABILITIES_COUNT],
+    pub randomFactionLevelUnlocks: [i32; 7],
+    pub doubleXP: i32,
+    pub coliseumTickets: i32,
+    pub coliseumWinStreak: i32,
+    pub coliseumBestStreak: i32,
+    pub coliseumTotalWins: i32,
+    pub coliseumTotalLosses: i32,
+    pub reventUnlocks: [RecentUnlock; 10],
+    pub hasBeenIntroducedToComms: bool,
+    pub lastCommsUseDate: i32,
+    pub numTimesUsedComms: i32,
+    pub custom_emoji_initialized: bool,
+    pub custom_emoji: [i32; 4],
+    pub burnmeterSlot: i32,
+    pub pve: PveData,
+    pub factionChoice: Faction,
+    pub enemyFaction: Faction,
+    pub persistentRewards: [bool; 32],
+    pub consumableRewards: [i32; 32],
+    pub pilotSpawnLoadout: SpawnLoadout,
+    pub titanSpawnLoadout: SpawnLoadout,
+    pub activePilotLoadout: PilotLoadout,
+    pub activeTitanLoadout: TitanLoadout,
+    pub activeTitanLoadoutIndex: i32,
+    pub pilotLoadouts: [PilotLoadout; 10],
+    pub titanLoadouts: [TitanLoadout; 10],
+    pub pinTrackedEntitlements: [bool; 9],
+    pub newPinTrackedEntitlements: [bool; 9],
+    pub activeBCID: i32,
     pub activeCallingCardIndex: i32,
     pub activeCallsignIconIndex: i32,
     pub activeCallsignIconStyleIndex: i32,
     pub gen: i32,
-    factionXP: [i32; 7],
-    previousFactionXP: [i32; 7],
-    titanXP: [i32; 7],
-    previousTitanXP: [i32; 7],
-    fdTitanXP: [i32; 7],
-    fdPreviousTitanXP: [i32; 7],
-    titanFDUnlockPoints: [i32; 7],
-    previousFDUnlockPoints: [i32; 7],
-    fd_match: [i32; 20],
-    fd_count: [i32; 20],
-    titanClassLockState: [i32; 7],
-    fdTutorialBits: i32,
-    fdPlaylistBits: i32,
-    gameStats: GameStats,
-    mapStats: [MapStats; MAP_COUNT],
-    timeStats: HoursPlayed,
-    distanceStats: MilesTraveled,
+    pub factionXP: [i32; 7],
+    pub previousFactionXP: [i32; 7],
+    pub titanXP: [i32; 7],
+    pub previousTitanXP: [i32; 7],
+    pub fdTitanXP: [i32; 7],
+    pub fdPreviousTitanXP: [i32; 7],
+    pub titanFDUnlockPoints: [i32; 7],
+    pub previousFDUnlockPoints: [i32; 7],
+    pub fd_match: [i32; 20],
+    pub fd_count: [i32; 20],
+    pub titanClassLockState: [i32; 7],
+    pub fdTutorialBits: i32,
+    pub fdPlaylistBits: i32,
+    pub gameStats: GameStats,
+    pub mapStats: [MapStats; MAP_COUNT],
+    pub timeStats: HoursPlayed,
+    pub distanceStats: MilesTraveled,
     #[serde_as(as = "[_; WEAPONS_ABILITIES_COUNT]")]
-    weaponStats: [WeaponStats; WEAPONS_ABILITIES_COUNT],
+    pub weaponStats: [WeaponStats; WEAPONS_ABILITIES_COUNT],
     #[serde_as(as = "[_; WEAPONS_ABILITIES_COUNT]")]
-    weaponKillStats: [WeaponKillStats; WEAPONS_ABILITIES_COUNT],
-    killStats: KillStats,
-    deathStats: DeathStats,
-    miscStats: MiscStats,
-    fdStats: FdStats,
-    titanStats: [TitanStats; TITAN_COUNT],
-    kdratio_lifetime: f32,
-    kdratio_lifetime_pvp: f32,
-    kdratio_match: [f32; 10],
-    kdratiopvp_match: [f32; 10],
-    winStreak: i32,
-    highestWinStreakEver: i32,
-    winStreakIsDraws: bool,
-    winLossHistory: [i32; 10],
-    winLossHistorySize: i32,
-    mostProjectilesCollectedInVortex: i32,
-    blackMarketItemsBought: i32,
-    respawnKillInfected: bool,
+    pub weaponKillStats: [WeaponKillStats; WEAPONS_ABILITIES_COUNT],
+    pub killStats: KillStats,
+    pub deathStats: DeathStats,
+    pub miscStats: MiscStats,
+    pub fdStats: FdStats,
+    pub titanStats: [TitanStats; TITAN_COUNT],
+    pub kdratio_lifetime: f32,
+    pub kdratio_lifetime_pvp: f32,
+    pub kdratio_match: [f32; 10],
+    pub kdratiopvp_match: [f32; 10],
+    pub winStreak: i32,
+    pub highestWinStreakEver: i32,
+    pub winStreakIsDraws: bool,
+    pub winLossHistory: [i32; 10],
+    pub winLossHistorySize: i32,
+    pub mostProjectilesCollectedInVortex: i32,
+    pub blackMarketItemsBought: i32,
+    pub respawnKillInfected: bool,
     #[serde_as(as = "[_; 35]")]
-    pilotWeapons: [WeaponMain; 35],
+    pub pilotWeapons: [WeaponMain; 35],
     #[serde_as(as = "[_; 35]")]
-    pilotOffhands: [WeaponOffHand; 35],
-    titanWeapons: [WeaponMain; 15],
-    titanOffhands: [WeaponOffHand; 30],
-    titanChassis: [TitanMain; 12],
-    hasSeenStore: bool,
-    newPilotSkins: [i32; 5],
-    unlockedPilotSkins: [i32; 5],
-    newPrimePilotSkins: i32,
-    unlockedPrimePilotSkins: i32,
-    newPilotWeapons: [i32; 2],
-    unlockedPilotWeapons: [i32; 2],
-    newPilotOffhands: [i32; 2],
-    unlockedPilotOffhands: [i32; 2],
-    newPilotPassives: i32,
-    unlockedPilotPassives: i32,
-    newTitanOffhands: [i32; 2],
-    unlockedTitanOffhands: [i32; 2],
-    newTitanPassives: i32,
-    unlockedTitanPassives: i32,
-    newTitanChassis: i32,
-    unlockedTitanChassis: i32,
-    newPrimeTitans: i32,
-    unlockedPrimeTitans: i32,
-    newPilotSuits: i32,
-    unlockedPilotSuits: i32,
-    newPilotExecutions: i32,
-    unlockedPilotExecutions: i32,
-    unlockedFeatures: [i32; 2],
-    newFeatures: [i32; 2],
-    unlockedBoosts: i32,
-    newBoosts: i32,
-    unlockedFactions: i32,
-    newFactions: i32,
-    unlockedCallingCards: [i32; 16],
-    newCallingCards: [i32; 16],
-    unlockedCallsignIcons: [i32; 7],
-    newCallsignIcons: [i32; 7],
-    unlockedCommsIcons: [i32; 5],
-    newCommsIcons: [i32; 5],
-    newTitanExecutions: i32,
-    unlockedTitanExecutions: i32,
+    pub pilotOffhands: [WeaponOffHand; 35],
+    pub titanWeapons: [WeaponMain; 15],
+    pub titanOffhands: [WeaponOffHand; 30],
+    pub titanChassis: [TitanMain; 12],
+    pub hasSeenStore: bool,
+    pub newPilotSkins: [i32; 5],
+    pub unlockedPilotSkins: [i32; 5],
+    pub newPrimePilotSkins: i32,
+    pub unlockedPrimePilotSkins: i32,
+    pub newPilotWeapons: [i32; 2],
+    pub unlockedPilotWeapons: [i32; 2],
+    pub newPilotOffhands: [i32; 2],
+    pub unlockedPilotOffhands: [i32; 2],
+    pub newPilotPassives: i32,
+    pub unlockedPilotPassives: i32,
+    pub newTitanOffhands: [i32; 2],
+    pub unlockedTitanOffhands: [i32; 2],
+    pub newTitanPassives: i32,
+    pub unlockedTitanPassives: i32,
+    pub newTitanChassis: i32,
+    pub unlockedTitanChassis: i32,
+    pub newPrimeTitans: i32,
+    pub unlockedPrimeTitans: i32,
+    pub newPilotSuits: i32,
+    pub unlockedPilotSuits: i32,
+    pub newPilotExecutions: i32,
+    pub unlockedPilotExecutions: i32,
+    pub unlockedFeatures: [i32; 2],
+    pub newFeatures: [i32; 2],
+    pub unlockedBoosts: i32,
+    pub newBoosts: i32,
+    pub unlockedFactions: i32,
+    pub newFactions: i32,
+    pub unlockedCallingCards: [i32; 16],
+    pub newCallingCards: [i32; 16],
+    pub unlockedCallsignIcons: [i32; 7],
+    pub newCallsignIcons: [i32; 7],
+    pub unlockedCommsIcons: [i32; 5],
+    pub newCommsIcons: [i32; 5],
+    pub newTitanExecutions: i32,
+    pub unlockedTitanExecutions: i32,
     #[serde_as(as = "[_; CHALLENGE_COUNT]")]
-    challenges: [EChallenge; CHALLENGE_COUNT],
-    dailychallenges: [EChallenge; DAILY_CHALLENGE_COUNT],
-    activeDailyChallenges: [ActiveDailyChallenge; 9],
-    trackedChallenges: [i32; 3],
-    EOGTrackedChallenges: [i32; 3],
-    #[serde_as(as = "[FixedString<64>; 3]")]
-    trackedChallengeRefs: [String; 3],
-    #[serde_as(as = "[FixedString<64>; 3]")]
-    EOGTrackedChallengeRefs: [String; 3],
-    dailyChallengeDayIndex: i32,
-    newDailyChallenges: bool,
-    isPostGameScoreboardValid: bool,
-    postGameData: PostGameData,
-    isFDPostGameScoreboardValid: bool,
-    postGameDataFD: FdPostGameData,
-    previousGooserProgress: i32,
-    mapHistory: [i32; 24],
-    modeHistory: [i32; 10],
-    #[serde_as(as = "FixedString<32>")]
-    pub lastPlayList: String,
-    lastDailyMatchVictory: i32,
-    lastTimePlayed: i32,
-    lastTimeLoggedIn: i32,
-    abandonCountForMode: [i32; GAME_MODE_COUNT],
-    lastAbandonedMode: GameMode,
-    lastAbandonTime: i32,
-    ranked: Ranked,
+    pub challenges: [EChallenge; CHALLENGE_COUNT],
+    pub dailychallenges: [EChallenge; DAILY_CHALLENGE_COUNT],
+    pub activeDailyChallenges: [ActiveDailyChallenge; 9],
+    pub trackedChallenges: [i32; 3],
+    pub EOGTrackedChallenges: [i32; 3],
+    pub trackedChallengeRefs: [FixedString<64>; 3],
+    pub EOGTrackedChallengeRefs: [FixedString<64>; 3],
+    pub dailyChallengeDayIndex: i32,
+    pub newDailyChallenges: bool,
+    pub isPostGameScoreboardValid: bool,
+    pub postGameData: PostGameData,
+    pub isFDPostGameScoreboardValid: bool,
+    pub postGameDataFD: FdPostGameData,
+    pub previousGooserProgress: i32,
+    pub mapHistory: [i32; 24],
+    pub modeHistory: [i32; 10],
+    pub lastPlayList: FixedString<32>,
+    pub lastDailyMatchVictory: i32,
+    pub lastTimePlayed: i32,
+    pub lastTimeLoggedIn: i32,
+    pub abandonCountForMode: [i32; GAME_MODE_COUNT],
+    pub lastAbandonedMode: GameMode,
+    pub lastAbandonTime: i32,
+    pub ranked: Ranked,
+    // Not mapped to any fields yet, kept so the data can be written back unchanged
+    #[serde_as(as = "[_; UNMAPPED_TAIL_LENGTH]")]
+    pub unmappedTail: [u8; UNMAPPED_TAIL_LENGTH],
 }
 
-const GAME_MODE_COUNT: usize = 14;
+pub const UNMAPPED_TAIL_LENGTH: usize = 137;
 
-#[derive(Deserialize)]
-enum GameMode {
+pub const GAME_MODE_COUNT: usize = 14;
+
+#[derive(Deserialize, Serialize)]
+pub enum GameMode {
     Tdm,
     Cp,
     At,
@@ -214,11 +243,11 @@ enum GameMode {
     Fd,
 }
 
-const MAP_COUNT: usize = 25;
+pub const MAP_COUNT: usize = 25;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum Map {
+#[derive(Deserialize, Serialize)]
+pub enum Map {
     mp_box,
     mp_test_engagement_range,
 
@@ -250,11 +279,11 @@ enum Map {
     mp_rise,
 }
 
-const WEAPONS_ABILITIES_COUNT: usize = 100;
+pub const WEAPONS_ABILITIES_COUNT: usize = 100;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum LoadoutWeaponsAbilities {
+#[derive(Deserialize, Serialize)]
+pub enum LoadoutWeaponsAbilities {
     NULL,
     melee_pilot_emptyhanded,
     melee_pilot_sword,
@@ -358,8 +387,8 @@ enum LoadoutWeaponsAbilities {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum PilotMod {
+#[derive(Deserialize, Serialize)]
+pub enum PilotMod {
     NULL,
     aog,
     automatic_fire,
@@ -441,11 +470,11 @@ enum PilotMod {
     rocket_arena,
 }
 
-const TITAN_COUNT: usize = 7;
+pub const TITAN_COUNT: usize = 7;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum TitanClass {
+#[derive(Deserialize, Serialize)]
+pub enum TitanClass {
     ion,
     scorch,
     ronin,
@@ -456,8 +485,8 @@ enum TitanClass {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum TitanMod {
+#[derive(Deserialize, Serialize)]
+pub enum TitanMod {
     NULL,
     accelerator,
     afterburners,
@@ -487,8 +516,8 @@ enum TitanMod {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum PilotPassive {
+#[derive(Deserialize, Serialize)]
+pub enum PilotPassive {
     NULL,
     pas_stealth_movement,
     pas_ordnance_pack,
@@ -516,8 +545,8 @@ enum PilotPassive {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum PilotSuit {
+#[derive(Deserialize, Serialize)]
+pub enum PilotSuit {
     medium,
     geist,
     stalker,
@@ -528,17 +557,17 @@ enum PilotSuit {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum PilotRace {
+#[derive(Deserialize, Serialize)]
+pub enum PilotRace {
     race_human_male,
     race_human_female,
 }
 
-const PILOT_EXECUTION_COUNT: usize = 13;
+pub const PILOT_EXECUTION_COUNT: usize = 13;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum PilotExecution {
+#[derive(Deserialize, Serialize)]
+pub enum PilotExecution {
     execution_neck_snap,
     execution_face_stab,
     execution_backshot,
@@ -555,8 +584,8 @@ enum PilotExecution {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum TitanExecution {
+#[derive(Deserialize, Serialize)]
+pub enum TitanExecution {
     execution_ion,
     execution_ion_prime,
     execution_tone,
@@ -580,8 +609,8 @@ enum TitanExecution {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum TitanPassive {
+#[derive(Deserialize, Serialize)]
+pub enum TitanPassive {
     NULL,
     pas_enhanced_titan_ai,
     pas_auto_eject,
@@ -646,15 +675,15 @@ enum TitanPassive {
     pas_vanguard_core9,
 }
 
-#[derive(Deserialize)]
-enum TitanIsPrime {
+#[derive(Deserialize, Serialize)]
+pub enum TitanIsPrime {
     No,
     Yes,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum Faction {
+#[derive(Deserialize, Serialize)]
+pub enum Faction {
     faction_apex,
     faction_64,
     faction_vinson,
@@ -665,8 +694,8 @@ enum Faction {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum OwnedEntitlements {
+#[derive(Deserialize, Serialize)]
+pub enum OwnedEntitlements {
     ET_DLC7_WEAPON_BUNDLE, // BUNDLE MUST BE FIRST!!!!
     ET_DLC7_R201_WARPAINT,
     ET_DLC7_G2A5_WARPAINT,
@@ -678,96 +707,92 @@ enum OwnedEntitlements {
     ET_DLC7_ARCHER_WARPAINT,
 }
 
-#[derive(Deserialize)]
-struct SpawnLoadout {
-    index: i32,
+#[derive(Deserialize, Serialize)]
+pub struct SpawnLoadout {
+    pub index: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[serde_as]
-#[derive(Deserialize)]
-struct PilotLoadout {
-    #[serde_as(as = "FixedString<42>")]
-    name: String,
-    suit: PilotSuit,
-    race: PilotRace,
-    execution: PilotExecution,
-    primary: LoadoutWeaponsAbilities,
-    primaryAttachment: PilotMod,
-    primaryMod1: PilotMod,
-    primaryMod2: PilotMod,
-    primaryMod3: PilotMod,
-    secondary: LoadoutWeaponsAbilities,
-    secondaryMod1: PilotMod,
-    secondaryMod2: PilotMod,
-    secondaryMod3: PilotMod,
-    weapon3: LoadoutWeaponsAbilities,
-    weapon3Mod1: PilotMod,
-    weapon3Mod2: PilotMod,
-    weapon3Mod3: PilotMod,
-    ordnance: LoadoutWeaponsAbilities,
-    passive1: PilotPassive,
-    passive2: PilotPassive,
-    skinIndex: i32,
-    camoIndex: i32,
-    primarySkinIndex: i32,
-    primaryCamoIndex: i32,
-    secondarySkinIndex: i32,
-    secondaryCamoIndex: i32,
-    weapon3SkinIndex: i32,
-    weapon3CamoIndex: i32,
+#[derive(Deserialize, Serialize)]
+pub struct PilotLoadout {
+    pub name: FixedString<42>,
+    pub suit: PilotSuit,
+    pub race: PilotRace,
+    pub execution: PilotExecution,
+    pub primary: LoadoutWeaponsAbilities,
+    pub primaryAttachment: PilotMod,
+    pub primaryMod1: PilotMod,
+    pub primaryMod2: PilotMod,
+    pub primaryMod3: PilotMod,
+    pub secondary: LoadoutWeaponsAbilities,
+    pub secondaryMod1: PilotMod,
+    pub secondaryMod2: PilotMod,
+    pub secondaryMod3: PilotMod,
+    pub weapon3: LoadoutWeaponsAbilities,
+    pub weapon3Mod1: PilotMod,
+    pub weapon3Mod2: PilotMod,
+    pub weapon3Mod3: PilotMod,
+    pub ordnance: LoadoutWeaponsAbilities,
+    pub passive1: PilotPassive,
+    pub passive2: PilotPassive,
+    pub skinIndex: i32,
+    pub camoIndex: i32,
+    pub primarySkinIndex: i32,
+    pub primaryCamoIndex: i32,
+    pub secondarySkinIndex: i32,
+    pub secondaryCamoIndex: i32,
+    pub weapon3SkinIndex: i32,
+    pub weapon3CamoIndex: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[serde_as]
-#[derive(Deserialize)]
-struct TitanLoadout {
-    #[serde_as(as = "FixedString<42>")]
-    name: String,
-    titanClass: TitanClass,
-    primaryMod: TitanMod,
-    special: LoadoutWeaponsAbilities,
-    antirodeo: LoadoutWeaponsAbilities,
-    passive1: TitanPassive,
-    passive2: TitanPassive,
-    passive3: TitanPassive,
-    passive4: TitanPassive,
-    passive5: TitanPassive,
-    passive6: TitanPassive,
-    titanExecution: TitanExecution,
-    skinIndex: i32,
-    camoIndex: i32,
-    decalIndex: i32,
-    primarySkinIndex: i32,
-    primaryCamoIndex: i32,
-    isPrime: TitanIsPrime, //Really should be bool, but script for loadouts is not easily set up to handle bools unfortunately...
-    primeSkinIndex: i32,
-    primeCamoIndex: i32,
-    primeDecalIndex: i32,
-    showArmBadge: i32,
+#[derive(Deserialize, Serialize)]
+pub struct TitanLoadout {
+    pub name: FixedString<42>,
+    pub titanClass: TitanClass,
+    pub primaryMod: TitanMod,
+    pub special: LoadoutWeaponsAbilities,
+    pub antirodeo: LoadoutWeaponsAbilities,
+    pub passive1: TitanPassive,
+    pub passive2: TitanPassive,
+    pub passive3: TitanPassive,
+    pub passive4: TitanPassive,
+    pub passive5: TitanPassive,
+    pub passive6: TitanPassive,
+    pub titanExecution: TitanExecution,
+    pub skinIndex: i32,
+    pub camoIndex: i32,
+    pub decalIndex: i32,
+    pub primarySkinIndex: i32,
+    pub primaryCamoIndex: i32,
+    pub isPrime: TitanIsPrime, //Really should be bool, but script for loadouts is not easily set up to handle bools unfortunately...
+    pub primeSkinIndex: i32,
+    pub primeCamoIndex: i32,
+    pub primeDecalIndex: i32,
+    pub showArmBadge: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct RecentUnlock {
-    refGuid: i32,
-    parentRefGuid: i32,
-    count: i32,
+#[derive(Deserialize, Serialize)]
+pub struct RecentUnlock {
+    pub refGuid: i32,
+    pub parentRefGuid: i32,
+    pub count: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct PveData {
-    version: i32,
-    currency: i32,
-    currencyInLatestMatch: i32,
-    tacticalUnlocks: [i32; 6],
-    feathersForMap: [i32; MAP_COUNT],
+#[derive(Deserialize, Serialize)]
+pub struct PveData {
+    pub version: i32,
+    pub currency: i32,
+    pub currencyInLatestMatch: i32,
+    pub tacticalUnlocks: [i32; 6],
+    pub feathersForMap: [i32; MAP_COUNT],
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum UnlockRef {
+#[derive(Deserialize, Serialize)]
+pub enum UnlockRef {
     edit_pilots, // these two must come first
     edit_titans,
     pilot_custom_loadout_1,
@@ -792,8 +817,8 @@ enum UnlockRef {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum BurnCard {
+#[derive(Deserialize, Serialize)]
+pub enum BurnCard {
     NULL,
     bc_conscription,
     bc_double_xp,
@@ -875,336 +900,336 @@ enum BurnCard {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct ActiveBurnCardData {
-    cardRef: BurnCard,
-    lastCardRef: BurnCard,
-    clearOnStart: bool,
+#[derive(Deserialize, Serialize)]
+pub struct ActiveBurnCardData {
+    pub cardRef: BurnCard,
+    pub lastCardRef: BurnCard,
+    pub clearOnStart: bool,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct HistoryBurnCardData {
-    collected: i32,
-    spent: i32,
+#[derive(Deserialize, Serialize)]
+pub struct HistoryBurnCardData {
+    pub collected: i32,
+    pub spent: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct BlackMarketBurnCardUpgrade {
-    cardRef: BurnCard,
+#[derive(Deserialize, Serialize)]
+pub struct BlackMarketBurnCardUpgrade {
+    pub cardRef: BurnCard,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct MapStats {
-    gamesJoined: [i32; GAME_MODE_COUNT],
-    gamesCompleted: [i32; GAME_MODE_COUNT],
-    gamesWon: [i32; GAME_MODE_COUNT],
-    gamesLost: [i32; GAME_MODE_COUNT],
-    topPlayerOnTeam: [i32; GAME_MODE_COUNT],
-    top3OnTeam: [i32; GAME_MODE_COUNT],
-    hoursPlayed: [f32; GAME_MODE_COUNT],
-    timesScored100AttritionPoints_byMap: i32,
-    winsByDifficulty: [i32; 5],
-    matchesByDifficulty: [i32; 5],
-    perfectMatchesByDifficulty: [i32; 5],
+#[derive(Deserialize, Serialize)]
+pub struct MapStats {
+    pub gamesJoined: [i32; GAME_MODE_COUNT],
+    pub gamesCompleted: [i32; GAME_MODE_COUNT],
+    pub gamesWon: [i32; GAME_MODE_COUNT],
+    pub gamesLost: [i32; GAME_MODE_COUNT],
+    pub topPlayerOnTeam: [i32; GAME_MODE_COUNT],
+    pub top3OnTeam: [i32; GAME_MODE_COUNT],
+    pub hoursPlayed: [f32; GAME_MODE_COUNT],
+    pub timesScored100AttritionPoints_byMap: i32,
+    pub winsByDifficulty: [i32; 5],
+    pub matchesByDifficulty: [i32; 5],
+    pub perfectMatchesByDifficulty: [i32; 5],
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct GameStats {
-    modesPlayed: [i32; GAME_MODE_COUNT],
-    previousModesPlayed: [i32; GAME_MODE_COUNT],
-    modesWon: [i32; GAME_MODE_COUNT],
-    mvp_total: i32,
-    gamesCompletedTotal: i32,
-    gamesWonTotal: i32,
-    gamesWonAsIMC: i32,
-    gamesWonAsMilitia: i32,
-    gamesCompletedAsIMC: i32,
-    gamesCompletedAsMilitia: i32,
-    pvpKills: [i32; GAME_MODE_COUNT],
-    timesKillDeathRatio2to1: [i32; GAME_MODE_COUNT],
-    timesKillDeathRatio2to1_pvp: [i32; GAME_MODE_COUNT],
-    timesScored100AttritionPoints_total: i32,
+#[derive(Deserialize, Serialize)]
+pub struct GameStats {
+    pub modesPlayed: [i32; GAME_MODE_COUNT],
+    pub previousModesPlayed: [i32; GAME_MODE_COUNT],
+    pub modesWon: [i32; GAME_MODE_COUNT],
+    pub mvp_total: i32,
+    pub gamesCompletedTotal: i32,
+    pub gamesWonTotal: i32,
+    pub gamesWonAsIMC: i32,
+    pub gamesWonAsMilitia: i32,
+    pub gamesCompletedAsIMC: i32,
+    pub gamesCompletedAsMilitia: i32,
+    pub pvpKills: [i32; GAME_MODE_COUNT],
+    pub timesKillDeathRatio2to1: [i32; GAME_MODE_COUNT],
+    pub timesKillDeathRatio2to1_pvp: [i32; GAME_MODE_COUNT],
+    pub timesScored100AttritionPoints_total: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct HoursPlayed {
-    total: f32,
-    asTitan: [f32; TITAN_COUNT],
-    asPilot: f32,
-    asTitanTotal: f32,
-    dead: f32,
-    wallhanging: f32,
-    wallrunning: f32,
-    inAir: f32,
+#[derive(Deserialize, Serialize)]
+pub struct HoursPlayed {
+    pub total: f32,
+    pub asTitan: [f32; TITAN_COUNT],
+    pub asPilot: f32,
+    pub asTitanTotal: f32,
+    pub dead: f32,
+    pub wallhanging: f32,
+    pub wallrunning: f32,
+    pub inAir: f32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct MilesTraveled {
-    total: f32,
-    asTitan: [f32; TITAN_COUNT],
-    asPilot: f32,
-    asTitanTotal: f32,
-    wallrunning: f32,
-    inAir: f32,
-    ziplining: f32,
-    onFriendlyTitan: f32,
-    onEnemyTitan: f32,
+#[derive(Deserialize, Serialize)]
+pub struct MilesTraveled {
+    pub total: f32,
+    pub asTitan: [f32; TITAN_COUNT],
+    pub asPilot: f32,
+    pub asTitanTotal: f32,
+    pub wallrunning: f32,
+    pub inAir: f32,
+    pub ziplining: f32,
+    pub onFriendlyTitan: f32,
+    pub onEnemyTitan: f32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct WeaponStats {
-    hoursUsed: f32,
-    hoursEquipped: f32,
-    shotsFired: i32,
-    shotsHit: i32,
-    headshots: i32,
-    critHits: i32,
-    titanDamage: i32,
+#[derive(Deserialize, Serialize)]
+pub struct WeaponStats {
+    pub hoursUsed: f32,
+    pub hoursEquipped: f32,
+    pub shotsFired: i32,
+    pub shotsHit: i32,
+    pub headshots: i32,
+    pub critHits: i32,
+    pub titanDamage: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct WeaponKillStats {
-    total: i32,
-    pilots: i32,
-    ejecting_pilots: i32,
-    spectres: i32,
-    marvins: i32,
-    grunts: i32,
-    ai: i32,
-    titansTotal: i32,
-    titans: [i32; TITAN_COUNT],
-    npcTitans: [i32; TITAN_COUNT],
-    assistsTotal: i32,
-    killingSprees: i32,
+#[derive(Deserialize, Serialize)]
+pub struct WeaponKillStats {
+    pub total: i32,
+    pub pilots: i32,
+    pub ejecting_pilots: i32,
+    pub spectres: i32,
+    pub marvins: i32,
+    pub grunts: i32,
+    pub ai: i32,
+    pub titansTotal: i32,
+    pub titans: [i32; TITAN_COUNT],
+    pub npcTitans: [i32; TITAN_COUNT],
+    pub assistsTotal: i32,
+    pub killingSprees: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct KillStats {
-    total: i32,
-    totalWhileUsingBurnCard: i32,
-    titansWhileTitanBCActive: i32,
-    totalPVP: i32,
-    pilots: i32,
-    spectres: i32,
-    marvins: i32,
-    grunts: i32,
-    totalTitans: i32,
-    totalTitansWhileDoomed: i32,
-    totalPilots: i32,
-    totalNPC: i32,
-    asPilot: i32,
-    asTitan: [i32; TITAN_COUNT],
-    firstStrikes: i32,
-    ejectingPilots: i32,
-    whileEjecting: i32,
-    cloakedPilots: i32,
-    whileCloaked: i32,
-    wallrunningPilots: i32,
-    whileWallrunning: i32,
-    wallhangingPilots: i32,
-    whileWallhanging: i32,
-    pilotExecution: i32,
-    pilotExecutePilot: i32,
-    pilotExecutePilotByType: [i32; PILOT_EXECUTION_COUNT],
-    pilotKickMelee: i32,
-    pilotKickMeleePilot: i32,
-    titanMelee: i32,
-    titanMeleePilot: i32,
-    titanStepCrush: i32,
-    titanStepCrushPilot: i32,
-    titanExocutionIon: i32,
-    titanExocutionScorch: i32,
-    titanExocutionNorthstar: i32,
-    titanExocutionRonin: i32,
-    titanExocutionTone: i32,
-    titanExocutionLegion: i32,
-    titanExocutionVanguard: i32,
-    titanFallKill: i32,
-    petTitanKillsFollowMode: i32,
-    petTitanKillsGuardMode: i32,
-    rodeo_total: i32,
-    rodeo_stryder: i32,
-    rodeo_buddy: i32,
-    rodeo_atlas: i32,
-    rodeo_ogre: i32,
-    pilot_headshots_total: i32,
-    evacShips: i32,
-    flyers: i32,
-    nuclearCore: i32,
-    evacuatingEnemies: i32,
-    exportTrapKills: i32,
-    coopChallenge_NukeTitan_Kills: i32,
-    coopChallenge_MortarTitan_Kills: i32,
-    coopChallenge_EmpTitan_Kills: i32,
-    coopChallenge_BubbleShieldGrunt_Kills: i32,
-    coopChallenge_CloakDrone_Kills: i32,
-    coopChallenge_Dropship_Kills: i32,
-    coopChallenge_SuicideSpectre_Kills: i32,
-    coopChallenge_Turret_Kills: i32,
-    coopChallenge_Sniper_Kills: i32,
-    ampedVortexKills: i32,
-    meleeWhileCloaked: i32,
-    pilotKillsWhileUsingActiveRadarPulse: i32,
-    titanKillsAsPilot: i32,
-    pilotKillsWhileStimActive: i32,
-    pilotKillsAsTitan: i32,
-    totalAssists: i32,
-    killingSpreeds: [i32; TITAN_COUNT],
-    pilotKillsAsPilot: i32,
-    titanKillsAsTitan: i32,
-    telefragKils: i32,
-    grappleKills: i32,
-    throughAWallKills: i32,
-    distractedKills: i32,
-    pilotExecutePilotWhileCloaked: i32,
-    pilotKillsWithHoloPilotActive: i32,
-    pilotKillsWithAmpedWallActive: i32,
+#[derive(Deserialize, Serialize)]
+pub struct KillStats {
+    pub total: i32,
+    pub totalWhileUsingBurnCard: i32,
+    pub titansWhileTitanBCActive: i32,
+    pub totalPVP: i32,
+    pub pilots: i32,
+    pub spectres: i32,
+    pub marvins: i32,
+    pub grunts: i32,
+    pub totalTitans: i32,
+    pub totalTitansWhileDoomed: i32,
+    pub totalPilots: i32,
+    pub totalNPC: i32,
+    pub asPilot: i32,
+    pub asTitan: [i32; TITAN_COUNT],
+    pub firstStrikes: i32,
+    pub ejectingPilots: i32,
+    pub whileEjecting: i32,
+    pub cloakedPilots: i32,
+    pub whileCloaked: i32,
+    pub wallrunningPilots: i32,
+    pub whileWallrunning: i32,
+    pub wallhangingPilots: i32,
+    pub whileWallhanging: i32,
+    pub pilotExecution: i32,
+    pub pilotExecutePilot: i32,
+    pub pilotExecutePilotByType: [i32; PILOT_EXECUTION_COUNT],
+    pub pilotKickMelee: i32,
+    pub pilotKickMeleePilot: i32,
+    pub titanMelee: i32,
+    pub titanMeleePilot: i32,
+    pub titanStepCrush: i32,
+    pub titanStepCrushPilot: i32,
+    pub titanExocutionIon: i32,
+    pub titanExocutionScorch: i32,
+    pub titanExocutionNorthstar: i32,
+    pub titanExocutionRonin: i32,
+    pub titanExocutionTone: i32,
+    pub titanExocutionLegion: i32,
+    pub titanExocutionVanguard: i32,
+    pub titanFallKill: i32,
+    pub petTitanKillsFollowMode: i32,
+    pub petTitanKillsGuardMode: i32,
+    pub rodeo_total: i32,
+    pub rodeo_stryder: i32,
+    pub rodeo_buddy: i32,
+    pub rodeo_atlas: i32,
+    pub rodeo_ogre: i32,
+    pub pilot_headshots_total: i32,
+    pub evacShips: i32,
+    pub flyers: i32,
+    pub nuclearCore: i32,
+    pub evacuatingEnemies: i32,
+    pub exportTrapKills: i32,
+    pub coopChallenge_NukeTitan_Kills: i32,
+    pub coopChallenge_MortarTitan_Kills: i32,
+    pub coopChallenge_EmpTitan_Kills: i32,
+    pub coopChallenge_BubbleShieldGrunt_Kills: i32,
+    pub coopChallenge_CloakDrone_Kills: i32,
+    pub coopChallenge_Dropship_Kills: i32,
+    pub coopChallenge_SuicideSpectre_Kills: i32,
+    pub coopChallenge_Turret_Kills: i32,
+    pub coopChallenge_Sniper_Kills: i32,
+    pub ampedVortexKills: i32,
+    pub meleeWhileCloaked: i32,
+    pub pilotKillsWhileUsingActiveRadarPulse: i32,
+    pub titanKillsAsPilot: i32,
+    pub pilotKillsWhileStimActive: i32,
+    pub pilotKillsAsTitan: i32,
+    pub totalAssists: i32,
+    pub killingSpreeds: [i32; TITAN_COUNT],
+    pub pilotKillsAsPilot: i32,
+    pub titanKillsAsTitan: i32,
+    pub telefragKils: i32,
+    pub grappleKills: i32,
+    pub throughAWallKills: i32,
+    pub distractedKills: i32,
+    pub pilotExecutePilotWhileCloaked: i32,
+    pub pilotKillsWithHoloPilotActive: i32,
+    pub pilotKillsWithAmpedWallActive: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct DeathStats {
-    total: i32,
-    totalPVP: i32,
-    asPilot: i32,
-    asTitan: [i32; TITAN_COUNT],
-    byPilots: i32,
-    bySpectres: i32,
-    byGrunts: i32,
-    byTitans: [i32; TITAN_COUNT],
-    byNPCTitans: [i32; TITAN_COUNT],
-    suicides: i32,
-    whileEjecting: i32,
+#[derive(Deserialize, Serialize)]
+pub struct DeathStats {
+    pub total: i32,
+    pub totalPVP: i32,
+    pub asPilot: i32,
+    pub asTitan: [i32; TITAN_COUNT],
+    pub byPilots: i32,
+    pub bySpectres: i32,
+    pub byGrunts: i32,
+    pub byTitans: [i32; TITAN_COUNT],
+    pub byNPCTitans: [i32; TITAN_COUNT],
+    pub suicides: i32,
+    pub whileEjecting: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct MiscStats {
-    titanFalls: i32,
-    titanFallsFirst: i32,
-    titanEmbarks: i32,
-    rodeos: i32,
-    rodeosFromEject: i32,
-    timesEjected: i32,
-    timesEjectedNuclear: i32,
-    burnCardsEarned: i32,
-    burnCardsSpent: i32,
-    boostsActivated: i32,
-    spectreLeeches: i32,
-    spectreLeechesByMap: [i32; MAP_COUNT],
-    evacsAttempted: i32,
-    evacsSurvived: i32,
-    flagsCaptured: i32,
-    flagsReturned: i32,
-    arcCannonMultiKills: i32,
-    gruntsConscripted: i32,
-    hardpointsCaptured: i32,
-    challengeTiersCompleted: i32,
-    challengesCompleted: i32,
-    dailyChallengesCompleted: i32,
-    timesLastTitanRemaining: i32,
-    killingSprees: i32,
-    coopChallengesCompleted: i32,
-    forgedCertificationsUsed: i32,
-    regenForgedCertificationsUsed: i32,
+#[derive(Deserialize, Serialize)]
+pub struct MiscStats {
+    pub titanFalls: i32,
+    pub titanFallsFirst: i32,
+    pub titanEmbarks: i32,
+    pub rodeos: i32,
+    pub rodeosFromEject: i32,
+    pub timesEjected: i32,
+    pub timesEjectedNuclear: i32,
+    pub burnCardsEarned: i32,
+    pub burnCardsSpent: i32,
+    pub boostsActivated: i32,
+    pub spectreLeeches: i32,
+    pub spectreLeechesByMap: [i32; MAP_COUNT],
+    pub evacsAttempted: i32,
+    pub evacsSurvived: i32,
+    pub flagsCaptured: i32,
+    pub flagsReturned: i32,
+    pub arcCannonMultiKills: i32,
+    pub gruntsConscripted: i32,
+    pub hardpointsCaptured: i32,
+    pub challengeTiersCompleted: i32,
+    pub challengesCompleted: i32,
+    pub dailyChallengesCompleted: i32,
+    pub timesLastTitanRemaining: i32,
+    pub killingSprees: i32,
+    pub coopChallengesCompleted: i32,
+    pub forgedCertificationsUsed: i32,
+    pub regenForgedCertificationsUsed: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct FdStats {
-    arcMinesPlaced: i32,
-    turretsPlaced: i32,
-    rodeos: i32,
-    rodeoNukes: i32,
-    arcMineZaps: i32,
-    turretKills: i32,
-    harvesterBoosts: i32,
-    wavesComplete: i32,
-    easyWins: i32,
-    normalWins: i32,
-    hardWins: i32,
-    masterWins: i32,
-    insaneWins: i32,
-    highestTitanFDLevel: i32,
+#[derive(Deserialize, Serialize)]
+pub struct FdStats {
+    pub arcMinesPlaced: i32,
+    pub turretsPlaced: i32,
+    pub rodeos: i32,
+    pub rodeoNukes: i32,
+    pub arcMineZaps: i32,
+    pub turretKills: i32,
+    pub harvesterBoosts: i32,
+    pub wavesComplete: i32,
+    pub easyWins: i32,
+    pub normalWins: i32,
+    pub hardWins: i32,
+    pub masterWins: i32,
+    pub insaneWins: i32,
+    pub highestTitanFDLevel: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct TitanStats {
-    pilots: i32,
-    titansTotal: i32,
-    ejections: i32,
-    titansWhileDoomed: i32,
-    titanDamage: i32,
-    titansAsPrime: i32,
-    pilotsAsPrime: i32,
-    executionsAsPrime: i32,
-    coresEarned: i32,
-    matchesByDifficulty: [i32; 5],
-    perfectMatchesByDifficulty: [i32; 5],
+#[derive(Deserialize, Serialize)]
+pub struct TitanStats {
+    pub pilots: i32,
+    pub titansTotal: i32,
+    pub ejections: i32,
+    pub titansWhileDoomed: i32,
+    pub titanDamage: i32,
+    pub titansAsPrime: i32,
+    pub pilotsAsPrime: i32,
+    pub executionsAsPrime: i32,
+    pub coresEarned: i32,
+    pub matchesByDifficulty: [i32; 5],
+    pub perfectMatchesByDifficulty: [i32; 5],
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct WeaponMain {
-    weaponStats: WeaponStats,
-    weaponKillStats: WeaponKillStats,
-    weaponXP: i32,
-    previousWeaponXP: i32,
-    proScreenKills: i32,
-    previousProScreenKills: i32,
-    newMods: i32,
-    unlockedMods: i32,
-    newWeaponSkins: [i32; 5],
-    unlockedWeaponSkins: [i32; 5],
-    newPrimeWeaponSkins: [i32; 6],
-    unlockedPrimeWeaponSkins: [i32; 6],
-    newFeatures: i32,
-    unlockedFeatures: i32,
+#[derive(Deserialize, Serialize)]
+pub struct WeaponMain {
+    pub weaponStats: WeaponStats,
+    pub weaponKillStats: WeaponKillStats,
+    pub weaponXP: i32,
+    pub previousWeaponXP: i32,
+    pub proScreenKills: i32,
+    pub previousProScreenKills: i32,
+    pub newMods: i32,
+    pub unlockedMods: i32,
+    pub newWeaponSkins: [i32; 5],
+    pub unlockedWeaponSkins: [i32; 5],
+    pub newPrimeWeaponSkins: [i32; 6],
+    pub unlockedPrimeWeaponSkins: [i32; 6],
+    pub newFeatures: i32,
+    pub unlockedFeatures: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct WeaponOffHand {
-    weaponStats: WeaponStats,
-    weaponKillStats: WeaponKillStats,
+#[derive(Deserialize, Serialize)]
+pub struct WeaponOffHand {
+    pub weaponStats: WeaponStats,
+    pub weaponKillStats: WeaponKillStats,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct TitanMain {
-    newPassives: [i32; 2],
-    unlockedPassives: [i32; 2],
-    newSkins: [i32; 5],
-    unlockedSkins: [i32; 5],
-    newPrimeSkins: [i32; 2],
-    unlockedPrimeSkins: [i32; 2],
-    newWeaponSkins: [i32; 5],
-    unlockedWeaponSkins: [i32; 5],
-    newPrimeWeaponSkins: i32,
-    unlockedPrimeWeaponSkins: i32,
-    newTitanDecals: [i32; 3],
-    unlockedTitanDecals: [i32; 3],
-    newPrimeTitanDecals: i32,
-    unlockedPrimeTitanDecals: i32,
-    unlockedFDUpgrades: [i32; 2],
-    newFDUpgrades: [i32; 2],
+#[derive(Deserialize, Serialize)]
+pub struct TitanMain {
+    pub newPassives: [i32; 2],
+    pub unlockedPassives: [i32; 2],
+    pub newSkins: [i32; 5],
+    pub unlockedSkins: [i32; 5],
+    pub newPrimeSkins: [i32; 2],
+    pub unlockedPrimeSkins: [i32; 2],
+    pub newWeaponSkins: [i32; 5],
+    pub unlockedWeaponSkins: [i32; 5],
+    pub newPrimeWeaponSkins: i32,
+    pub unlockedPrimeWeaponSkins: i32,
+    pub newTitanDecals: [i32; 3],
+    pub unlockedTitanDecals: [i32; 3],
+    pub newPrimeTitanDecals: i32,
+    pub unlockedPrimeTitanDecals: i32,
+    pub unlockedFDUpgrades: [i32; 2],
+    pub newFDUpgrades: [i32; 2],
 }
 
-const CHALLENGE_COUNT: usize = 177;
+pub const CHALLENGE_COUNT: usize = 177;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum Challenge {
+#[derive(Deserialize, Serialize)]
+pub enum Challenge {
     NULL,
     // General
     ch_games_played,
@@ -1423,11 +1448,11 @@ enum Challenge {
     ch_satchel_grunt_kills,
 }
 
-const DAILY_CHALLENGE_COUNT: usize = 4;
+pub const DAILY_CHALLENGE_COUNT: usize = 4;
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-enum DailyChallenge {
+#[derive(Deserialize, Serialize)]
+pub enum DailyChallenge {
     NULL,
     ch_daily_xo16_pilot_kills,
     ch_daily_emp_grenade_kills,
@@ -1435,85 +1460,190 @@ enum DailyChallenge {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct EChallenge {
-    progress: f32,
-    previousProgress: f32,
+#[derive(Deserialize, Serialize)]
+pub struct EChallenge {
+    pub progress: f32,
+    pub previousProgress: f32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct ActiveDailyChallenge {
-    reference: DailyChallenge,
-    day: i32,
+#[derive(Deserialize, Serialize)]
+pub struct ActiveDailyChallenge {
+    pub reference: DailyChallenge,
+    pub day: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[serde_as]
-#[derive(Deserialize)]
-struct PostGamePlayer {
-    #[serde_as(as = "FixedString<32>")]
-    name: String,
-    #[serde_as(as = "FixedString<22>")]
-    xuid: String,
-    level: i32,
-    gen: i32,
-    team: i32,
-    scores: [i32; 4],
-    playingRanked: bool,
-    rank: i32,
-    callsignIconIndex: i32,
-    matchPerformance: f32,
+#[derive(Deserialize, Serialize)]
+pub struct PostGamePlayer {
+    pub name: FixedString<32>,
+    pub xuid: FixedString<22>,
+    pub level: i32,
+    pub gen: i32,
+    pub team: i32,
+    pub scores: [i32; 4],
+    pub playingRanked: bool,
+    pub rank: i32,
+    pub callsignIconIndex: i32,
+    pub matchPerformance: f32,
 }
 
 #[allow(non_camel_case_types)]
-#[serde_as]
-#[derive(Deserialize)]
-struct PostGameData {
-    gameMode: i32,
-    map: i32,
-    #[serde_as(as = "FixedString<22>")]
-    myXuid: String,
-    myTeam: i32,
-    maxTeamSize: i32,
-    factionIMC: Faction,
-    factionMCOR: Faction,
-    scoreIMC: i32,
-    scoreMCOR: i32,
-    teams: bool,
-    privateMatch: bool,
-    ranked: bool,
-    hadMatchLossProtection: bool,
-    challengeUnlocks: [RecentUnlock; 6],
-    players: [PostGamePlayer; 16],
+#[derive(Deserialize, Serialize)]
+pub struct PostGameData {
+    pub gameMode: i32,
+    pub map: i32,
+    pub myXuid: FixedString<22>,
+    pub myTeam: i32,
+    pub maxTeamSize: i32,
+    pub factionIMC: Faction,
+    pub factionMCOR: Faction,
+    pub scoreIMC: i32,
+    pub scoreMCOR: i32,
+    pub teams: bool,
+    pub privateMatch: bool,
+    pub ranked: bool,
+    pub hadMatchLossProtection: bool,
+    pub challengeUnlocks: [RecentUnlock; 6],
+    pub players: [PostGamePlayer; 16],
 }
 
 #[allow(non_camel_case_types)]
-#[serde_as]
-#[derive(Deserialize)]
-struct FdPostGamePlayer {
-    #[serde_as(as = "FixedString<32>")]
-    name: String,
-    #[serde_as(as = "FixedString<22>")]
-    xuid: String,
-    awardId: i32,
-    awardValue: f32,
-    suitIndex: i32,
+#[derive(Deserialize, Serialize)]
+pub struct FdPostGamePlayer {
+    pub name: FixedString<32>,
+    pub xuid: FixedString<22>,
+    pub awardId: i32,
+    pub awardValue: f32,
+    pub suitIndex: i32,
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct FdPostGameData {
-    gameMode: i32,
-    map: i32,
-    myIndex: i32,
-    numPlayers: i32,
-    players: [FdPostGamePlayer; 4],
+#[derive(Deserialize, Serialize)]
+pub struct FdPostGameData {
+    pub gameMode: i32,
+    pub map: i32,
+    pub myIndex: i32,
+    pub numPlayers: i32,
+    pub players: [FdPostGamePlayer; 4],
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Deserialize)]
-struct Ranked {
-    isPlayingRanked: bool,
-    currentRank: i32,
+#[derive(Deserialize, Serialize)]
+pub struct Ranked {
+    pub isPlayingRanked: bool,
+    pub currentRank: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_PDATA: &[u8] = include_bytes!("../../default.pdata");
+
+    #[test]
+    fn default_round_trip() {
+        let data = from_u8(DEFAULT_PDATA).unwrap();
+        assert_eq!(to_vec(&data).unwrap(), DEFAULT_PDATA);
+    }
+
+    #[test]
+    fn stale_string_bytes_round_trip() {
+        let mut data = from_u8(DEFAULT_PDATA).unwrap();
+        // A loadout renamed to a shorter name keeps the end of the previous one
+        let mut raw = [0u8; 42];
+        raw[..30].copy_from_slice(b"short\0er name it had before\0\0\0");
+        let name: FixedString<42> = de::from_u8(&raw).unwrap();
+        data.pilotLoadouts[0].name = name;
+
+        let encoded = to_vec(&data).unwrap();
+        let decoded = from_u8(&encoded).unwrap();
+        assert_eq!(decoded.pilotLoadouts[0].name.as_str(), "short");
+        assert_eq!(to_vec(&decoded).unwrap(), encoded);
+    }
+
+    #[test]
+    fn truncated_data() {
+        let err = match from_u8(&DEFAULT_PDATA[..DEFAULT_PDATA.len() - 1]) {
+            Ok(_) => panic!("truncated data was decoded"),
+            Err(err) => err,
+        };
+        assert!(
+            err.to_string().contains("Unexpected end of input"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn invalid_enum_index() {
+        // Find the byte of an enum field by changing its value
+        let mut data = from_u8(DEFAULT_PDATA).unwrap();
+        data.activePilotLoadout.suit = match data.activePilotLoadout.suit {
+            PilotSuit::medium => PilotSuit::geist,
+            _ => PilotSuit::medium,
+        };
+        let changed = to_vec(&data).unwrap();
+        let offset = (0..DEFAULT_PDATA.len())
+            .find(|&i| DEFAULT_PDATA[i] != changed[i])
+            .unwrap();
+
+        let mut blob = DEFAULT_PDATA.to_vec();
+        blob[offset] = u8::MAX;
+        match from_u8(&blob) {
+            Err(Error::Field { path, source }) => {
+                assert_eq!(path, "activePilotLoadout.suit");
+                assert!(matches!(*source, Error::InvalidEnum(255)));
+            }
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("invalid enum index was decoded"),
+        }
+    }
+
+    /// Checks the JSON shape described in the crate docs, run with `UPDATE_SNAPSHOTS=1` to accept a changed shape.
+    #[test]
+    fn json_snapshot() {
+        let data = from_u8(DEFAULT_PDATA).unwrap();
+        let json = serde_json::to_string_pretty(&data).unwrap() + "\n";
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/default.json");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(path, &json).unwrap();
+        }
+        let snapshot = std::fs::read_to_string(path).unwrap();
+        if let Some((line, (expected, actual))) = snapshot
+            .lines()
+            .zip(json.lines())
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+        {
+            panic!(
+                "JSON differs from snapshots/default.json at line {}: expected `{}`, got `{}`",
+                line + 1,
+                expected,
+                actual
+            );
+        }
+        assert_eq!(
+            snapshot.len(),
+            json.len(),
+            "JSON length differs from the snapshot"
+        );
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        // Enums are names, not indices
+        assert_eq!(value["activePilotLoadout"]["suit"], "light");
+        // Fixed length strings are strings without padding
+        assert_eq!(value["lastFDTitanRef"], "ion");
+        assert_eq!(value["activePilotLoadout"]["name"], "marv with BIG GUN");
+        // The default data contains a NaN
+        assert!(value["mapStats"][3]["hoursPlayed"][2].is_null());
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut blob = DEFAULT_PDATA.to_vec();
+        blob.extend_from_slice(&[1, 2, 3]);
+        assert!(matches!(from_u8(&blob), Err(Error::TrailingBytes(3))));
+    }
 }
@@ -0,0 +1,553 @@
+//! Mod specific player data.
+//!
+//! Mods describe the data they add on top of the vanilla [`PlayerData`](crate::PlayerData)
+//! layout in a pdiff file. The game appends the data of every enabled mod to the vanilla data,
+//! in the order the mods are loaded.
+//!
+//! A pdiff file uses the same syntax as the game's persistent data definition:
+//!
+//! ```text
+//! // Define a new enum
+//! $ENUM_START myRanks
+//! bronze
+//! silver
+//! $ENUM_END
+//!
+//! // Add values to an enum of the mod
+//! $ENUM_ADD myRanks
+//! gold
+//! $ENUM_END
+//!
+//! // Fields appended after the vanilla data
+//! int myScore
+//! float myTime[gameModes]
+//! string{32} myTitle
+//! myRanks myRank
+//! bool myFlags[4]
+//! ```
+//!
+//! Vanilla enums can't be extended: the game sizes vanilla arrays indexed by them with the vanilla definition,
+//! so extending them would change the layout of the vanilla data every client and server relies on.
+//! Enums can have at most 256 values, as their values are stored in a single byte.
+//! Names of enums, enum values and members must be identifiers (letters, digits and `_`, not starting with a digit).
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PdiffError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("unknown enum `{0}`")]
+    UnknownEnum(String),
+    #[error("mod data is {actual} bytes long, expected {expected} bytes")]
+    InvalidLength { expected: usize, actual: usize },
+    #[error("`{0}` is too large")]
+    TooLarge(String),
+    #[error(
+        "enum `{name}` has {count} values, at most {} are supported",
+        MAX_ENUM_VALUES
+    )]
+    TooManyEnumValues { name: String, count: usize },
+    #[error("{member} contains enum value {value} which does not exist in `{name}`")]
+    InvalidEnumValue {
+        member: String,
+        name: String,
+        value: u8,
+    },
+}
+
+/// The type of a single value in mod data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberKind {
+    Int,
+    Float,
+    Bool,
+    /// A zero padded string with a fixed length in bytes
+    String(usize),
+    /// A single byte index into the named enum
+    Enum(String),
+}
+
+/// The length of an array member.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayLength {
+    Fixed(usize),
+    /// One element for each value of the named enum
+    Enum(String),
+}
+
+/// A field appended to the player data by a mod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    pub kind: MemberKind,
+    pub array: Option<ArrayLength>,
+}
+
+/// The definition of the player data a mod adds.
+#[derive(Debug, Clone, Default)]
+pub struct Pdiff {
+    /// Values appended to enums of the mod with `$ENUM_ADD`
+    pub enum_additions: HashMap<String, Vec<String>>,
+    /// Enums introduced by the mod
+    pub enums: HashMap<String, Vec<String>>,
+    /// Fields appended after the vanilla data, in storage order
+    pub members: Vec<Member>,
+}
+
+/// Enum values are stored as a single byte.
+const MAX_ENUM_VALUES: usize = u8::MAX as usize + 1;
+
+/// Whether a name can be used for an enum, enum value or member.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Number of values in the enums of the vanilla persistent data definition.
+fn vanilla_enum_size(name: &str) -> Option<usize> {
+    use crate::{
+        enum_variants, BurnCard, Challenge, DailyChallenge, Faction, GameMode,
+        LoadoutWeaponsAbilities, Map, OwnedEntitlements, PilotExecution, PilotMod, PilotPassive,
+        PilotRace, PilotSuit, TitanClass, TitanExecution, TitanIsPrime, TitanMod, TitanPassive,
+        UnlockRef,
+    };
+
+    let variants = match name {
+        "gameModes" => enum_variants::<GameMode>(),
+        "maps" => enum_variants::<Map>(),
+        "loadoutWeaponsAndAbilities" => enum_variants::<LoadoutWeaponsAbilities>(),
+        "pilotMod" => enum_variants::<PilotMod>(),
+        "titanClasses" => enum_variants::<TitanClass>(),
+        "titanMod" => enum_variants::<TitanMod>(),
+        "pilotPassive" => enum_variants::<PilotPassive>(),
+        "pilotSuit" => enum_variants::<PilotSuit>(),
+        "pilotRace" => enum_variants::<PilotRace>(),
+        "pilotExecution" => enum_variants::<PilotExecution>(),
+        "titanExecution" => enum_variants::<TitanExecution>(),
+        "titanPassive" => enum_variants::<TitanPassive>(),
+        "titanIsPrime" => enum_variants::<TitanIsPrime>(),
+        "faction" => enum_variants::<Faction>(),
+        "ownedEntitlements" => enum_variants::<OwnedEntitlements>(),
+        "unlockRefs" => enum_variants::<UnlockRef>(),
+        "BurnCard" => enum_variants::<BurnCard>(),
+        "challenge" => enum_variants::<Challenge>(),
+        "dailychallenge" => enum_variants::<DailyChallenge>(),
+        _ => return None,
+    };
+    Some(variants.len())
+}
+
+impl Pdiff {
+    /// Parses a pdiff definition.
+    pub fn parse(text: &str) -> Result<Self, PdiffError> {
+        enum Block {
+            Members,
+            EnumStart(String),
+            EnumAdd(String),
+        }
+
+        let mut pdiff = Pdiff::default();
+        let mut block = Block::Members;
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let syntax = |message: &str| PdiffError::Syntax {
+                line: line_number,
+                message: message.to_owned(),
+            };
+
+            let line = match raw_line.find("//") {
+                Some(comment) => &raw_line[..comment],
+                None => raw_line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let first = words.next().unwrap();
+            match first {
+                "$ENUM_START" | "$ENUM_ADD" => {
+                    if !matches!(block, Block::Members) {
+                        return Err(syntax("enums can not be nested"));
+                    }
+                    let name = words.next().ok_or_else(|| syntax("missing enum name"))?;
+                    if words.next().is_some() {
+                        return Err(syntax("unexpected text after enum name"));
+                    }
+                    if !is_identifier(name) {
+                        return Err(syntax("enum names must be identifiers"));
+                    }
+
+                    block = if first == "$ENUM_START" {
+                        if pdiff.enums.contains_key(name) || vanilla_enum_size(name).is_some() {
+                            return Err(syntax("enum is already defined"));
+                        }
+                        pdiff.enums.insert(name.to_owned(), Vec::new());
+                        Block::EnumStart(name.to_owned())
+                    } else {
+                        if vanilla_enum_size(name).is_some() {
+                            return Err(syntax("vanilla enums can not be extended"));
+                        }
+                        pdiff.enum_additions.entry(name.to_owned()).or_default();
+                        Block::EnumAdd(name.to_owned())
+                    };
+                }
+                "$ENUM_END" => {
+                    if matches!(block, Block::Members) {
+                        return Err(syntax("$ENUM_END without matching enum"));
+                    }
+                    block = Block::Members;
+                }
+                _ if first.starts_with('$') => {
+                    return Err(syntax("unsupported directive"));
+                }
+                _ => {
+                    let values = match &block {
+                        Block::EnumStart(name) => pdiff.enums.get_mut(name),
+                        Block::EnumAdd(name) => pdiff.enum_additions.get_mut(name),
+                        Block::Members => {
+                            let member = parse_member(line)
+                                .ok_or_else(|| syntax("expected a member like `int name[4]`"))?;
+                            if !is_identifier(&member.name) {
+                                return Err(syntax("member names must be identifiers"));
+                            }
+                            pdiff.members.push(member);
+                            continue;
+                        }
+                    };
+                    if words.next().is_some() {
+                        return Err(syntax("enum values can not contain whitespace"));
+                    }
+                    if !is_identifier(first) {
+                        return Err(syntax("enum values must be identifiers"));
+                    }
+                    values.unwrap().push(first.to_owned());
+                }
+            }
+        }
+
+        if !matches!(block, Block::Members) {
+            return Err(PdiffError::Syntax {
+                line: text.lines().count(),
+                message: "missing $ENUM_END".to_owned(),
+            });
+        }
+
+        // Make sure every referenced type exists
+        pdiff.size()?;
+        for name in pdiff.enums.keys().chain(pdiff.enum_additions.keys()) {
+            let count = pdiff.enum_size(name)?;
+            if count > MAX_ENUM_VALUES {
+                return Err(PdiffError::TooManyEnumValues {
+                    name: name.clone(),
+                    count,
+                });
+            }
+        }
+
+        Ok(pdiff)
+    }
+
+    /// Number of values in an enum, including values added by this mod.
+    pub fn enum_size(&self, name: &str) -> Result<usize, PdiffError> {
+        let base = match self.enums.get(name) {
+            Some(values) => values.len(),
+            None => vanilla_enum_size(name).ok_or_else(|| PdiffError::UnknownEnum(name.into()))?,
+        };
+        let added = self.enum_additions.get(name).map_or(0, Vec::len);
+        Ok(base + added)
+    }
+
+    fn element_size(&self, kind: &MemberKind) -> usize {
+        match kind {
+            MemberKind::Int | MemberKind::Float => 4,
+            MemberKind::Bool | MemberKind::Enum(_) => 1,
+            MemberKind::String(length) => *length,
+        }
+    }
+
+    fn array_length(&self, member: &Member) -> Result<usize, PdiffError> {
+        match &member.array {
+            None => Ok(1),
+            Some(ArrayLength::Fixed(length)) => Ok(*length),
+            Some(ArrayLength::Enum(name)) => self.enum_size(name),
+        }
+    }
+
+    /// Size in bytes of the data this mod appends to the player data.
+    pub fn size(&self) -> Result<usize, PdiffError> {
+        let mut size: usize = 0;
+        for member in &self.members {
+            if let MemberKind::Enum(name) = &member.kind {
+                self.enum_size(name)?;
+            }
+            size = self
+                .element_size(&member.kind)
+                .checked_mul(self.array_length(member)?)
+                .and_then(|member_size| size.checked_add(member_size))
+                .ok_or_else(|| PdiffError::TooLarge(member.name.clone()))?;
+        }
+        Ok(size)
+    }
+
+    /// Data for a player that has never played with this mod, every value is zeroed.
+    pub fn default_data(&self) -> Result<Vec<u8>, PdiffError> {
+        Ok(vec![0u8; self.size()?])
+    }
+
+    /// Checks that mod data matches this definition.
+    pub fn validate(&self, data: &[u8]) -> Result<(), PdiffError> {
+        let expected = self.size()?;
+        if data.len() != expected {
+            return Err(PdiffError::InvalidLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let mut offset = 0;
+        for member in &self.members {
+            let element_size = self.element_size(&member.kind);
+            let length = self.array_length(member)?;
+            if let MemberKind::Enum(name) = &member.kind {
+                let values = self.enum_size(name)?;
+                if let Some(&value) = data[offset..offset + length]
+                    .iter()
+                    .find(|&&v| v as usize >= values)
+                {
+                    return Err(PdiffError::InvalidEnumValue {
+                        member: member.name.clone(),
+                        name: name.clone(),
+                        value,
+                    });
+                }
+            }
+            offset += element_size * length;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_member(line: &str) -> Option<Member> {
+    let mut words = line.split_whitespace();
+    let kind = words.next()?;
+    let declaration = words.next()?;
+    if words.next().is_some() {
+        return None;
+    }
+
+    let kind = match kind {
+        "int" => MemberKind::Int,
+        "float" => MemberKind::Float,
+        "bool" => MemberKind::Bool,
+        _ => match kind.strip_prefix("string{") {
+            Some(rest) => MemberKind::String(rest.strip_suffix('}')?.parse().ok()?),
+            None => MemberKind::Enum(kind.to_owned()),
+        },
+    };
+
+    let (name, array) = match declaration.split_once('[') {
+        Some((name, rest)) => {
+            let length = rest.strip_suffix(']')?;
+            let array = match length.parse() {
+                Ok(length) => ArrayLength::Fixed(length),
+                Err(_) => ArrayLength::Enum(length.to_owned()),
+            };
+            (name, Some(array))
+        }
+        None => (declaration, None),
+    };
+
+    Some(Member {
+        name: name.to_owned(),
+        kind,
+        array,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "
+        // Define a new enum
+        $ENUM_START myRanks
+        bronze
+        silver
+        $ENUM_END
+
+        $ENUM_ADD myRanks
+        gold // added later
+        $ENUM_END
+
+        int myScore
+        float myTime[gameModes]
+        string{32} myTitle
+        myRanks myRank
+        bool myFlags[4]
+        myRanks rankHistory[myRanks]
+    ";
+
+    #[test]
+    fn parse_example() {
+        let pdiff = Pdiff::parse(EXAMPLE).unwrap();
+        assert_eq!(pdiff.enums["myRanks"], ["bronze", "silver"]);
+        assert_eq!(pdiff.enum_additions["myRanks"], ["gold"]);
+        assert_eq!(pdiff.enum_size("myRanks").unwrap(), 3);
+        assert_eq!(
+            pdiff.members[1],
+            Member {
+                name: "myTime".to_owned(),
+                kind: MemberKind::Float,
+                array: Some(ArrayLength::Enum("gameModes".to_owned())),
+            }
+        );
+        assert_eq!(pdiff.members[2].kind, MemberKind::String(32));
+        assert_eq!(
+            pdiff.members[3].kind,
+            MemberKind::Enum("myRanks".to_owned())
+        );
+
+        let game_modes = vanilla_enum_size("gameModes").unwrap();
+        assert_eq!(pdiff.size().unwrap(), 4 + 4 * game_modes + 32 + 1 + 4 + 3);
+    }
+
+    #[test]
+    fn default_data_is_valid() {
+        let pdiff = Pdiff::parse(EXAMPLE).unwrap();
+        let data = pdiff.default_data().unwrap();
+        assert_eq!(data.len(), pdiff.size().unwrap());
+        pdiff.validate(&data).unwrap();
+    }
+
+    #[test]
+    fn validate_rejects_wrong_length_and_enum_values() {
+        let pdiff =
+            Pdiff::parse("$ENUM_START colors\nred\nblue\n$ENUM_END\ncolors color[2]").unwrap();
+        pdiff.validate(&[1, 0]).unwrap();
+        assert!(matches!(
+            pdiff.validate(&[0]),
+            Err(PdiffError::InvalidLength {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        assert!(matches!(
+            pdiff.validate(&[0, 2]),
+            Err(PdiffError::InvalidEnumValue { value: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn vanilla_enums_can_not_be_extended() {
+        let err = Pdiff::parse("$ENUM_ADD gameModes\nmy_mode\n$ENUM_END").unwrap_err();
+        assert!(
+            matches!(&err, PdiffError::Syntax { line: 1, message } if message == "vanilla enums can not be extended"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn vanilla_enums_can_not_be_redefined() {
+        let err = Pdiff::parse("$ENUM_START maps\nmp_mine\n$ENUM_END").unwrap_err();
+        assert!(matches!(err, PdiffError::Syntax { line: 1, .. }), "{}", err);
+    }
+
+    #[test]
+    fn syntax_errors_report_line() {
+        for (text, line) in [
+            ("int", 1),
+            ("int a\n\nint b c", 3),
+            ("$ENUM_START a\n$ENUM_START b", 2),
+            ("$ENUM_END", 1),
+            ("$ENUM_START a\nx\n", 2),
+            ("$INCLUDE other", 1),
+            ("$ENUM_START a\ntwo words\n$ENUM_END", 2),
+            ("string{x} name", 1),
+            ("int 1st", 1),
+            ("int my-score", 1),
+            (
+                "$ENUM_START my.enum
+$ENUM_END",
+                1,
+            ),
+            (
+                "$ENUM_START a
+valid
+not-valid
+$ENUM_END",
+                3,
+            ),
+        ] {
+            match Pdiff::parse(text) {
+                Err(PdiffError::Syntax { line: actual, .. }) => {
+                    assert_eq!(actual, line, "{}", text)
+                }
+                other => panic!("{:?} parsed as {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_enums_are_rejected() {
+        assert!(matches!(
+            Pdiff::parse("missing value"),
+            Err(PdiffError::UnknownEnum(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            Pdiff::parse("int values[missing]"),
+            Err(PdiffError::UnknownEnum(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            Pdiff::parse("$ENUM_ADD missing\nvalue\n$ENUM_END"),
+            Err(PdiffError::UnknownEnum(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn enums_fit_in_a_byte() {
+        let values = |count: usize| {
+            (0..count)
+                .map(|i| format!("value{}\n", i))
+                .collect::<String>()
+        };
+
+        let text = format!("$ENUM_START full\n{}$ENUM_END", values(256));
+        assert_eq!(Pdiff::parse(&text).unwrap().enum_size("full").unwrap(), 256);
+
+        let text = format!("$ENUM_START large\n{}$ENUM_END", values(257));
+        assert!(matches!(
+            Pdiff::parse(&text),
+            Err(PdiffError::TooManyEnumValues { name, count: 257 }) if name == "large"
+        ));
+
+        // Additions count towards the limit
+        let text = format!(
+            "$ENUM_START grown\n{}$ENUM_END\n$ENUM_ADD grown\nlast\n$ENUM_END",
+            values(256)
+        );
+        assert!(matches!(
+            Pdiff::parse(&text),
+            Err(PdiffError::TooManyEnumValues { count: 257, .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_members_are_rejected() {
+        let text = format!("int huge[{}]", usize::MAX / 2);
+        assert!(matches!(Pdiff::parse(&text), Err(PdiffError::TooLarge(name)) if name == "huge"));
+
+        let text = format!("string{{{}}} a\nstring{{{}}} b", usize::MAX, 1);
+        assert!(matches!(Pdiff::parse(&text), Err(PdiffError::TooLarge(name)) if name == "b"));
+    }
+}
@@ -0,0 +1,175 @@
+use std::ops::RangeInclusive;
+
+use thiserror::Error;
+
+use crate::PlayerData;
+
+/// Number of pilot and titan loadout slots.
+const LOADOUT_COUNT: i32 = 10;
+
+/// Semantic checks applied to structurally valid player data.
+///
+/// Every check is optional, a default instance accepts all data.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    /// Allowed range for the player's generation (regen count)
+    pub gen: Option<RangeInclusive<i32>>,
+    /// Allowed range for the player's total xp
+    pub xp: Option<RangeInclusive<i32>>,
+    /// Whether selected loadout indices must point to an existing loadout slot
+    pub loadout_indices: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum ValidationError {
+    #[error("{field} is {value}, which is outside of the allowed range {min}..={max}")]
+    OutOfRange {
+        field: &'static str,
+        value: i32,
+        min: i32,
+        max: i32,
+    },
+}
+
+fn check_range(
+    field: &'static str,
+    value: i32,
+    range: &RangeInclusive<i32>,
+) -> Result<(), ValidationError> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(ValidationError::OutOfRange {
+            field,
+            value,
+            min: *range.start(),
+            max: *range.end(),
+        })
+    }
+}
+
+impl PlayerData {
+    /// Checks that the values in the player data are plausible.
+    pub fn validate(&self, limits: &Limits) -> Result<(), ValidationError> {
+        if let Some(range) = &limits.gen {
+            check_range("gen", self.gen, range)?;
+        }
+
+        if let Some(range) = &limits.xp {
+            check_range("xp", self.xp, range)?;
+        }
+
+        if limits.loadout_indices {
+            let slots = 0..=LOADOUT_COUNT - 1;
+            check_range(
+                "pilotSpawnLoadout.index",
+                self.pilotSpawnLoadout.index,
+                &slots,
+            )?;
+            check_range(
+                "titanSpawnLoadout.index",
+                self.titanSpawnLoadout.index,
+                &slots,
+            )?;
+            check_range(
+                "activeTitanLoadoutIndex",
+                self.activeTitanLoadoutIndex,
+                &slots,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_PDATA: &[u8] = include_bytes!("../../default.pdata");
+
+    fn default_data() -> Box<PlayerData> {
+        crate::from_u8(DEFAULT_PDATA).unwrap()
+    }
+
+    fn strict() -> Limits {
+        Limits {
+            gen: Some(1..=10),
+            xp: Some(0..=1000),
+            loadout_indices: true,
+        }
+    }
+
+    fn out_of_range(result: Result<(), ValidationError>) -> (&'static str, i32) {
+        match result {
+            Err(ValidationError::OutOfRange { field, value, .. }) => (field, value),
+            Ok(()) => panic!("data was accepted"),
+        }
+    }
+
+    #[test]
+    fn accepts_default_data() {
+        let data = default_data();
+        data.validate(&Limits::default()).unwrap();
+        data.validate(&strict()).unwrap();
+    }
+
+    #[test]
+    fn default_limits_accept_anything() {
+        let mut data = default_data();
+        data.gen = -5;
+        data.xp = i32::MAX;
+        data.pilotSpawnLoadout.index = 100;
+        data.validate(&Limits::default()).unwrap();
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let mut data = default_data();
+        data.gen = 11;
+        assert_eq!(out_of_range(data.validate(&strict())), ("gen", 11));
+
+        data.gen = 0;
+        assert_eq!(out_of_range(data.validate(&strict())), ("gen", 0));
+
+        data.gen = 1;
+        data.xp = 1001;
+        assert_eq!(out_of_range(data.validate(&strict())), ("xp", 1001));
+
+        data.xp = -1;
+        assert_eq!(out_of_range(data.validate(&strict())), ("xp", -1));
+    }
+
+    #[test]
+    fn rejects_loadout_indices() {
+        let limits = Limits {
+            loadout_indices: true,
+            ..Limits::default()
+        };
+
+        let mut data = default_data();
+        data.pilotSpawnLoadout.index = LOADOUT_COUNT;
+        assert_eq!(
+            out_of_range(data.validate(&limits)),
+            ("pilotSpawnLoadout.index", LOADOUT_COUNT)
+        );
+
+        let mut data = default_data();
+        data.titanSpawnLoadout.index = -1;
+        assert_eq!(
+            out_of_range(data.validate(&limits)),
+            ("titanSpawnLoadout.index", -1)
+        );
+
+        let mut data = default_data();
+        data.activeTitanLoadoutIndex = 42;
+        assert_eq!(
+            out_of_range(data.validate(&limits)),
+            ("activeTitanLoadoutIndex", 42)
+        );
+
+        // The last slot is still valid
+        data.activeTitanLoadoutIndex = LOADOUT_COUNT - 1;
+        data.validate(&limits).unwrap();
+    }
+}
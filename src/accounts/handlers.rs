@@ -1,14 +1,14 @@
-use std::{io::Read, net::SocketAddr};
+use std::{io::Read, net::IpAddr, sync::Arc};
 
 use futures_util::StreamExt;
 use serde::Deserialize;
 use thiserror::Error;
 use tracing::debug;
-use warp::{multipart::FormData, Buf};
+use warp::{http::StatusCode, multipart::FormData, Buf};
 
-use crate::{accounts::default_persistent_data, api::ApiErrorKind, id::UniqueId, SharedServerList};
+use crate::{api::ApiErrorKind, id::UniqueId, leaderboard, SharedServerList};
 
-use super::{AccountId, AccountRepository};
+use super::{pdiff, persistent_data_limits, AccountId, AccountRepository};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -23,20 +23,53 @@ pub(super) enum WritePersistenceError {
     InvalidAccount,
     #[error("this server is not allowed to update the persistent data")]
     NotPermitted,
-    #[error("persistent data is invalid")]
-    InvalidData,
+    #[error("persistent data is invalid: {0}")]
+    InvalidData(#[from] player_data::Error),
+    #[error("persistent data is not plausible: {0}")]
+    ImplausibleData(#[from] player_data::ValidationError),
+    #[error("persistent data for mod {name} is invalid: {source}")]
+    InvalidModData {
+        name: String,
+        source: player_data::pdiff::PdiffError,
+    },
     #[error("persistent data is missing")]
     MissingData,
 }
 
+impl From<pdiff::SplitError> for WritePersistenceError {
+    fn from(err: pdiff::SplitError) -> Self {
+        match err {
+            pdiff::SplitError::Mod { name, error } => WritePersistenceError::InvalidModData {
+                name,
+                source: error,
+            },
+            pdiff::SplitError::TrailingBytes(count) => {
+                WritePersistenceError::InvalidData(player_data::Error::TrailingBytes(count))
+            }
+        }
+    }
+}
+
 impl ApiErrorKind for WritePersistenceError {
     fn kind(&self) -> &'static str {
         match self {
             WritePersistenceError::InvalidAccount => "PLAYER_NOT_FOUND",
             WritePersistenceError::NotPermitted => "UNAUTHORIZED_GAMESERVER",
-            WritePersistenceError::InvalidData | WritePersistenceError::MissingData => {
-                "INVALID_PERSISTENT_DATA"
-            }
+            WritePersistenceError::InvalidData(_)
+            | WritePersistenceError::ImplausibleData(_)
+            | WritePersistenceError::InvalidModData { .. }
+            | WritePersistenceError::MissingData => "INVALID_PERSISTENT_DATA",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            WritePersistenceError::InvalidAccount => StatusCode::NOT_FOUND,
+            WritePersistenceError::NotPermitted => StatusCode::FORBIDDEN,
+            WritePersistenceError::InvalidData(_)
+            | WritePersistenceError::ImplausibleData(_)
+            | WritePersistenceError::InvalidModData { .. }
+            | WritePersistenceError::MissingData => StatusCode::BAD_REQUEST,
         }
     }
 }
@@ -44,67 +77,266 @@ impl ApiErrorKind for WritePersistenceError {
 pub(super) async fn write_persistence(
     param: WritePersistenceParam,
     mut data: FormData,
-    remote: Option<SocketAddr>,
-    accounts: AccountRepository,
+    ip: IpAddr,
+    accounts: Arc<dyn AccountRepository>,
     servers: SharedServerList,
 ) -> Result<(), WritePersistenceError> {
-    let ip = remote.unwrap().ip();
+    check_permission(
+        param.id,
+        &param.server_id,
+        ServerCredentials::Address,
+        ip,
+        accounts.as_ref(),
+        &servers,
+    )
+    .await?;
 
-    if !accounts.exists(param.id).await.unwrap() {
+    let mut file = data
+        .next()
+        .await
+        .ok_or(WritePersistenceError::MissingData)?
+        .map_err(|_| WritePersistenceError::MissingData)?;
+    let mut buffer = Vec::new();
+    file.data()
+        .await
+        .ok_or(WritePersistenceError::MissingData)?
+        .map_err(|_| WritePersistenceError::MissingData)?
+        .reader()
+        .read_to_end(&mut buffer)
+        .map_err(|_| WritePersistenceError::MissingData)?;
+
+    store_persistence(
+        param.id,
+        &param.server_id,
+        &buffer,
+        accounts.as_ref(),
+        &servers,
+    )
+    .await
+}
+
+/// Body of the versioned `write_persistence` route, the server auth token is sent as bearer token.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct WritePersistenceBody {
+    id: AccountId,
+    server_id: UniqueId,
+    /// The same data the legacy route receives as file, including the data of mods
+    persistent_data: Vec<u8>,
+}
+
+pub(super) async fn write_persistence_v2(
+    body: WritePersistenceBody,
+    token: Option<UniqueId>,
+    ip: IpAddr,
+    accounts: Arc<dyn AccountRepository>,
+    servers: SharedServerList,
+) -> Result<(), WritePersistenceError> {
+    check_permission(
+        body.id,
+        &body.server_id,
+        ServerCredentials::Token(token),
+        ip,
+        accounts.as_ref(),
+        &servers,
+    )
+    .await?;
+
+    store_persistence(
+        body.id,
+        &body.server_id,
+        &body.persistent_data,
+        accounts.as_ref(),
+        &servers,
+    )
+    .await
+}
+
+/// How a game server proves it sent a request.
+enum ServerCredentials {
+    /// The request comes from the address of the server, which is all the legacy route checks
+    Address,
+    /// The request comes from the address of the server and contains its auth token
+    Token(Option<UniqueId>),
+}
+
+/// Checks that the player data is written by the player itself or the server it is playing on.
+async fn check_permission(
+    id: AccountId,
+    server_id: &UniqueId,
+    credentials: ServerCredentials,
+    ip: IpAddr,
+    accounts: &dyn AccountRepository,
+    servers: &SharedServerList,
+) -> Result<(), WritePersistenceError> {
+    if !accounts.exists(id).await.unwrap() {
         return Err(WritePersistenceError::InvalidAccount);
     }
 
     let auth_data = accounts
-        .get_auth(param.id)
+        .get_auth(id)
         .await
         .map_err(|_| WritePersistenceError::NotPermitted)?;
-    let mut allowed = auth_data.last_auth_ip == ip;
-    if !allowed {
-        // Check if player is on given server and request was sent by it
-        if let Some(current_server_id) = auth_data.current_server {
-            if param.server_id == current_server_id {
-                let servers = servers.read().await;
-                if let Some(server) = servers.get(&param.server_id) {
-                    if server.ip() == ip {
-                        allowed = true;
-                    }
-                }
+    if auth_data.last_auth_ip == ip {
+        return Ok(());
+    }
+
+    // Check if player is on given server and request was sent by it
+    if auth_data.current_server.as_ref() == Some(server_id) {
+        let servers = servers.read().await;
+        if let Some(server) = servers.get(server_id) {
+            let authorized = match credentials {
+                ServerCredentials::Address => true,
+                ServerCredentials::Token(token) => server.has_auth_token(token),
+            };
+            if authorized && server.ip() == ip {
+                return Ok(());
             }
         }
     }
 
-    if !allowed {
-        return Err(WritePersistenceError::NotPermitted);
-    }
+    Err(WritePersistenceError::NotPermitted)
+}
 
-    // TODO: Way too many unwraps here, figure out how to clean this mess
-    let mut file = data
-        .next()
-        .await
-        .ok_or(WritePersistenceError::MissingData)
-        .unwrap()
-        .unwrap();
-    let mut buffer = Vec::new();
-    file.data()
+/// Validates and stores player data uploaded by a game server, and indexes its ranked values.
+async fn store_persistence(
+    id: AccountId,
+    server_id: &UniqueId,
+    buffer: &[u8],
+    accounts: &dyn AccountRepository,
+    servers: &SharedServerList,
+) -> Result<(), WritePersistenceError> {
+    // Game servers append the data of their mods after the vanilla data
+    let mods: Vec<String> = servers
+        .read()
         .await
-        .unwrap()
-        .unwrap()
-        .reader()
-        .read_to_end(&mut buffer)
-        .unwrap();
-    let expected_length = default_persistent_data().len();
-    if buffer.len() != expected_length {
-        debug!(
-            expected_length,
-            actual_length = buffer.len(),
-            "invalid persistent data length"
-        );
-        return Err(WritePersistenceError::InvalidData);
-    }
+        .get(server_id)
+        .map(|server| server.mod_names().map(str::to_owned).collect())
+        .unwrap_or_default();
+    let (vanilla, mod_data) = pdiff::split(
+        pdiff::definitions(),
+        buffer,
+        mods.iter().map(String::as_str),
+    )?;
+
+    let player_data = player_data::from_u8(vanilla).map_err(|err| {
+        debug!(error = %err, length = buffer.len(), "invalid persistent data");
+        err
+    })?;
+    player_data.validate(persistent_data_limits())?;
 
+    // Index ranked values now so leaderboards don't need to decode the data of every player
     accounts
-        .set_data(param.id, &buffer)
+        .set_data(id, vanilla, &mod_data, &leaderboard::extract(&player_data))
         .await
         .expect("Error writing account persistent data");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::RwLock;
+
+    use super::*;
+    use crate::{
+        accounts::{account_repository, default_persistent_data},
+        database::test_databases,
+        game_servers::{server_repository, ServerList},
+    };
+
+    struct Setup {
+        accounts: Arc<dyn AccountRepository>,
+        servers: SharedServerList,
+        id: AccountId,
+    }
+
+    /// An account that last logged in from [`ip`], so it may write its own data.
+    async fn setups() -> Vec<Setup> {
+        let mut setups = Vec::new();
+        for database in test_databases().await {
+            let accounts = account_repository(database.clone());
+            let id = AccountId(rand::random::<u32>().into());
+            accounts.create(id).await.unwrap();
+            accounts.create_token(id, ip()).await.unwrap();
+            let servers = ServerList::load(server_repository(database.clone()))
+                .await
+                .unwrap();
+            setups.push(Setup {
+                accounts,
+                servers: Arc::new(RwLock::new(servers)),
+                id,
+            });
+        }
+        setups
+    }
+
+    fn ip() -> IpAddr {
+        [127, 0, 0, 1].into()
+    }
+
+    async fn write(setup: &Setup, data: Vec<u8>) -> Result<(), WritePersistenceError> {
+        write_persistence_v2(
+            WritePersistenceBody {
+                id: setup.id,
+                server_id: UniqueId::new(rand::thread_rng()),
+                persistent_data: data,
+            },
+            None,
+            ip(),
+            setup.accounts.clone(),
+            setup.servers.clone(),
+        )
+        .await
+    }
+
+    fn default_data() -> Box<player_data::PlayerData> {
+        player_data::from_u8(default_persistent_data()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stores_valid_data() {
+        for setup in setups().await {
+            let mut data = default_data();
+            data.netWorth = 42;
+            let data = player_data::to_vec(&data).unwrap();
+
+            write(&setup, data.clone()).await.unwrap();
+            assert_eq!(setup.accounts.get_data(setup.id).await.unwrap(), &data[..]);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_data() {
+        for setup in setups().await {
+            let truncated = default_persistent_data()[..100].to_vec();
+            let err = write(&setup, truncated).await.unwrap_err();
+            assert!(matches!(err, WritePersistenceError::InvalidData(_)));
+            assert_eq!(err.kind(), "INVALID_PERSISTENT_DATA");
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+
+            let mut trailing = default_persistent_data().to_vec();
+            trailing.push(0);
+            let err = write(&setup, trailing).await.unwrap_err();
+            assert_eq!(err.kind(), "INVALID_PERSISTENT_DATA");
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_implausible_data() {
+        for setup in setups().await {
+            let mut data = default_data();
+            data.pilotSpawnLoadout.index = 10;
+            let err = write(&setup, player_data::to_vec(&data).unwrap())
+                .await
+                .unwrap_err();
+            assert!(matches!(err, WritePersistenceError::ImplausibleData(_)));
+            assert_eq!(err.kind(), "INVALID_PERSISTENT_DATA");
+
+            // Nothing was stored
+            assert_eq!(
+                setup.accounts.get_data(setup.id).await.unwrap(),
+                default_persistent_data()
+            );
+        }
+    }
+}
@@ -1,14 +1,17 @@
+use std::{sync::Arc, time::Duration};
+
 use once_cell::sync::OnceCell;
-pub use repository::AccountRepository;
-pub use routes::{routes, with_accounts};
+pub use repository::{account_repository, token_lifetime, token_secret, AccountRepository};
+pub use routes::{routes, v2_routes, with_accounts};
 use serde::{Deserialize, Serialize};
 
 mod handlers;
+pub mod pdiff;
 mod repository;
 mod routes;
 
 /// The unique identifier for an account
-#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(transparent)]
 pub struct AccountId(pub u64);
 
@@ -18,31 +21,62 @@ impl std::fmt::Display for AccountId {
     }
 }
 
-// As sqlite doesn't explicitly support unsigned integers, we convert to and from an signed 64 bit integer
-impl<'q> sqlx::Encode<'q, sqlx::Sqlite> for AccountId {
+// As sqlite and postgres don't support unsigned integers, we convert to and from an signed 64 bit integer
+impl<'q, DB: sqlx::Database> sqlx::Encode<'q, DB> for AccountId
+where
+    i64: sqlx::Encode<'q, DB>,
+{
     fn encode_by_ref(
         &self,
-        buf: &mut <sqlx::Sqlite as sqlx::database::HasArguments<'q>>::ArgumentBuffer,
+        buf: &mut <DB as sqlx::database::HasArguments<'q>>::ArgumentBuffer,
     ) -> sqlx::encode::IsNull {
         (self.0 as i64).encode_by_ref(buf)
     }
 }
 
-impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for AccountId {
+impl<'r, DB: sqlx::Database> sqlx::Decode<'r, DB> for AccountId
+where
+    i64: sqlx::Decode<'r, DB>,
+{
     fn decode(
-        value: <sqlx::Sqlite as sqlx::database::HasValueRef<'r>>::ValueRef,
+        value: <DB as sqlx::database::HasValueRef<'r>>::ValueRef,
     ) -> Result<Self, sqlx::error::BoxDynError> {
-        Ok(Self(i64::decode(value)? as u64))
+        Ok(Self(<i64 as sqlx::Decode<DB>>::decode(value)? as u64))
     }
 }
 
-impl sqlx::Type<sqlx::Sqlite> for AccountId {
-    fn type_info() -> <sqlx::Sqlite as sqlx::Database>::TypeInfo {
-        i64::type_info()
+impl<DB: sqlx::Database> sqlx::Type<DB> for AccountId
+where
+    i64: sqlx::Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <i64 as sqlx::Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <i64 as sqlx::Type<DB>>::compatible(ty)
     }
 }
 
-fn default_persistent_data() -> &'static [u8] {
+/// How often expired sessions are removed.
+const SESSION_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Removes expired sessions in the background.
+///
+/// Logging in only removes the expired sessions of that account, sessions of players that never return would stay forever.
+pub async fn prune_sessions(accounts: Arc<dyn AccountRepository>) {
+    let mut interval = tokio::time::interval(SESSION_PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match accounts.remove_expired_sessions().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "removed expired sessions"),
+            Err(err) => tracing::warn!(%err, "unable to remove expired sessions"),
+        }
+    }
+}
+
+pub(crate) fn default_persistent_data() -> &'static [u8] {
     static INSTANCE: OnceCell<Vec<u8>> = OnceCell::new();
     INSTANCE
         .get_or_init(|| {
@@ -51,3 +85,74 @@ fn default_persistent_data() -> &'static [u8] {
         })
         .as_slice()
 }
+
+/// Semantic checks applied to uploaded persistent data.
+///
+/// The generation and xp checks are enabled by setting `PERSISTENCE_MAX_GEN` and `PERSISTENCE_MAX_XP`.
+/// Loadout index checks are enabled unless `PERSISTENCE_CHECK_LOADOUTS` is `false`.
+/// Called when the master server starts, so invalid values stop it instead of failing requests.
+pub fn persistent_data_limits() -> &'static player_data::Limits {
+    static INSTANCE: OnceCell<player_data::Limits> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        parse_limits(|name| std::env::var(name).ok()).unwrap_or_else(|err| panic!("{}", err))
+    })
+}
+
+fn parse_limits(var: impl Fn(&str) -> Option<String>) -> Result<player_data::Limits, String> {
+    let max = |name: &str, min: i32| {
+        var(name)
+            .map(|v| match v.parse::<i32>() {
+                Ok(max) if max >= min => Ok(min..=max),
+                _ => Err(format!("{} must be an integer of at least {}", name, min)),
+            })
+            .transpose()
+    };
+
+    Ok(player_data::Limits {
+        gen: max("PERSISTENCE_MAX_GEN", 1)?,
+        xp: max("PERSISTENCE_MAX_XP", 0)?,
+        loadout_indices: var("PERSISTENCE_CHECK_LOADOUTS")
+            .map(|v| {
+                v.parse::<bool>()
+                    .map_err(|_| "PERSISTENCE_CHECK_LOADOUTS must be true or false".to_owned())
+            })
+            .transpose()?
+            .unwrap_or(true),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(vars: &[(&str, &str)]) -> Result<player_data::Limits, String> {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+        parse_limits(|name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn parses_limits() {
+        let limits = parse(&[]).unwrap();
+        assert!(limits.gen.is_none() && limits.xp.is_none() && limits.loadout_indices);
+
+        let limits = parse(&[
+            ("PERSISTENCE_MAX_GEN", "50"),
+            ("PERSISTENCE_MAX_XP", "0"),
+            ("PERSISTENCE_CHECK_LOADOUTS", "false"),
+        ])
+        .unwrap();
+        assert_eq!(limits.gen, Some(1..=50));
+        assert_eq!(limits.xp, Some(0..=0));
+        assert!(!limits.loadout_indices);
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        assert!(parse(&[("PERSISTENCE_MAX_GEN", "many")]).is_err());
+        assert!(parse(&[("PERSISTENCE_MAX_GEN", "0")]).is_err());
+        assert!(parse(&[("PERSISTENCE_MAX_XP", "-1")]).is_err());
+        assert!(parse(&[("PERSISTENCE_CHECK_LOADOUTS", "yes")]).is_err());
+    }
+}
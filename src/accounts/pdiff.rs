@@ -0,0 +1,216 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use once_cell::sync::OnceCell;
+use player_data::pdiff::{Pdiff, PdiffError};
+use tracing::{info, warn};
+
+use super::{default_persistent_data, AccountId, AccountRepository};
+
+/// Pdiff definitions of all known mods, keyed by mod name.
+///
+/// Definitions are read from the directory in `PDIFF_DIRECTORY` (default `pdiffs/`),
+/// each file is named after the mod it belongs to, for example `pdiffs/Mod.Name.pdiff`.
+/// They are loaded when the master server starts, which fails if a definition is invalid.
+pub fn definitions() -> &'static HashMap<String, Pdiff> {
+    static INSTANCE: OnceCell<HashMap<String, Pdiff>> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        let directory = std::env::var("PDIFF_DIRECTORY").unwrap_or_else(|_| "pdiffs".to_owned());
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!(
+                    directory = directory.as_str(),
+                    %err,
+                    "unable to read pdiff directory, mod data will not be stored"
+                );
+                return HashMap::new();
+            }
+        };
+
+        let mut definitions = HashMap::new();
+        for entry in entries {
+            let path = entry.expect("Unable to read pdiff directory").path();
+            if path.extension().is_none_or(|e| e != "pdiff") {
+                continue;
+            }
+
+            let name = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .expect("Pdiff file name is not valid UTF-8")
+                .to_owned();
+            let text = std::fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("Unable to read {}: {}", path.display(), err));
+            let pdiff = Pdiff::parse(&text)
+                .unwrap_or_else(|err| panic!("Invalid pdiff {}: {}", path.display(), err));
+            info!(name = name.as_str(), "loaded pdiff");
+            definitions.insert(name, pdiff);
+        }
+        definitions
+    })
+}
+
+/// Data appended to the player data by a mod.
+pub struct ModData<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+}
+
+/// Errors when splitting uploaded data into vanilla and mod data.
+#[derive(Debug)]
+pub enum SplitError {
+    /// The data of a mod does not match its definition
+    Mod { name: String, error: PdiffError },
+    /// There is data left over after all mods
+    TrailingBytes(usize),
+}
+
+/// Splits player data uploaded by a game server into the vanilla data and the data of each mod.
+///
+/// The mods must be passed in the order the game server loaded them.
+/// Mods without a pdiff in `definitions` are assumed to not add any data.
+pub fn split<'a>(
+    definitions: &HashMap<String, Pdiff>,
+    data: &'a [u8],
+    mods: impl IntoIterator<Item = &'a str>,
+) -> Result<(&'a [u8], Vec<ModData<'a>>), SplitError> {
+    // Shorter data is rejected when the vanilla part is parsed
+    let vanilla_length = default_persistent_data().len().min(data.len());
+    let (vanilla, mut remaining) = data.split_at(vanilla_length);
+
+    let mut mod_data = Vec::new();
+    for name in mods {
+        let pdiff = match definitions.get(name) {
+            Some(p) => p,
+            None => continue,
+        };
+        let mod_error = |error| SplitError::Mod {
+            name: name.to_owned(),
+            error,
+        };
+
+        let size = pdiff.size().map_err(mod_error)?;
+        if remaining.len() < size {
+            return Err(mod_error(PdiffError::InvalidLength {
+                expected: size,
+                actual: remaining.len(),
+            }));
+        }
+        let (data, rest) = remaining.split_at(size);
+        pdiff.validate(data).map_err(mod_error)?;
+        mod_data.push(ModData { name, data });
+        remaining = rest;
+    }
+
+    if remaining.is_empty() {
+        Ok((vanilla, mod_data))
+    } else {
+        Err(SplitError::TrailingBytes(remaining.len()))
+    }
+}
+
+/// Appends the stored data of each mod to the vanilla player data, in the format game servers expect.
+///
+/// Players that have no stored data for a mod (or data for an outdated definition) get the default data.
+pub async fn combine<'a>(
+    accounts: &dyn AccountRepository,
+    id: AccountId,
+    vanilla: Cow<'static, [u8]>,
+    mods: impl IntoIterator<Item = &'a str>,
+) -> Result<Cow<'static, [u8]>, sqlx::Error> {
+    let mut combined = vanilla;
+    for name in mods {
+        let pdiff = match definitions().get(name) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let data = match accounts.get_mod_data(id, name).await? {
+            Some(data) if pdiff.validate(&data).is_ok() => data,
+            _ => pdiff
+                .default_data()
+                .expect("Pdiff definitions are checked when loading"),
+        };
+        combined.to_mut().extend_from_slice(&data);
+    }
+    Ok(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions() -> HashMap<String, Pdiff> {
+        let mut definitions = HashMap::new();
+        definitions.insert(
+            "Scores".to_owned(),
+            Pdiff::parse("int score\nbool flags[2]").unwrap(),
+        );
+        definitions.insert(
+            "Ranks".to_owned(),
+            Pdiff::parse("$ENUM_START ranks\nbronze\nsilver\n$ENUM_END\nranks rank").unwrap(),
+        );
+        definitions
+    }
+
+    fn upload(mod_data: &[u8]) -> Vec<u8> {
+        let mut data = default_persistent_data().to_vec();
+        data.extend_from_slice(mod_data);
+        data
+    }
+
+    #[test]
+    fn split_in_load_order() {
+        let data = upload(&[1, 0, 0, 0, 1, 0, 1]);
+        let (vanilla, mods) = split(&definitions(), &data, ["Ranks", "Unknown", "Scores"]).unwrap();
+
+        assert_eq!(vanilla, default_persistent_data());
+        assert_eq!(mods.len(), 2);
+        assert_eq!((mods[0].name, mods[0].data), ("Ranks", &[1u8][..]));
+        assert_eq!(
+            (mods[1].name, mods[1].data),
+            ("Scores", &[0u8, 0, 0, 1, 0, 1][..])
+        );
+    }
+
+    #[test]
+    fn split_without_mods() {
+        let data = upload(&[]);
+        let (vanilla, mods) = split(&definitions(), &data, []).unwrap();
+        assert_eq!(vanilla, default_persistent_data());
+        assert!(mods.is_empty());
+    }
+
+    #[test]
+    fn split_rejects_short_mod_data() {
+        let data = upload(&[1, 0, 0]);
+        match split(&definitions(), &data, ["Scores"]) {
+            Err(SplitError::Mod {
+                name,
+                error: PdiffError::InvalidLength { expected, actual },
+            }) => assert_eq!((name.as_str(), expected, actual), ("Scores", 6, 3)),
+            _ => panic!("short mod data was accepted"),
+        }
+    }
+
+    #[test]
+    fn split_rejects_invalid_enum_values() {
+        let data = upload(&[2]);
+        assert!(matches!(
+            split(&definitions(), &data, ["Ranks"]),
+            Err(SplitError::Mod {
+                error: PdiffError::InvalidEnumValue { value: 2, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn split_rejects_trailing_bytes() {
+        let data = upload(&[1, 9, 9]);
+        assert!(matches!(
+            split(&definitions(), &data, ["Ranks"]),
+            Err(SplitError::TrailingBytes(2))
+        ));
+    }
+}
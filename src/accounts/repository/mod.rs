@@ -0,0 +1,418 @@
+use std::{borrow::Cow, net::IpAddr, sync::Arc};
+
+use hmac::{Hmac, Mac, NewMac};
+use once_cell::sync::OnceCell;
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::{id::UniqueId, Database};
+
+use super::{pdiff::ModData, AccountId};
+
+mod postgres;
+mod sqlite;
+
+#[derive(sqlx::FromRow)]
+pub struct PersistenceAuthData {
+    pub current_server: Option<UniqueId>,
+    pub last_auth_ip: IpAddr,
+}
+
+/// A name an account used, starting at `changed`.
+pub struct NameChange {
+    pub username: String,
+    pub changed: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct NameHistory {
+    pub username: Option<String>,
+    /// When the name was last confirmed by a lookup
+    pub updated: Option<chrono::DateTime<chrono::Utc>>,
+    /// Every name the account used, newest first
+    pub changes: Vec<NameChange>,
+}
+
+/// Storage of player accounts, implemented for every supported database.
+#[async_trait::async_trait]
+pub trait AccountRepository: Send + Sync {
+    async fn exists(&self, id: AccountId) -> Result<bool, sqlx::Error>;
+
+    async fn create(&self, id: AccountId) -> Result<(), sqlx::Error>;
+
+    /// Starts a new session for the account, expired sessions of the account are removed.
+    async fn create_token(&self, id: AccountId, ip: IpAddr) -> Result<UniqueId, sqlx::Error>;
+
+    async fn authenticate(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error>;
+
+    /// Replaces a valid session token with a new one, returning `None` if the token is not valid.
+    async fn refresh_token(
+        &self,
+        id: AccountId,
+        token: UniqueId,
+    ) -> Result<Option<UniqueId>, sqlx::Error>;
+
+    /// Ends a single session, returning whether it existed.
+    async fn revoke_token(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error>;
+
+    /// Ends every session of the account, returning how many were ended.
+    async fn revoke_tokens(&self, id: AccountId) -> Result<u64, sqlx::Error>;
+
+    /// Removes the expired sessions of every account, returning how many were removed.
+    async fn remove_expired_sessions(&self) -> Result<u64, sqlx::Error>;
+
+    async fn get_name(&self, id: AccountId) -> Result<Option<String>, sqlx::Error>;
+
+    /// Stores the name of an account, recording it in the name history if it changed.
+    async fn set_name(&self, id: AccountId, username: &str) -> Result<(), sqlx::Error>;
+
+    async fn get_name_history(&self, id: AccountId) -> Result<NameHistory, sqlx::Error>;
+
+    async fn get_data(&self, id: AccountId) -> Result<Cow<'static, [u8]>, sqlx::Error>;
+
+    /// Stores the vanilla player data together with the data of the mods and the ranked values
+    /// extracted from it, in a single transaction.
+    async fn set_data(
+        &self,
+        id: AccountId,
+        data: &[u8],
+        mod_data: &[ModData<'_>],
+        ranked_values: &[(&str, f64)],
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the ids of every account that has stored player data.
+    async fn ids_with_data(&self) -> Result<Vec<AccountId>, sqlx::Error>;
+
+    async fn get_mod_data(
+        &self,
+        id: AccountId,
+        mod_name: &str,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error>;
+
+    async fn get_auth(&self, id: AccountId) -> Result<PersistenceAuthData, sqlx::Error>;
+
+    async fn join_server(&self, id: AccountId, server_id: &UniqueId) -> Result<(), sqlx::Error>;
+}
+
+/// Creates the account repository for the configured database.
+pub fn account_repository(database: Database) -> Arc<dyn AccountRepository> {
+    match database {
+        Database::Sqlite(pool) => Arc::new(sqlite::SqliteAccountRepository::new(pool)),
+        Database::Postgres(pool) => Arc::new(postgres::PostgresAccountRepository::new(pool)),
+    }
+}
+
+/// How long a session token can be used, configured in hours by `TOKEN_LIFETIME_HOURS` (default: 24).
+pub fn token_lifetime() -> chrono::Duration {
+    static INSTANCE: OnceCell<chrono::Duration> = OnceCell::new();
+    *INSTANCE.get_or_init(|| {
+        let hours = std::env::var("TOKEN_LIFETIME_HOURS")
+            .map(|v| {
+                v.parse::<i64>()
+                    .expect("TOKEN_LIFETIME_HOURS must be an integer")
+            })
+            .unwrap_or(24);
+        chrono::Duration::hours(hours)
+    })
+}
+
+/// Sessions created before this time have expired.
+fn expired_before() -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() - token_lifetime()
+}
+
+/// Secret key of the token hashes, configured by `TOKEN_SECRET`.
+///
+/// Called when the master server starts, so a missing secret stops it instead of ending every session on the
+/// next restart. A random secret is only used if `TOKEN_SECRET_RANDOM` is `true`, for local development.
+#[cfg(not(test))]
+pub fn token_secret() -> &'static [u8] {
+    static INSTANCE: OnceCell<Vec<u8>> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        parse_token_secret(|name| std::env::var(name).ok()).unwrap_or_else(|err| panic!("{}", err))
+    })
+}
+
+/// Tests don't configure a secret, their tokens only have to match within a run.
+#[cfg(test)]
+pub fn token_secret() -> &'static [u8] {
+    b"test secret"
+}
+
+fn parse_token_secret(var: impl Fn(&str) -> Option<String>) -> Result<Vec<u8>, String> {
+    match var("TOKEN_SECRET") {
+        Some(secret) if !secret.is_empty() => Ok(secret.into_bytes()),
+        _ if var("TOKEN_SECRET_RANDOM").as_deref() == Some("true") => {
+            warn!("TOKEN_SECRET is not set, using a random secret: sessions will not survive a restart");
+            Ok(UniqueId::new(&mut rand::thread_rng()).bytes().to_vec())
+        }
+        _ => Err("TOKEN_SECRET must be set, or TOKEN_SECRET_RANDOM must be true to use a random secret for development".to_owned()),
+    }
+}
+
+fn token_mac(token: &UniqueId) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token_secret()).expect("HMAC accepts keys of any length");
+    mac.update(token.bytes());
+    mac
+}
+
+/// Keyed hash of a token, which is stored instead of the token itself.
+fn hash_token(token: &UniqueId) -> Vec<u8> {
+    token_mac(token).finalize().into_bytes().to_vec()
+}
+
+/// Finds the stored hash of a token, every hash is compared in constant time.
+fn find_token(token: &UniqueId, hashes: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+    hashes
+        .into_iter()
+        .find(|hash| token_mac(token).verify(hash).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_databases;
+
+    fn parse_secret(vars: &[(&str, &str)]) -> Result<Vec<u8>, String> {
+        let vars: std::collections::HashMap<&str, &str> = vars.iter().copied().collect();
+        parse_token_secret(|name| vars.get(name).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn parses_token_secret() {
+        assert_eq!(
+            parse_secret(&[("TOKEN_SECRET", "secret")]).unwrap(),
+            b"secret"
+        );
+        // An explicit secret wins over the development option
+        assert_eq!(
+            parse_secret(&[("TOKEN_SECRET", "secret"), ("TOKEN_SECRET_RANDOM", "true")]).unwrap(),
+            b"secret"
+        );
+        assert_eq!(
+            parse_secret(&[("TOKEN_SECRET_RANDOM", "true")])
+                .unwrap()
+                .len(),
+            16
+        );
+    }
+
+    #[test]
+    fn requires_token_secret() {
+        assert!(parse_secret(&[]).is_err());
+        assert!(parse_secret(&[("TOKEN_SECRET", "")]).is_err());
+        assert!(parse_secret(&[("TOKEN_SECRET_RANDOM", "false")]).is_err());
+    }
+
+    async fn repositories() -> Vec<Arc<dyn AccountRepository>> {
+        test_databases()
+            .await
+            .into_iter()
+            .map(account_repository)
+            .collect()
+    }
+
+    /// Creates an account with a random id, so tests don't conflict on a shared database.
+    async fn new_account(accounts: &dyn AccountRepository) -> AccountId {
+        let id = AccountId(rand::random::<u32>().into());
+        accounts.create(id).await.unwrap();
+        id
+    }
+
+    fn ip() -> IpAddr {
+        [127, 0, 0, 1].into()
+    }
+
+    #[tokio::test]
+    async fn exists() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            assert!(accounts.exists(id).await.unwrap());
+            assert!(!accounts.exists(AccountId(u64::MAX)).await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn create_token() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            let first = accounts.create_token(id, ip()).await.unwrap();
+            let second = accounts.create_token(id, ip()).await.unwrap();
+
+            assert!(accounts.authenticate(id, first).await.unwrap());
+            assert!(accounts.authenticate(id, second).await.unwrap());
+            assert!(!accounts
+                .authenticate(id, UniqueId::new(rand::thread_rng()))
+                .await
+                .unwrap());
+            assert_eq!(accounts.get_auth(id).await.unwrap().last_auth_ip, ip());
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_token() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            let token = accounts.create_token(id, ip()).await.unwrap();
+
+            let refreshed = accounts.refresh_token(id, token).await.unwrap().unwrap();
+            assert!(accounts.authenticate(id, refreshed).await.unwrap());
+            assert!(!accounts.authenticate(id, token).await.unwrap());
+            assert!(accounts.refresh_token(id, token).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn revoke_token() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            let revoked = accounts.create_token(id, ip()).await.unwrap();
+            let kept = accounts.create_token(id, ip()).await.unwrap();
+
+            assert!(accounts.revoke_token(id, revoked).await.unwrap());
+            assert!(!accounts.revoke_token(id, revoked).await.unwrap());
+            assert!(!accounts.authenticate(id, revoked).await.unwrap());
+            assert!(accounts.authenticate(id, kept).await.unwrap());
+
+            assert_eq!(accounts.revoke_tokens(id).await.unwrap(), 1);
+            assert!(!accounts.authenticate(id, kept).await.unwrap());
+        }
+    }
+
+    /// Stores a session that expired an hour ago.
+    async fn insert_expired_session(database: &Database, id: AccountId) -> UniqueId {
+        let token = UniqueId::new(rand::thread_rng());
+        let created = expired_before() - chrono::Duration::hours(1);
+        let query = r#"INSERT INTO sessions (token_hash, account_id, created) VALUES ($1, $2, $3)"#;
+        match database {
+            Database::Sqlite(pool) => {
+                sqlx::query(query)
+                    .bind(hash_token(&token))
+                    .bind(id)
+                    .bind(created)
+                    .execute(pool)
+                    .await
+                    .unwrap();
+            }
+            Database::Postgres(pool) => {
+                sqlx::query(query)
+                    .bind(hash_token(&token))
+                    .bind(id)
+                    .bind(created)
+                    .execute(pool)
+                    .await
+                    .unwrap();
+            }
+        }
+        token
+    }
+
+    #[tokio::test]
+    async fn remove_expired_sessions() {
+        for database in test_databases().await {
+            let accounts = account_repository(database.clone());
+            let id = new_account(accounts.as_ref()).await;
+            let kept = accounts.create_token(id, ip()).await.unwrap();
+            insert_expired_session(&database, id).await;
+
+            assert!(accounts.remove_expired_sessions().await.unwrap() >= 1);
+            assert_eq!(accounts.remove_expired_sessions().await.unwrap(), 0);
+            assert!(accounts.authenticate(id, kept).await.unwrap());
+            // The expired session was the only other one
+            assert_eq!(accounts.revoke_tokens(id).await.unwrap(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn name_history() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            assert_eq!(accounts.get_name(id).await.unwrap(), None);
+
+            accounts.set_name(id, "first").await.unwrap();
+            accounts.set_name(id, "second").await.unwrap();
+            // Confirming the current name doesn't add a change
+            accounts.set_name(id, "second").await.unwrap();
+
+            let history = accounts.get_name_history(id).await.unwrap();
+            assert_eq!(history.username.as_deref(), Some("second"));
+            assert!(history.updated.is_some());
+            let names: Vec<_> = history
+                .changes
+                .iter()
+                .map(|c| c.username.as_str())
+                .collect();
+            assert_eq!(names, ["second", "first"]);
+            assert_eq!(
+                accounts.get_name(id).await.unwrap().as_deref(),
+                Some("second")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn data() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            assert_eq!(
+                accounts.get_data(id).await.unwrap(),
+                crate::accounts::default_persistent_data()
+            );
+
+            let data = [1, 2, 3];
+            accounts.set_data(id, &data, &[], &[]).await.unwrap();
+            assert_eq!(accounts.get_data(id).await.unwrap(), &data[..]);
+            assert!(accounts.ids_with_data().await.unwrap().contains(&id));
+        }
+    }
+
+    #[tokio::test]
+    async fn data_ranked_values() {
+        for database in test_databases().await {
+            let accounts = account_repository(database.clone());
+            let leaderboard = crate::leaderboard::leaderboard_repository(database);
+            let id = new_account(accounts.as_ref()).await;
+            let metric = format!("test.{}", rand::random::<u32>());
+
+            accounts
+                .set_data(id, &[1], &[], &[(&metric, 5.0)])
+                .await
+                .unwrap();
+            let page = leaderboard.page(&metric, 0, 10).await.unwrap();
+            assert_eq!((page[0].account_id, page[0].value), (id, 5.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn mod_data_upsert() {
+        for accounts in repositories().await {
+            let id = new_account(accounts.as_ref()).await;
+            let mod_data = |data| {
+                [ModData {
+                    name: "Example.Mod",
+                    data,
+                }]
+            };
+            assert_eq!(
+                accounts.get_mod_data(id, "Example.Mod").await.unwrap(),
+                None
+            );
+
+            accounts
+                .set_data(id, &[1], &mod_data(&[1, 1]), &[])
+                .await
+                .unwrap();
+            accounts
+                .set_data(id, &[2], &mod_data(&[2, 2]), &[])
+                .await
+                .unwrap();
+
+            assert_eq!(accounts.get_data(id).await.unwrap(), &[2][..]);
+            assert_eq!(
+                accounts.get_mod_data(id, "Example.Mod").await.unwrap(),
+                Some(vec![2, 2])
+            );
+            assert_eq!(accounts.get_mod_data(id, "Other.Mod").await.unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use sqlx::PgPool;
+
+use crate::{
+    accounts::{pdiff::ModData, AccountId},
+    id::UniqueId,
+};
+
+use super::{
+    expired_before, find_token, hash_token, AccountRepository, NameChange, NameHistory,
+    PersistenceAuthData,
+};
+
+// Queries are checked at compile time against SQLite only, so they are built at runtime here
+pub struct PostgresAccountRepository {
+    database: PgPool,
+}
+
+impl PostgresAccountRepository {
+    pub fn new(database: PgPool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl AccountRepository for PostgresAccountRepository {
+    async fn exists(&self, id: AccountId) -> Result<bool, sqlx::Error> {
+        Ok(sqlx::query(r#"SELECT 1 FROM accounts WHERE id = $1"#)
+            .bind(id)
+            .fetch_optional(&self.database)
+            .await?
+            .is_some())
+    }
+
+    async fn create(&self, id: AccountId) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"INSERT INTO accounts (id) VALUES ($1)"#)
+            .bind(id)
+            .execute(&self.database)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_token(&self, id: AccountId, ip: IpAddr) -> Result<UniqueId, sqlx::Error> {
+        let token = UniqueId::new(&mut rand::thread_rng());
+        let now = chrono::Utc::now();
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query(r#"DELETE FROM sessions WHERE account_id = $1 AND created <= $2"#)
+            .bind(id)
+            .bind(expired_before())
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query(
+            r#"INSERT INTO sessions (token_hash, account_id, created) VALUES ($1, $2, $3)"#,
+        )
+        .bind(hash_token(&token))
+        .bind(id)
+        .bind(now)
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query(r#"UPDATE accounts SET last_auth_ip = $1 WHERE id = $2"#)
+            .bind(ip.to_string())
+            .bind(id)
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(token)
+    }
+
+    async fn authenticate(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error> {
+        let hashes: Vec<(Vec<u8>,)> = sqlx::query_as(
+            r#"SELECT token_hash FROM sessions WHERE account_id = $1 AND created > $2"#,
+        )
+        .bind(id)
+        .bind(expired_before())
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(find_token(&token, hashes.into_iter().map(|(hash,)| hash).collect()).is_some())
+    }
+
+    async fn refresh_token(
+        &self,
+        id: AccountId,
+        token: UniqueId,
+    ) -> Result<Option<UniqueId>, sqlx::Error> {
+        let new_token = UniqueId::new(&mut rand::thread_rng());
+        let now = chrono::Utc::now();
+        let mut transaction = self.database.begin().await?;
+
+        let hashes: Vec<(Vec<u8>,)> = sqlx::query_as(
+            r#"SELECT token_hash FROM sessions WHERE account_id = $1 AND created > $2 FOR UPDATE"#,
+        )
+        .bind(id)
+        .bind(expired_before())
+        .fetch_all(&mut transaction)
+        .await?;
+        let token_hash = match find_token(&token, hashes.into_iter().map(|(hash,)| hash).collect())
+        {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+
+        // A concurrent refresh or revocation may have ended the session since it was read
+        let deleted = sqlx::query(r#"DELETE FROM sessions WHERE token_hash = $1"#)
+            .bind(token_hash)
+            .execute(&mut transaction)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        sqlx::query(
+            r#"INSERT INTO sessions (token_hash, account_id, created) VALUES ($1, $2, $3)"#,
+        )
+        .bind(hash_token(&new_token))
+        .bind(id)
+        .bind(now)
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(Some(new_token))
+    }
+
+    async fn revoke_token(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error> {
+        let hashes: Vec<(Vec<u8>,)> =
+            sqlx::query_as(r#"SELECT token_hash FROM sessions WHERE account_id = $1"#)
+                .bind(id)
+                .fetch_all(&self.database)
+                .await?;
+        let token_hash = match find_token(&token, hashes.into_iter().map(|(hash,)| hash).collect())
+        {
+            Some(hash) => hash,
+            None => return Ok(false),
+        };
+
+        let deleted = sqlx::query(r#"DELETE FROM sessions WHERE token_hash = $1"#)
+            .bind(token_hash)
+            .execute(&self.database)
+            .await?;
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    async fn revoke_tokens(&self, id: AccountId) -> Result<u64, sqlx::Error> {
+        Ok(sqlx::query(r#"DELETE FROM sessions WHERE account_id = $1"#)
+            .bind(id)
+            .execute(&self.database)
+            .await?
+            .rows_affected())
+    }
+
+    async fn remove_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        Ok(sqlx::query(r#"DELETE FROM sessions WHERE created <= $1"#)
+            .bind(expired_before())
+            .execute(&self.database)
+            .await?
+            .rows_affected())
+    }
+
+    async fn get_name(&self, id: AccountId) -> Result<Option<String>, sqlx::Error> {
+        let (username,): (Option<String>,) =
+            sqlx::query_as(r#"SELECT username FROM accounts WHERE id = $1"#)
+                .bind(id)
+                .fetch_one(&self.database)
+                .await?;
+        Ok(username)
+    }
+
+    async fn set_name(&self, id: AccountId, username: &str) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let mut transaction = self.database.begin().await?;
+
+        let (current,): (Option<String>,) =
+            sqlx::query_as(r#"SELECT username FROM accounts WHERE id = $1 FOR UPDATE"#)
+                .bind(id)
+                .fetch_one(&mut transaction)
+                .await?;
+        if current.as_deref() != Some(username) {
+            sqlx::query(
+                r#"INSERT INTO username_history (account_id, username, changed) VALUES ($1, $2, $3)"#,
+            )
+            .bind(id)
+            .bind(username)
+            .bind(now)
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        sqlx::query(r#"UPDATE accounts SET username = $1, username_updated = $2 WHERE id = $3"#)
+            .bind(username)
+            .bind(now)
+            .bind(id)
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await
+    }
+
+    async fn get_name_history(&self, id: AccountId) -> Result<NameHistory, sqlx::Error> {
+        let (username, updated): (Option<String>, Option<chrono::DateTime<chrono::Utc>>) =
+            sqlx::query_as(r#"SELECT username, username_updated FROM accounts WHERE id = $1"#)
+                .bind(id)
+                .fetch_one(&self.database)
+                .await?;
+        let changes: Vec<(String, chrono::DateTime<chrono::Utc>)> = sqlx::query_as(
+            r#"SELECT username, changed FROM username_history
+            WHERE account_id = $1 ORDER BY changed DESC"#,
+        )
+        .bind(id)
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(NameHistory {
+            username,
+            updated,
+            changes: changes
+                .into_iter()
+                .map(|(username, changed)| NameChange { username, changed })
+                .collect(),
+        })
+    }
+
+    async fn get_data(&self, id: AccountId) -> Result<Cow<'static, [u8]>, sqlx::Error> {
+        let (data,): (Option<Vec<u8>>,) =
+            sqlx::query_as(r#"SELECT persistent_data FROM accounts WHERE id = $1"#)
+                .bind(id)
+                .fetch_one(&self.database)
+                .await?;
+        Ok(data.map_or_else(
+            || crate::accounts::default_persistent_data().into(),
+            |v| v.into(),
+        ))
+    }
+
+    async fn set_data(
+        &self,
+        id: AccountId,
+        data: &[u8],
+        mod_data: &[ModData<'_>],
+        ranked_values: &[(&str, f64)],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query(r#"UPDATE accounts SET persistent_data = $1 WHERE id = $2"#)
+            .bind(data)
+            .bind(id)
+            .execute(&mut transaction)
+            .await?;
+        for ModData { name, data } in mod_data {
+            sqlx::query(
+                r#"INSERT INTO mod_persistent_data (account_id, mod_name, data) VALUES ($1, $2, $3)
+                ON CONFLICT (account_id, mod_name) DO UPDATE SET data = excluded.data"#,
+            )
+            .bind(id)
+            .bind(*name)
+            .bind(*data)
+            .execute(&mut transaction)
+            .await?;
+        }
+        for (metric, value) in ranked_values {
+            sqlx::query(
+                r#"INSERT INTO leaderboard (account_id, metric, value) VALUES ($1, $2, $3)
+                ON CONFLICT (account_id, metric) DO UPDATE SET value = excluded.value"#,
+            )
+            .bind(id)
+            .bind(metric)
+            .bind(value)
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        transaction.commit().await
+    }
+
+    async fn ids_with_data(&self) -> Result<Vec<AccountId>, sqlx::Error> {
+        let rows: Vec<(AccountId,)> = sqlx::query_as(
+            r#"SELECT id FROM accounts WHERE persistent_data IS NOT NULL ORDER BY id"#,
+        )
+        .fetch_all(&self.database)
+        .await?;
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    async fn get_mod_data(
+        &self,
+        id: AccountId,
+        mod_name: &str,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        let row: Option<(Vec<u8>,)> = sqlx::query_as(
+            r#"SELECT data FROM mod_persistent_data WHERE account_id = $1 AND mod_name = $2"#,
+        )
+        .bind(id)
+        .bind(mod_name)
+        .fetch_optional(&self.database)
+        .await?;
+        Ok(row.map(|(data,)| data))
+    }
+
+    async fn get_auth(&self, id: AccountId) -> Result<PersistenceAuthData, sqlx::Error> {
+        let (current_server, last_auth_ip): (Option<Vec<u8>>, String) = sqlx::query_as(
+            r#"SELECT current_server, last_auth_ip FROM accounts
+            WHERE id = $1 AND last_auth_ip IS NOT NULL"#,
+        )
+        .bind(id)
+        .fetch_one(&self.database)
+        .await?;
+        Ok(PersistenceAuthData {
+            current_server: current_server.map(|d| UniqueId::existing(d.try_into().unwrap())),
+            last_auth_ip: last_auth_ip.parse().unwrap(),
+        })
+    }
+
+    async fn join_server(&self, id: AccountId, server_id: &UniqueId) -> Result<(), sqlx::Error> {
+        let raw_server_id = &server_id.bytes()[..];
+        sqlx::query(r#"UPDATE accounts SET current_server = $1 WHERE id = $2"#)
+            .bind(raw_server_id)
+            .bind(id)
+            .execute(&self.database)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use sqlx::SqlitePool;
+
+use crate::{
+    accounts::{pdiff::ModData, AccountId},
+    id::UniqueId,
+};
+
+use super::{
+    expired_before, find_token, hash_token, AccountRepository, NameChange, NameHistory,
+    PersistenceAuthData,
+};
+
+pub struct SqliteAccountRepository {
+    database: SqlitePool,
+}
+
+impl SqliteAccountRepository {
+    pub fn new(database: SqlitePool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl AccountRepository for SqliteAccountRepository {
+    async fn exists(&self, id: AccountId) -> Result<bool, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"SELECT 1 as none FROM accounts WHERE id = ?"#, id)
+                .fetch_optional(&self.database)
+                .await?
+                .is_some(),
+        )
+    }
+
+    async fn create(&self, id: AccountId) -> Result<(), sqlx::Error> {
+        sqlx::query!(r#"INSERT INTO accounts (id) VALUES (?)"#, id)
+            .execute(&self.database)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_token(&self, id: AccountId, ip: IpAddr) -> Result<UniqueId, sqlx::Error> {
+        let token = UniqueId::new(&mut rand::thread_rng());
+        let token_hash = hash_token(&token);
+        let ip = ip.to_string();
+        let now = chrono::Utc::now();
+        let expired = expired_before();
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE account_id = ? AND created <= ?"#,
+            id,
+            expired
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            r#"INSERT INTO sessions (token_hash, account_id, created) VALUES (?, ?, ?)"#,
+            token_hash,
+            id,
+            now
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            r#"UPDATE accounts SET last_auth_ip = ? WHERE id = ?"#,
+            ip,
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(token)
+    }
+
+    async fn authenticate(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error> {
+        let expired = expired_before();
+        let hashes = sqlx::query!(
+            r#"SELECT token_hash FROM sessions WHERE account_id = ? AND created > ?"#,
+            id,
+            expired
+        )
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(find_token(
+            &token,
+            hashes.into_iter().map(|row| row.token_hash).collect(),
+        )
+        .is_some())
+    }
+
+    async fn refresh_token(
+        &self,
+        id: AccountId,
+        token: UniqueId,
+    ) -> Result<Option<UniqueId>, sqlx::Error> {
+        let expired = expired_before();
+        let new_token = UniqueId::new(&mut rand::thread_rng());
+        let new_token_hash = hash_token(&new_token);
+        let now = chrono::Utc::now();
+        let mut transaction = self.database.begin().await?;
+
+        let hashes = sqlx::query!(
+            r#"SELECT token_hash FROM sessions WHERE account_id = ? AND created > ?"#,
+            id,
+            expired
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+        let token_hash = match find_token(
+            &token,
+            hashes.into_iter().map(|row| row.token_hash).collect(),
+        ) {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+
+        // A concurrent refresh or revocation may have ended the session since it was read
+        let deleted = sqlx::query!(r#"DELETE FROM sessions WHERE token_hash = ?"#, token_hash)
+            .execute(&mut transaction)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Ok(None);
+        }
+        sqlx::query!(
+            r#"INSERT INTO sessions (token_hash, account_id, created) VALUES (?, ?, ?)"#,
+            new_token_hash,
+            id,
+            now
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(Some(new_token))
+    }
+
+    async fn revoke_token(&self, id: AccountId, token: UniqueId) -> Result<bool, sqlx::Error> {
+        let hashes = sqlx::query!(
+            r#"SELECT token_hash FROM sessions WHERE account_id = ?"#,
+            id
+        )
+        .fetch_all(&self.database)
+        .await?;
+        let token_hash = match find_token(
+            &token,
+            hashes.into_iter().map(|row| row.token_hash).collect(),
+        ) {
+            Some(hash) => hash,
+            None => return Ok(false),
+        };
+
+        let deleted = sqlx::query!(r#"DELETE FROM sessions WHERE token_hash = ?"#, token_hash)
+            .execute(&self.database)
+            .await?;
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    async fn revoke_tokens(&self, id: AccountId) -> Result<u64, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"DELETE FROM sessions WHERE account_id = ?"#, id)
+                .execute(&self.database)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    async fn remove_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        let expired = expired_before();
+        Ok(
+            sqlx::query!(r#"DELETE FROM sessions WHERE created <= ?"#, expired)
+                .execute(&self.database)
+                .await?
+                .rows_affected(),
+        )
+    }
+
+    async fn get_name(&self, id: AccountId) -> Result<Option<String>, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"SELECT username FROM accounts WHERE id = ?"#, id)
+                .fetch_one(&self.database)
+                .await?
+                .username,
+        )
+    }
+
+    async fn set_name(&self, id: AccountId, username: &str) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let mut transaction = self.database.begin().await?;
+
+        let current = sqlx::query!(r#"SELECT username FROM accounts WHERE id = ?"#, id)
+            .fetch_one(&mut transaction)
+            .await?
+            .username;
+        if current.as_deref() != Some(username) {
+            sqlx::query!(
+                r#"INSERT INTO username_history (account_id, username, changed) VALUES (?, ?, ?)"#,
+                id,
+                username,
+                now
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"UPDATE accounts SET username = ?, username_updated = ? WHERE id = ?"#,
+            username,
+            now,
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await
+    }
+
+    async fn get_name_history(&self, id: AccountId) -> Result<NameHistory, sqlx::Error> {
+        let account = sqlx::query!(
+            r#"SELECT username, username_updated as "username_updated: chrono::DateTime<chrono::Utc>"
+            FROM accounts WHERE id = ?"#,
+            id
+        )
+        .fetch_one(&self.database)
+        .await?;
+        let changes = sqlx::query_as!(
+            NameChange,
+            r#"SELECT username, changed as "changed: chrono::DateTime<chrono::Utc>"
+            FROM username_history WHERE account_id = ? ORDER BY changed DESC"#,
+            id
+        )
+        .fetch_all(&self.database)
+        .await?;
+
+        Ok(NameHistory {
+            username: account.username,
+            updated: account.username_updated,
+            changes,
+        })
+    }
+
+    async fn get_data(&self, id: AccountId) -> Result<Cow<'static, [u8]>, sqlx::Error> {
+        Ok(
+            sqlx::query!(r#"SELECT persistent_data FROM accounts WHERE id = ?"#, id)
+                .fetch_one(&self.database)
+                .await?
+                .persistent_data
+                .map_or_else(
+                    || crate::accounts::default_persistent_data().into(),
+                    |v| v.into(),
+                ),
+        )
+    }
+
+    async fn set_data(
+        &self,
+        id: AccountId,
+        data: &[u8],
+        mod_data: &[ModData<'_>],
+        ranked_values: &[(&str, f64)],
+    ) -> Result<(), sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+
+        sqlx::query!(
+            r#"UPDATE accounts SET persistent_data = ? WHERE id = ?"#,
+            data,
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
+        for ModData { name, data } in mod_data {
+            sqlx::query!(
+                r#"INSERT INTO mod_persistent_data (account_id, mod_name, data) VALUES (?, ?, ?)
+                ON CONFLICT (account_id, mod_name) DO UPDATE SET data = excluded.data"#,
+                id,
+                name,
+                data
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        for (metric, value) in ranked_values {
+            sqlx::query!(
+                r#"INSERT INTO leaderboard (account_id, metric, value) VALUES (?, ?, ?)
+                ON CONFLICT (account_id, metric) DO UPDATE SET value = excluded.value"#,
+                id,
+                metric,
+                value
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+
+        transaction.commit().await
+    }
+
+    async fn ids_with_data(&self) -> Result<Vec<AccountId>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT id as "id: AccountId" FROM accounts WHERE persistent_data IS NOT NULL ORDER BY id"#
+        )
+        .fetch_all(&self.database)
+        .await?
+        .into_iter()
+        .map(|row| row.id)
+        .collect())
+    }
+
+    async fn get_mod_data(
+        &self,
+        id: AccountId,
+        mod_name: &str,
+    ) -> Result<Option<Vec<u8>>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT data FROM mod_persistent_data WHERE account_id = ? AND mod_name = ?"#,
+            id,
+            mod_name
+        )
+        .fetch_optional(&self.database)
+        .await?
+        .map(|row| row.data))
+    }
+
+    async fn get_auth(&self, id: AccountId) -> Result<PersistenceAuthData, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"SELECT current_server, last_auth_ip as "last_auth_ip!" FROM accounts
+            WHERE id = ? AND last_auth_ip IS NOT NULL"#,
+            id
+        )
+        .fetch_one(&self.database)
+        .await?;
+        Ok(PersistenceAuthData {
+            current_server: row
+                .current_server
+                .map(|d| UniqueId::existing(d.try_into().unwrap())),
+            last_auth_ip: row.last_auth_ip.parse().unwrap(),
+        })
+    }
+
+    async fn join_server(&self, id: AccountId, server_id: &UniqueId) -> Result<(), sqlx::Error> {
+        let raw_server_id = &server_id.bytes()[..];
+        sqlx::query!(
+            r#"UPDATE accounts SET current_server = ? WHERE id = ?"#,
+            raw_server_id,
+            id
+        )
+        .execute(&self.database)
+        .await?;
+        Ok(())
+    }
+}
@@ -1,8 +1,18 @@
+use std::sync::Arc;
+
 use warp::Filter;
 
-use crate::{api::api_response, game_servers::with_servers, Database, SharedServerList};
+use crate::{
+    api::{api_response, bearer_token, json_body_with_limit},
+    game_servers::with_servers,
+    proxy::client_ip,
+    Database, SharedServerList,
+};
+
+use super::{account_repository, AccountRepository};
 
-use super::AccountRepository;
+/// Maximum size of a versioned `write_persistence` body, the data is sent as an array of numbers.
+const MAX_PERSISTENCE_BODY: u64 = 1024 * 1024;
 
 pub fn routes(
     database: Database,
@@ -12,6 +22,15 @@ pub fn routes(
     base.and(write_persistence(database, servers))
 }
 
+/// Routes of the versioned api, game servers send JSON bodies and their auth token as bearer token.
+pub fn v2_routes(
+    database: Database,
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let base = warp::path("accounts");
+    base.and(write_persistence_v2(database, servers))
+}
+
 pub(super) fn write_persistence(
     database: Database,
     servers: SharedServerList,
@@ -20,15 +39,32 @@ pub(super) fn write_persistence(
         .and(warp::post())
         .and(warp::query::<super::handlers::WritePersistenceParam>())
         .and(warp::multipart::form())
-        .and(warp::addr::remote())
+        .and(client_ip())
         .and(with_accounts(database))
         .and(with_servers(servers))
         .then(super::handlers::write_persistence)
         .map(api_response)
 }
 
+pub(super) fn write_persistence_v2(
+    database: Database,
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("write_persistence")
+        .and(warp::post())
+        .and(json_body_with_limit::<super::handlers::WritePersistenceBody>(MAX_PERSISTENCE_BODY))
+        .and(bearer_token())
+        .and(client_ip())
+        .and(with_accounts(database))
+        .and(with_servers(servers))
+        .then(super::handlers::write_persistence_v2)
+        .map(api_response)
+}
+
 pub fn with_accounts(
     database: Database,
-) -> impl Filter<Extract = (AccountRepository,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || AccountRepository::new(database.clone()))
+) -> impl Filter<Extract = (Arc<dyn AccountRepository>,), Error = std::convert::Infallible> + Clone
+{
+    let accounts = account_repository(database);
+    warp::any().map(move || accounts.clone())
 }
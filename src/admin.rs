@@ -0,0 +1,52 @@
+use crate::{
+    accounts::{account_repository, AccountId},
+    leaderboard::{self, leaderboard_repository},
+    Database,
+};
+
+const USAGE: &str = "usage: northstar_master_server [revoke-sessions <account id> | prune-sessions | rebuild-leaderboards]";
+
+/// Runs the administrative command given on the command line, if any.
+///
+/// Returns `false` when no command was given and the server should start.
+pub async fn run(args: &[String], database: &Database) -> bool {
+    match args {
+        [] => false,
+        [command, id] if command == "revoke-sessions" => {
+            let id = match id.parse() {
+                Ok(id) => AccountId(id),
+                Err(_) => exit_with_usage(),
+            };
+            let revoked = account_repository(database.clone())
+                .revoke_tokens(id)
+                .await
+                .expect("Unable to revoke sessions");
+            println!("Revoked {} session(s) of account {}", revoked, id);
+            true
+        }
+        [command] if command == "prune-sessions" => {
+            let removed = account_repository(database.clone())
+                .remove_expired_sessions()
+                .await
+                .expect("Unable to remove expired sessions");
+            println!("Removed {} expired session(s)", removed);
+            true
+        }
+        [command] if command == "rebuild-leaderboards" => {
+            let indexed = leaderboard::rebuild(
+                account_repository(database.clone()).as_ref(),
+                leaderboard_repository(database.clone()).as_ref(),
+            )
+            .await
+            .expect("Unable to rebuild leaderboards");
+            println!("Indexed the player data of {} account(s)", indexed);
+            true
+        }
+        _ => exit_with_usage(),
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
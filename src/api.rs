@@ -1,20 +1,45 @@
-use std::convert::Infallible;
+use std::{convert::Infallible, error::Error as _};
 
 use once_cell::sync::OnceCell;
 use semver::{Version, VersionReq};
 use serde_derive::Serialize;
 use thiserror::Error;
 use tracing::warn;
-use warp::Filter;
+use warp::{
+    filters::body::BodyDeserializeError,
+    http::StatusCode,
+    reject::{LengthRequired, PayloadTooLarge, UnsupportedMediaType},
+    Filter, Reply,
+};
+
+use crate::{id::UniqueId, proxy::ClientAddressError, rate_limit::RateLimitError};
 
 pub trait ApiErrorKind {
     fn kind(&self) -> &'static str;
+
+    /// The HTTP status of the error response on versioned routes.
+    ///
+    /// Legacy routes always respond with 200, as older clients expect.
+    fn status(&self) -> StatusCode;
+
+    /// A more specific reason code, for errors that have several causes clients may handle differently.
+    fn reason(&self) -> Option<&'static str> {
+        None
+    }
 }
 
 impl<T: ApiErrorKind> ApiErrorKind for &T {
     fn kind(&self) -> &'static str {
         (*self).kind()
     }
+
+    fn status(&self) -> StatusCode {
+        (*self).status()
+    }
+
+    fn reason(&self) -> Option<&'static str> {
+        (*self).reason()
+    }
 }
 
 #[derive(Serialize)]
@@ -22,6 +47,10 @@ pub struct ApiError {
     #[serde(rename = "enum")]
     kind: &'static str,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    #[serde(skip)]
+    status: StatusCode,
 }
 
 impl<T> From<T> for ApiError
@@ -32,6 +61,8 @@ where
         Self {
             kind: error.kind(),
             message: error.to_string(),
+            reason: error.reason(),
+            status: error.status(),
         }
     }
 }
@@ -72,25 +103,62 @@ struct ErrorWrapper {
     error: ApiError,
 }
 
-pub fn api_response<T, E>(result: Result<T, E>) -> impl warp::Reply
+/// Marks responses with a status chosen by [`api_response`], so legacy routes can reset it.
+#[derive(Clone, Copy)]
+struct ApiStatus;
+
+pub struct ApiReply {
+    json: warp::reply::Json,
+    status: StatusCode,
+}
+
+impl Reply for ApiReply {
+    fn into_response(self) -> warp::reply::Response {
+        let mut response = self.json.into_response();
+        *response.status_mut() = self.status;
+        response.extensions_mut().insert(ApiStatus);
+        response
+    }
+}
+
+pub fn api_response<T, E>(result: Result<T, E>) -> ApiReply
 where
     T: serde::Serialize + 'static,
     E: std::error::Error + 'static,
     for<'a> &'a E: Into<ApiError>,
 {
     match result {
-        Ok(ok) => warp::reply::json(&Response::from(ok)),
+        Ok(ok) => ApiReply {
+            json: warp::reply::json(&Response::from(ok)),
+            status: StatusCode::OK,
+        },
         Err(err) => {
-            let response = Response::<ErrorWrapper>::from(Into::<ApiError>::into(&err));
+            let error = Into::<ApiError>::into(&err);
+            let status = error.status;
+            let response = Response::<ErrorWrapper>::from(error);
 
             let boxed_error: Box<dyn std::error::Error> = Box::new(err);
             warn!(error = boxed_error.as_ref(), "responding with api error");
 
-            warp::reply::json(&response)
+            ApiReply {
+                json: warp::reply::json(&response),
+                status,
+            }
         }
     }
 }
 
+/// Responds to api errors with 200, like the original master server.
+///
+/// Applied to the legacy routes, as the game only reads the error from the body.
+pub fn legacy_status(reply: impl Reply) -> warp::reply::Response {
+    let mut response = reply.into_response();
+    if response.extensions().get::<ApiStatus>().is_some() {
+        *response.status_mut() = StatusCode::OK;
+    }
+    response
+}
+
 #[derive(Error, Debug, Clone, Copy)]
 enum VersionError {
     #[error("user agent is not Northstar")]
@@ -103,6 +171,13 @@ impl ApiErrorKind for VersionError {
     fn kind(&self) -> &'static str {
         "UNSUPPORTED_VERSION"
     }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            VersionError::UserAgent => StatusCode::BAD_REQUEST,
+            VersionError::Version => StatusCode::UPGRADE_REQUIRED,
+        }
+    }
 }
 
 impl warp::reject::Reject for VersionError {}
@@ -148,11 +223,97 @@ async fn check_version(header: String) -> Result<(), warp::Rejection> {
     }
 }
 
-pub async fn version_error_handler(
-    err: warp::Rejection,
-) -> Result<impl warp::Reply, warp::Rejection> {
+/// Parses a JSON request body of at most 64 KiB, used by the versioned routes.
+pub fn json_body<T>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+where
+    T: serde::de::DeserializeOwned + Send,
+{
+    json_body_with_limit(64 * 1024)
+}
+
+/// Parses a JSON request body of at most `limit` bytes.
+///
+/// [`rejection_handler`] answers invalid bodies with an `INVALID_BODY` or `BODY_TOO_LARGE` api error.
+pub fn json_body_with_limit<T>(
+    limit: u64,
+) -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+where
+    T: serde::de::DeserializeOwned + Send,
+{
+    warp::body::content_length_limit(limit).and(warp::body::json())
+}
+
+#[derive(Error, Debug)]
+enum BodyError {
+    #[error("request body is invalid: {0}")]
+    Invalid(String),
+    #[error("request body is too large")]
+    TooLarge,
+}
+
+impl ApiErrorKind for BodyError {
+    fn kind(&self) -> &'static str {
+        match self {
+            BodyError::Invalid(_) => "INVALID_BODY",
+            BodyError::TooLarge => "BODY_TOO_LARGE",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            BodyError::Invalid(_) => StatusCode::BAD_REQUEST,
+            BodyError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+impl BodyError {
+    fn from_rejection(err: &warp::Rejection) -> Option<Self> {
+        if let Some(err) = err.find::<BodyDeserializeError>() {
+            // Name the field or position that couldn't be parsed
+            let cause = err
+                .source()
+                .map_or_else(|| err.to_string(), |c| c.to_string());
+            Some(BodyError::Invalid(cause))
+        } else if err.find::<UnsupportedMediaType>().is_some() {
+            Some(BodyError::Invalid(
+                "content type must be application/json".to_owned(),
+            ))
+        } else if err.find::<LengthRequired>().is_some() {
+            Some(BodyError::Invalid("content length is missing".to_owned()))
+        } else if err.find::<PayloadTooLarge>().is_some() {
+            Some(BodyError::TooLarge)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extracts the token of an `Authorization: Bearer <token>` header.
+///
+/// Missing or malformed tokens are passed on as `None`, so handlers can respond with their own error.
+pub fn bearer_token() -> impl Filter<Extract = (Option<UniqueId>,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("authorization")
+        .or(warp::any().map(|| None))
+        .unify()
+        .map(|header: Option<String>| {
+            header
+                .as_deref()
+                .and_then(|h| h.strip_prefix("Bearer "))
+                .and_then(|token| token.trim().parse().ok())
+        })
+}
+
+/// Responds to rejections of the api filters with an api error.
+pub async fn rejection_handler(err: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
     if let Some(version_error) = err.find::<VersionError>() {
         Ok(api_response::<(), VersionError>(Err(*version_error)))
+    } else if err.find::<ClientAddressError>().is_some() {
+        Ok(api_response::<(), _>(Err(ClientAddressError::Missing)))
+    } else if err.find::<RateLimitError>().is_some() {
+        Ok(api_response::<(), _>(Err(RateLimitError::Limited)))
+    } else if let Some(body_error) = BodyError::from_rejection(&err) {
+        Ok(api_response::<(), _>(Err(body_error)))
     } else {
         Err(err)
     }
@@ -1,71 +1,83 @@
-use std::net::{IpAddr, SocketAddr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
 use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::debug;
+use tracing::warn;
+use warp::http::StatusCode;
 
 use crate::{
-    accounts::{AccountId, AccountRepository},
+    accounts::{pdiff, token_lifetime, AccountId, AccountRepository},
     api::ApiErrorKind,
+    game_servers,
     id::UniqueId,
+    rate_limit::{self, RateLimitError, RouteGroup},
     SharedServerList,
 };
 
+use super::{
+    provider::{AuthProvider, ProviderError},
+    username::UsernameLookup,
+};
+
 #[derive(Deserialize)]
 pub(super) struct OriginAuthenticationParam {
     id: AccountId,
     token: String,
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct StryderParam {
-    qt: &'static str,
-    #[serde(rename = "type")]
-    kind: &'static str,
-    code: String,
-    force_trial: u32,
-    proto: u32,
-    json: u32,
-    env: &'static str,
-    user_id: String,
-}
-
-impl StryderParam {
-    fn new(user_id: AccountId, code: String) -> Self {
-        Self {
-            qt: "origin-requesttoken",
-            kind: "server_token",
-            code,
-            force_trial: 0,
-            proto: 0,
-            json: 1,
-            env: "production",
-            user_id: hex::encode_upper(user_id.0.to_be_bytes()),
-        }
-    }
-}
-
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
-struct StryderResponse {
-    has_online_access: char,
-    store_uri: String,
-}
-
 #[derive(Error, Debug)]
 pub(super) enum OriginAuthenticationError {
-    #[error("error while communicating with stryder api")]
-    StryderError(#[from] Option<reqwest::Error>),
+    #[error("origin token is invalid")]
+    InvalidToken,
+    #[error("origin token has expired, please restart the game")]
+    ExpiredToken,
+    #[error("your account does not have online access")]
+    NoOnlineAccess,
     #[error("you do not appear to own Titanfall 2")]
     NoGame,
+    #[error("error while communicating with stryder api")]
+    StryderError(#[source] ProviderError),
+    #[error(transparent)]
+    RateLimited(#[from] RateLimitError),
+}
+
+impl From<ProviderError> for OriginAuthenticationError {
+    fn from(err: ProviderError) -> Self {
+        match err {
+            ProviderError::InvalidToken => OriginAuthenticationError::InvalidToken,
+            ProviderError::ExpiredToken => OriginAuthenticationError::ExpiredToken,
+            ProviderError::Request(_) | ProviderError::Unavailable(_) => {
+                OriginAuthenticationError::StryderError(err)
+            }
+        }
+    }
 }
 
 impl ApiErrorKind for OriginAuthenticationError {
     fn kind(&self) -> &'static str {
         match self {
-            OriginAuthenticationError::StryderError(_) => "STRYDER_RESPONSE",
+            OriginAuthenticationError::InvalidToken => "INVALID_ORIGIN_TOKEN",
+            OriginAuthenticationError::ExpiredToken => "EXPIRED_ORIGIN_TOKEN",
+            OriginAuthenticationError::NoOnlineAccess => "NO_ONLINE_ACCESS",
             OriginAuthenticationError::NoGame => "UNAUTHORIZED_GAME",
+            OriginAuthenticationError::StryderError(_) => "STRYDER_RESPONSE",
+            OriginAuthenticationError::RateLimited(e) => e.kind(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            OriginAuthenticationError::InvalidToken | OriginAuthenticationError::ExpiredToken => {
+                StatusCode::UNAUTHORIZED
+            }
+            OriginAuthenticationError::NoOnlineAccess | OriginAuthenticationError::NoGame => {
+                StatusCode::FORBIDDEN
+            }
+            OriginAuthenticationError::StryderError(_) => StatusCode::BAD_GATEWAY,
+            OriginAuthenticationError::RateLimited(e) => e.status(),
         }
     }
 }
@@ -77,35 +89,27 @@ pub(super) struct OriginAuthenticationResponse {
 
 pub(super) async fn origin_authentication(
     param: OriginAuthenticationParam,
-    remote: Option<SocketAddr>,
-    accounts: crate::accounts::AccountRepository,
+    ip: IpAddr,
+    accounts: Arc<dyn AccountRepository>,
+    provider: Arc<dyn AuthProvider>,
+    names: Option<Arc<dyn UsernameLookup>>,
 ) -> Result<OriginAuthenticationResponse, OriginAuthenticationError> {
-    let ip = remote.unwrap().ip();
+    // Accounts are limited by their failed logins, so others can't lock a player out with valid requests
+    rate_limit::check_account(RouteGroup::ClientAuth, param.id)?;
 
     // Check if token is valid and user owns titanfall
-    let stryder_param = StryderParam::new(param.id, param.token);
-    debug!(
-        user_id = stryder_param.user_id.as_str(),
-        "requesting stryder auth"
-    );
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://r2-pc.stryder.respawn.com/nucleus-oauth.php")
-        .query(&stryder_param)
-        .send()
-        .await?;
-
-    // The returned JSON is not spec compliant
-    // It contains literal newlines in strings
-    // PHP moment
-    // let text = response.text().await?;
-    // if text.contains("\"success\": false") {
-    //     return Err(OriginAuthenticationError::StryderError(None));
-    // }
-
-    let response: StryderResponse = response.json().await?;
-    debug!(?response, account_id = param.id.0, "stryder response");
-    if response.has_online_access == '1' && response.store_uri.contains("titanfall-2") {
+    let account = match provider.verify(param.id, &param.token).await {
+        Ok(account) => account,
+        Err(err @ (ProviderError::InvalidToken | ProviderError::ExpiredToken)) => {
+            rate_limit::failed_account_attempt(RouteGroup::ClientAuth, param.id);
+            return Err(err.into());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if !account.has_online_access {
+        return Err(OriginAuthenticationError::NoOnlineAccess);
+    }
+    if account.owns_game() {
         if !accounts.exists(param.id).await.unwrap() {
             accounts
                 .create(param.id)
@@ -113,6 +117,16 @@ pub(super) async fn origin_authentication(
                 .expect("Unable to create account");
         }
 
+        // Players without a name would join servers nameless, so their lookup is awaited (bounded by its timeout).
+        // Known names are refreshed in the background, so a slow lookup doesn't delay the login
+        if let Some(names) = names {
+            if matches!(accounts.get_name(param.id).await, Ok(Some(_))) {
+                tokio::spawn(update_name(param.id, names, accounts.clone()));
+            } else {
+                update_name(param.id, names, accounts.clone()).await;
+            }
+        }
+
         let token = accounts
             .create_token(param.id, ip)
             .await
@@ -124,6 +138,25 @@ pub(super) async fn origin_authentication(
     }
 }
 
+/// Stores the current name of an account.
+///
+/// A failed lookup keeps the stored name, it is updated again on the next login.
+async fn update_name(
+    id: AccountId,
+    names: Arc<dyn UsernameLookup>,
+    accounts: Arc<dyn AccountRepository>,
+) {
+    match names.lookup(id).await {
+        Ok(Some(name)) => {
+            if let Err(err) = accounts.set_name(id, &name).await {
+                warn!(%err, account_id = id.0, "unable to store username");
+            }
+        }
+        Ok(None) => {}
+        Err(err) => warn!(%err, account_id = id.0, "unable to look up username"),
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct AuthenticateSelfParam {
@@ -151,11 +184,17 @@ impl ApiErrorKind for AuthenticateSelfError {
             AuthenticateSelfError::InvalidToken => "INVALID_MASTERSERVER_TOKEN",
         }
     }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            AuthenticateSelfError::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
 }
 
 pub(super) async fn authenticate_self(
     param: AuthenticateSelfParam,
-    accounts: AccountRepository,
+    accounts: Arc<dyn AccountRepository>,
 ) -> Result<AuthenticateSelfResponse, AuthenticateSelfError> {
     let authenticated = accounts
         .authenticate(param.id, param.player_token)
@@ -180,6 +219,171 @@ pub(super) async fn authenticate_self(
     })
 }
 
+/// Body of the versioned `auth_with_self` route, the token is sent in the `Authorization` header.
+#[derive(Deserialize)]
+pub(super) struct AuthenticateSelfBody {
+    id: AccountId,
+}
+
+pub(super) async fn authenticate_self_v2(
+    body: AuthenticateSelfBody,
+    player_token: Option<UniqueId>,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<AuthenticateSelfResponse, AuthenticateSelfError> {
+    let player_token = player_token.ok_or(AuthenticateSelfError::InvalidToken)?;
+    authenticate_self(
+        AuthenticateSelfParam {
+            id: body.id,
+            player_token,
+        },
+        accounts,
+    )
+    .await
+}
+
+#[derive(Error, Debug)]
+pub(super) enum SessionError {
+    #[error("token is not valid")]
+    InvalidToken,
+}
+
+impl ApiErrorKind for SessionError {
+    fn kind(&self) -> &'static str {
+        match self {
+            SessionError::InvalidToken => "INVALID_MASTERSERVER_TOKEN",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            SessionError::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RefreshTokenParam {
+    id: AccountId,
+    player_token: UniqueId,
+}
+
+#[derive(Serialize)]
+pub(super) struct RefreshTokenResponse {
+    token: UniqueId,
+    /// RFC 3339 timestamp of when the new token expires
+    expires: String,
+}
+
+/// Replaces a player token with a new one, the old token can no longer be used.
+pub(super) async fn refresh_token(
+    param: RefreshTokenParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<RefreshTokenResponse, SessionError> {
+    let token = accounts
+        .refresh_token(param.id, param.player_token)
+        .await
+        .expect("Unable to refresh token")
+        .ok_or(SessionError::InvalidToken)?;
+
+    Ok(RefreshTokenResponse {
+        token,
+        expires: (chrono::Utc::now() + token_lifetime()).to_rfc3339(),
+    })
+}
+
+/// Body of the versioned `refresh_token` route, the token is sent in the `Authorization` header.
+#[derive(Deserialize)]
+pub(super) struct RefreshTokenBody {
+    id: AccountId,
+}
+
+pub(super) async fn refresh_token_v2(
+    body: RefreshTokenBody,
+    player_token: Option<UniqueId>,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<RefreshTokenResponse, SessionError> {
+    let player_token = player_token.ok_or(SessionError::InvalidToken)?;
+    refresh_token(
+        RefreshTokenParam {
+            id: body.id,
+            player_token,
+        },
+        accounts,
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RevokeTokenParam {
+    id: AccountId,
+    player_token: UniqueId,
+    /// Whether to end every session of the account instead of only this one
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Serialize)]
+pub(super) struct RevokeTokenResponse {
+    revoked: u64,
+}
+
+pub(super) async fn revoke_token(
+    param: RevokeTokenParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<RevokeTokenResponse, SessionError> {
+    let revoked = if param.all {
+        // Only a logged in player can log out their other sessions
+        if !accounts
+            .authenticate(param.id, param.player_token)
+            .await
+            .unwrap()
+        {
+            return Err(SessionError::InvalidToken);
+        }
+        accounts
+            .revoke_tokens(param.id)
+            .await
+            .expect("Unable to revoke tokens")
+    } else if accounts
+        .revoke_token(param.id, param.player_token)
+        .await
+        .expect("Unable to revoke token")
+    {
+        1
+    } else {
+        return Err(SessionError::InvalidToken);
+    };
+
+    Ok(RevokeTokenResponse { revoked })
+}
+
+/// Body of the versioned `revoke_token` route, the token is sent in the `Authorization` header.
+#[derive(Deserialize)]
+pub(super) struct RevokeTokenBody {
+    id: AccountId,
+    #[serde(default)]
+    all: bool,
+}
+
+pub(super) async fn revoke_token_v2(
+    body: RevokeTokenBody,
+    player_token: Option<UniqueId>,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<RevokeTokenResponse, SessionError> {
+    let player_token = player_token.ok_or(SessionError::InvalidToken)?;
+    revoke_token(
+        RevokeTokenParam {
+            id: body.id,
+            player_token,
+            all: body.all,
+        },
+        accounts,
+    )
+    .await
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct AuthenticateParam {
@@ -210,6 +414,11 @@ pub(super) enum AuthenticateError {
     Connection,
     #[error("game server didn't respond correctly")]
     WrongResponse,
+    #[error("{}", rejection_message(.reason, .message))]
+    Rejected {
+        reason: Option<JoinRejectReason>,
+        message: Option<String>,
+    },
 }
 
 impl ApiErrorKind for AuthenticateError {
@@ -220,13 +429,91 @@ impl ApiErrorKind for AuthenticateError {
             AuthenticateError::WrongPassword => "UNAUTHORIZED_PWD",
             AuthenticateError::WrongResponse => "BAD_GAMESERVER_RESPONSE",
             AuthenticateError::Connection => "NO_GAMESERVER_RESPONSE",
+            AuthenticateError::Rejected { .. } => "JOIN_REJECTED",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            AuthenticateError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthenticateError::NoServer => StatusCode::NOT_FOUND,
+            AuthenticateError::WrongPassword => StatusCode::FORBIDDEN,
+            AuthenticateError::WrongResponse => StatusCode::BAD_GATEWAY,
+            AuthenticateError::Connection => StatusCode::GATEWAY_TIMEOUT,
+            AuthenticateError::Rejected { .. } => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn reason(&self) -> Option<&'static str> {
+        match self {
+            AuthenticateError::Rejected {
+                reason: Some(reason),
+                ..
+            } => Some(reason.code()),
+            _ => None,
+        }
+    }
+}
+
+/// Why a game server refused a player, as reported by the game server.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum JoinRejectReason {
+    ServerFull,
+    Banned,
+    WhitelistOnly,
+    ModMismatch,
+    /// A reason of a custom join filter
+    #[serde(other)]
+    Other,
+}
+
+fn rejection_message<'a>(
+    reason: &Option<JoinRejectReason>,
+    message: &'a Option<String>,
+) -> &'a str {
+    match (reason, message) {
+        (_, Some(message)) => message,
+        (Some(reason), None) => reason.description(),
+        (None, None) => JoinRejectReason::Other.description(),
+    }
+}
+
+impl JoinRejectReason {
+    fn code(self) -> &'static str {
+        match self {
+            JoinRejectReason::ServerFull => "SERVER_FULL",
+            JoinRejectReason::Banned => "BANNED",
+            JoinRejectReason::WhitelistOnly => "WHITELIST_ONLY",
+            JoinRejectReason::ModMismatch => "MOD_MISMATCH",
+            JoinRejectReason::Other => "OTHER",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            JoinRejectReason::ServerFull => "server is full",
+            JoinRejectReason::Banned => "you are banned from this server",
+            JoinRejectReason::WhitelistOnly => "server only allows whitelisted players",
+            JoinRejectReason::ModMismatch => "your mods don't match the server",
+            JoinRejectReason::Other => "game server rejected the join",
         }
     }
 }
 
 impl From<reqwest::Error> for AuthenticateError {
     fn from(err: reqwest::Error) -> Self {
-        if err.is_connect() {
+        if err.is_connect() || err.is_timeout() {
+            AuthenticateError::Connection
+        } else {
+            AuthenticateError::WrongResponse
+        }
+    }
+}
+
+impl From<game_servers::client::SendError> for AuthenticateError {
+    fn from(err: game_servers::client::SendError) -> Self {
+        if err.is_transport_failure() {
             AuthenticateError::Connection
         } else {
             AuthenticateError::WrongResponse
@@ -247,11 +534,87 @@ struct AuthenticateIncomingParam {
 #[serde(rename_all = "camelCase")]
 struct AuthenticateIncomingResponse {
     success: bool,
+    /// Set by game servers that explain why they rejected the player
+    #[serde(default)]
+    reason: Option<JoinRejectReason>,
+    #[serde(default)]
+    message: Option<String>,
 }
 
+/// Maximum number of characters of a rejection message relayed to the client.
+const MAX_REJECTION_MESSAGE: usize = 200;
+
+/// The error relayed to the client when a game server rejects a player.
+///
+/// Messages are written by game server owners, control characters are removed and long messages are cut
+/// so they can't mess with the client's display.
+fn rejection(response: AuthenticateIncomingResponse) -> AuthenticateError {
+    let message = response.message.map(|message| {
+        message
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_REJECTION_MESSAGE)
+            .collect::<String>()
+            .trim()
+            .to_owned()
+    });
+    AuthenticateError::Rejected {
+        reason: response.reason,
+        message: message.filter(|m| !m.is_empty()),
+    }
+}
+
+/// Length of the player auth token on legacy routes, an apparent limitation in the original implementation.
+const LEGACY_AUTH_TOKEN_LENGTH: usize = 20;
+
 pub(super) async fn authenticate(
     param: AuthenticateParam,
-    accounts: AccountRepository,
+    accounts: Arc<dyn AccountRepository>,
+    servers: SharedServerList,
+) -> Result<AuthenticateResponse, AuthenticateError> {
+    authenticate_player(param, Some(LEGACY_AUTH_TOKEN_LENGTH), accounts, servers).await
+}
+
+/// Body of the versioned `auth_with_server` route, the token is sent in the `Authorization` header.
+#[derive(Deserialize)]
+pub(super) struct AuthenticateBody {
+    id: AccountId,
+    server: UniqueId,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+pub(super) async fn authenticate_v2(
+    body: AuthenticateBody,
+    player_token: Option<UniqueId>,
+    accounts: Arc<dyn AccountRepository>,
+    servers: SharedServerList,
+) -> Result<AuthenticateResponse, AuthenticateError> {
+    let player_token = player_token.ok_or(AuthenticateError::InvalidToken)?;
+    let param = AuthenticateParam {
+        id: body.id,
+        player_token,
+        server: body.server,
+        password: body.password.filter(|p| !p.is_empty()),
+    };
+    // Versioned clients receive the full token
+    authenticate_player(param, None, accounts, servers).await
+}
+
+/// The details of a game server needed to let a player join it.
+struct JoiningServer {
+    ip: IpAddr,
+    game_port: u16,
+    auth_address: SocketAddr,
+    auth_token: UniqueId,
+    mod_names: Vec<String>,
+}
+
+/// Authenticates a player with a game server, optionally truncating the auth token to `token_length` characters.
+async fn authenticate_player(
+    param: AuthenticateParam,
+    token_length: Option<usize>,
+    accounts: Arc<dyn AccountRepository>,
     servers: SharedServerList,
 ) -> Result<AuthenticateResponse, AuthenticateError> {
     let authenticated = accounts
@@ -263,39 +626,55 @@ pub(super) async fn authenticate(
         return Err(AuthenticateError::InvalidToken);
     }
 
-    let servers = servers.read().await;
-    let server = servers
-        .get(&param.server)
-        .ok_or(AuthenticateError::NoServer)?;
+    // Copy what is needed, so the server list isn't locked while waiting for the game server
+    let server = {
+        let servers = servers.read().await;
+        let server = servers
+            .get(&param.server)
+            .ok_or(AuthenticateError::NoServer)?;
 
-    if !server.check_password(param.password) {
-        return Err(AuthenticateError::WrongPassword);
-    }
+        if !server.check_password(param.password) {
+            return Err(AuthenticateError::WrongPassword);
+        }
 
-    let auth_token = UniqueId::new(&mut rand::thread_rng());
-    // TODO: Remove truncation (apparent limitation in original implementation)
-    let mut truncated = auth_token.to_string();
-    truncated.truncate(20);
+        JoiningServer {
+            ip: server.ip(),
+            game_port: server.game_port(),
+            auth_address: server.auth_address(),
+            auth_token: server.auth_token(),
+            mod_names: server.mod_names().map(str::to_owned).collect(),
+        }
+    };
 
-    // Get persistent account data
+    let mut auth_token = UniqueId::new(&mut rand::thread_rng()).to_string();
+    if let Some(length) = token_length {
+        auth_token.truncate(length);
+    }
+
+    // Get persistent account data, including the data of mods running on the server
     let data = accounts
         .get_data(param.id)
         .await
         .expect("Unable to read account data");
+    let data = pdiff::combine(
+        accounts.as_ref(),
+        param.id,
+        data,
+        server.mod_names.iter().map(String::as_str),
+    )
+    .await
+    .expect("Unable to read account mod data");
 
     // Tell the game server there will be a player joining
-    let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
-        .with(reqwest_tracing::TracingMiddleware)
-        .build();
-    let response: AuthenticateIncomingResponse = client
+    let request = game_servers::client::client()
         .post(format!(
             "http://{}/authenticate_incoming_player",
-            server.auth_address()
+            server.auth_address
         ))
         .query(&AuthenticateIncomingParam {
             id: param.id,
-            auth_token: truncated.clone(),
-            server_auth_token: server.auth_token(),
+            auth_token: auth_token.clone(),
+            server_auth_token: server.auth_token,
             username: accounts
                 .get_name(param.id)
                 .await
@@ -303,19 +682,12 @@ pub(super) async fn authenticate(
                 .unwrap_or_default(),
         })
         .body(data.into_owned())
-        .send()
-        .await
-        .map_err(|err| match err {
-            reqwest_middleware::Error::Middleware(err) => panic!("{}", err),
-            reqwest_middleware::Error::Reqwest(err) => err,
-        })?
-        .json()
-        .await?;
+        .build()?;
+    let response: AuthenticateIncomingResponse =
+        game_servers::client::send(request).await?.json().await?;
 
     if !response.success {
-        // TODO: Explicitly report that game server rejected attempt
-        // Could be useful for custom join filters
-        return Err(AuthenticateError::WrongResponse);
+        return Err(rejection(response));
     }
 
     // Store the server as current
@@ -325,8 +697,142 @@ pub(super) async fn authenticate(
         .expect("Unable to update current server");
 
     Ok(AuthenticateResponse {
-        ip: server.ip(),
-        port: server.game_port(),
-        auth_token: truncated,
+        ip: server.ip,
+        port: server.game_port,
+        auth_token,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        accounts::account_repository,
+        auth::{provider::MockProvider, username::MockLookup},
+        database::test_databases,
+    };
+
+    fn provider() -> Arc<dyn AuthProvider> {
+        Arc::new(MockProvider::new(
+            None,
+            true,
+            "https://www.origin.com/store/titanfall/titanfall-2/standard-edition".to_owned(),
+        ))
+    }
+
+    async fn log_in(
+        id: AccountId,
+        accounts: Arc<dyn AccountRepository>,
+        names: Arc<dyn UsernameLookup>,
+    ) {
+        let param = OriginAuthenticationParam {
+            id,
+            token: "token".to_owned(),
+        };
+        let ip = [127, 0, 0, 1].into();
+        if origin_authentication(param, ip, accounts, provider(), Some(names))
+            .await
+            .is_err()
+        {
+            panic!("login with the mock provider failed");
+        }
+    }
+
+    fn reject(response: &str) -> AuthenticateError {
+        rejection(serde_json::from_str(response).unwrap())
+    }
+
+    #[test]
+    fn maps_rejections() {
+        let err = reject(r#"{"success": false, "reason": "banned", "message": "Cheating"}"#);
+        assert_eq!(err.kind(), "JOIN_REJECTED");
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        assert_eq!(err.reason(), Some("BANNED"));
+        assert_eq!(err.to_string(), "Cheating");
+
+        // Reasons of custom filters are reported as other, without a message the reason is described
+        let err = reject(r#"{"success": false, "reason": "custom_filter"}"#);
+        assert_eq!(err.reason(), Some("OTHER"));
+        assert_eq!(err.to_string(), "game server rejected the join");
+
+        let err = reject(r#"{"success": false, "message": ""}"#);
+        assert_eq!(err.reason(), None);
+        assert_eq!(err.to_string(), "game server rejected the join");
+    }
+
+    #[test]
+    fn sanitizes_rejection_messages() {
+        let err = reject(r#"{"success": false, "message": "Line\nbreak\u001b[31m red\u0000 "}"#);
+        assert_eq!(err.to_string(), "Linebreak[31m red");
+
+        let long = "é".repeat(MAX_REJECTION_MESSAGE + 50);
+        let err = reject(&format!(r#"{{"success": false, "message": "{}"}}"#, long));
+        assert_eq!(err.to_string().chars().count(), MAX_REJECTION_MESSAGE);
+
+        // Only control characters is no message at all
+        let err = reject(r#"{"success": false, "reason": "server_full", "message": "\n\t"}"#);
+        assert_eq!(err.to_string(), "server is full");
+    }
+
+    #[tokio::test]
+    async fn limits_failed_logins_per_account() {
+        let (requests, _) = RouteGroup::ClientAuth.default_limit();
+        for database in test_databases().await {
+            let accounts = account_repository(database);
+            let id = AccountId(rand::random::<u32>().into());
+            let names: Arc<dyn UsernameLookup> = Arc::new(MockLookup::new(HashMap::new()));
+
+            // Successful logins don't count, requests for an account can't lock its owner out
+            for _ in 0..requests + 5 {
+                log_in(id, accounts.clone(), names.clone()).await;
+            }
+
+            let rejecting: Arc<dyn AuthProvider> = Arc::new(MockProvider::new(
+                Some(Default::default()),
+                true,
+                String::new(),
+            ));
+            let fail = || {
+                let param = OriginAuthenticationParam {
+                    id,
+                    token: "token".to_owned(),
+                };
+                origin_authentication(
+                    param,
+                    [127, 0, 0, 1].into(),
+                    accounts.clone(),
+                    rejecting.clone(),
+                    None,
+                )
+            };
+            for _ in 0..requests {
+                assert!(matches!(
+                    fail().await,
+                    Err(OriginAuthenticationError::InvalidToken)
+                ));
+            }
+            match fail().await {
+                Err(err) => assert_eq!(err.kind(), "RATE_LIMITED"),
+                Ok(_) => panic!("failed logins weren't limited"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn first_login_stores_name() {
+        for database in test_databases().await {
+            let accounts = account_repository(database);
+            let id = AccountId(rand::random::<u32>().into());
+            let names = Arc::new(MockLookup::new(HashMap::from([(id.0, "Name".to_owned())])));
+
+            log_in(id, accounts.clone(), names).await;
+            // Stored before the login returned, not in the background
+            assert_eq!(
+                accounts.get_name(id).await.unwrap().as_deref(),
+                Some("Name")
+            );
+        }
+    }
+}
@@ -1,4 +1,6 @@
-pub use routes::routes;
+pub use routes::{routes, v2_routes};
 
 mod handlers;
+pub mod provider;
 mod routes;
+pub mod username;
@@ -0,0 +1,69 @@
+use std::collections::HashSet;
+
+use crate::accounts::AccountId;
+
+use super::{AuthProvider, OriginAccount, ProviderError};
+
+const DEFAULT_STORE_URI: &str =
+    "https://www.origin.com/store/titanfall/titanfall-2/standard-edition";
+
+/// Accepts any token without contacting Respawn, for local development and testing.
+pub struct MockProvider {
+    /// Accounts that can log in, every account is accepted if not set
+    accounts: Option<HashSet<u64>>,
+    has_online_access: bool,
+    store_uri: String,
+}
+
+impl MockProvider {
+    pub fn new(accounts: Option<HashSet<u64>>, has_online_access: bool, store_uri: String) -> Self {
+        Self {
+            accounts,
+            has_online_access,
+            store_uri,
+        }
+    }
+
+    /// Configured with `MOCK_AUTH_ACCOUNTS` (comma separated account ids),
+    /// `MOCK_AUTH_ONLINE_ACCESS` (default `true`) and `MOCK_AUTH_STORE_URI`.
+    pub fn from_env() -> Self {
+        let accounts = std::env::var("MOCK_AUTH_ACCOUNTS").ok().map(|ids| {
+            ids.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse()
+                        .expect("MOCK_AUTH_ACCOUNTS must only contain account ids")
+                })
+                .collect()
+        });
+        let has_online_access = std::env::var("MOCK_AUTH_ONLINE_ACCESS")
+            .map(|v| {
+                v.parse()
+                    .expect("MOCK_AUTH_ONLINE_ACCESS must be true or false")
+            })
+            .unwrap_or(true);
+        let store_uri =
+            std::env::var("MOCK_AUTH_STORE_URI").unwrap_or_else(|_| DEFAULT_STORE_URI.to_owned());
+
+        Self::new(accounts, has_online_access, store_uri)
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for MockProvider {
+    async fn verify(&self, id: AccountId, _token: &str) -> Result<OriginAccount, ProviderError> {
+        if self
+            .accounts
+            .as_ref()
+            .is_some_and(|accounts| !accounts.contains(&id.0))
+        {
+            return Err(ProviderError::InvalidToken);
+        }
+
+        Ok(OriginAccount {
+            has_online_access: self.has_online_access,
+            store_uri: self.store_uri.clone(),
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::accounts::AccountId;
+
+mod mock;
+mod stryder;
+
+pub use mock::MockProvider;
+pub use stryder::StryderProvider;
+
+/// What the authentication provider knows about an origin account.
+#[derive(Debug)]
+pub struct OriginAccount {
+    pub has_online_access: bool,
+    /// Store page of the game the token was issued for
+    pub store_uri: String,
+}
+
+impl OriginAccount {
+    /// Whether the token was issued for Titanfall 2.
+    pub fn owns_game(&self) -> bool {
+        self.store_uri.contains("titanfall-2")
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ProviderError {
+    #[error("origin token is invalid")]
+    InvalidToken,
+    #[error("origin token has expired")]
+    ExpiredToken,
+    #[error("request to the authentication provider failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("authentication provider is unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// Verifies origin tokens sent by clients.
+#[async_trait::async_trait]
+pub trait AuthProvider: Send + Sync {
+    async fn verify(&self, id: AccountId, token: &str) -> Result<OriginAccount, ProviderError>;
+}
+
+/// Creates the provider selected with `AUTH_PROVIDER`, either `stryder` (default) or `mock`.
+pub fn from_env() -> Arc<dyn AuthProvider> {
+    match std::env::var("AUTH_PROVIDER").as_deref() {
+        Ok("stryder") | Err(_) => Arc::new(StryderProvider::from_env()),
+        Ok("mock") => {
+            tracing::warn!("using mock authentication provider, origin tokens are not checked");
+            Arc::new(MockProvider::from_env())
+        }
+        Ok(other) => panic!("AUTH_PROVIDER must be stryder or mock, not {}", other),
+    }
+}
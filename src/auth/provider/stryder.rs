@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use serde_derive::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::accounts::AccountId;
+
+use super::{AuthProvider, OriginAccount, ProviderError};
+
+const DEFAULT_URL: &str = "https://r2-pc.stryder.respawn.com/nucleus-oauth.php";
+
+/// Players wait for the response while logging in, a hanging Stryder shouldn't hold their requests forever.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StryderParam {
+    qt: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    code: String,
+    force_trial: u32,
+    proto: u32,
+    json: u32,
+    env: &'static str,
+    user_id: String,
+}
+
+impl StryderParam {
+    fn new(user_id: AccountId, code: String) -> Self {
+        Self {
+            qt: "origin-requesttoken",
+            kind: "server_token",
+            code,
+            force_trial: 0,
+            proto: 0,
+            json: 1,
+            env: "production",
+            user_id: hex::encode_upper(user_id.0.to_be_bytes()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct StryderResponse {
+    success: Option<bool>,
+    /// Usually the string `"1"` or `"0"`
+    has_online_access: Option<serde_json::Value>,
+    store_uri: Option<String>,
+}
+
+/// Escapes control characters inside of JSON strings.
+///
+/// Stryder doesn't escape the strings in its responses, they can contain literal newlines.
+fn escape_control_characters(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            } else if c.is_control() {
+                output.push_str(&format!("\\u{:04x}", c as u32));
+                continue;
+            }
+        } else if c == '"' {
+            in_string = true;
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Determines why Stryder rejected a token.
+///
+/// Failure payloads embed the upstream error as unescaped JSON in a string,
+/// so they can not be parsed and are matched by their content instead.
+fn classify_failure(text: &str) -> ProviderError {
+    let text = text.to_lowercase();
+    if text.contains("expired") {
+        ProviderError::ExpiredToken
+    } else if text.contains("invalid_grant")
+        || text.contains("invalid_token")
+        || text.contains("code is invalid")
+    {
+        ProviderError::InvalidToken
+    } else {
+        ProviderError::Unavailable("unrecognized stryder response".to_owned())
+    }
+}
+
+/// Parses a Stryder response, tolerating its malformed JSON.
+fn parse_response(text: &str) -> Result<OriginAccount, ProviderError> {
+    let response = serde_json::from_str::<StryderResponse>(&escape_control_characters(text))
+        .unwrap_or_default();
+    match response {
+        StryderResponse {
+            success: None | Some(true),
+            has_online_access: Some(has_online_access),
+            store_uri: Some(store_uri),
+        } => Ok(OriginAccount {
+            has_online_access: match has_online_access {
+                serde_json::Value::String(s) => s.trim() == "1",
+                serde_json::Value::Number(n) => n.as_u64() == Some(1),
+                serde_json::Value::Bool(b) => b,
+                _ => false,
+            },
+            store_uri,
+        }),
+        _ => Err(classify_failure(text)),
+    }
+}
+
+/// Reports a Stryder request that timed out as an outage, like its server errors.
+fn request_error(err: reqwest::Error) -> ProviderError {
+    if err.is_timeout() {
+        ProviderError::Unavailable("stryder timed out".to_owned())
+    } else {
+        ProviderError::Request(err)
+    }
+}
+
+/// Verifies tokens with Respawn's Stryder api.
+pub struct StryderProvider {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl StryderProvider {
+    pub fn new(url: String) -> Self {
+        Self::with_timeout(url, TIMEOUT)
+    }
+
+    fn with_timeout(url: String, timeout: Duration) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(timeout)
+                .build()
+                .expect("Unable to create stryder client"),
+            url,
+        }
+    }
+
+    /// Uses the endpoint in `STRYDER_URL`, or the official one if it is not set.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("STRYDER_URL").unwrap_or_else(|_| DEFAULT_URL.to_owned()))
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthProvider for StryderProvider {
+    async fn verify(&self, id: AccountId, token: &str) -> Result<OriginAccount, ProviderError> {
+        let param = StryderParam::new(id, token.to_owned());
+        debug!(user_id = param.user_id.as_str(), "requesting stryder auth");
+        let response = self
+            .client
+            .get(&self.url)
+            .query(&param)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = response.status();
+        let text = response.text().await.map_err(request_error)?;
+        debug!(
+            account_id = id.0,
+            %status,
+            response = text.as_str(),
+            "stryder response"
+        );
+
+        if status.is_server_error() {
+            return Err(ProviderError::Unavailable(format!(
+                "stryder responded with {}",
+                status
+            )));
+        }
+        parse_response(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORE_URI: &str = "https://www.origin.com/store/titanfall/titanfall-2/standard-edition";
+
+    #[test]
+    fn escape_newlines_in_strings() {
+        let text = "{\"a\": \"line\nbreak\ttab\",\n\"b\": 1}";
+        let escaped = escape_control_characters(text);
+        assert_eq!(
+            escaped,
+            "{\"a\": \"line\\u000abreak\\u0009tab\",\n\"b\": 1}"
+        );
+        assert!(serde_json::from_str::<serde_json::Value>(&escaped).is_ok());
+    }
+
+    #[test]
+    fn escape_keeps_escaped_quotes() {
+        let text = "{\"a\": \"say \\\"hi\nthere\\\"\"}";
+        let value: serde_json::Value =
+            serde_json::from_str(&escape_control_characters(text)).unwrap();
+        assert_eq!(value["a"], "say \"hi\nthere\"");
+    }
+
+    #[test]
+    fn parse_success() {
+        let text = format!(
+            r#"{{"success": true, "hasOnlineAccess": "1", "storeUri": "{}"}}"#,
+            STORE_URI
+        );
+        let account = parse_response(&text).unwrap();
+        assert!(account.has_online_access);
+        assert!(account.owns_game());
+    }
+
+    #[test]
+    fn parse_online_access_formats() {
+        for (value, expected) in [
+            ("\"1\"", true),
+            ("\"0\"", false),
+            ("1", true),
+            ("false", false),
+        ] {
+            let text = format!(
+                r#"{{"hasOnlineAccess": {}, "storeUri": "{}"}}"#,
+                value, STORE_URI
+            );
+            assert_eq!(parse_response(&text).unwrap().has_online_access, expected);
+        }
+    }
+
+    #[test]
+    fn parse_success_with_control_characters() {
+        let text = format!(
+            "{{\"success\": true, \"hasOnlineAccess\": \"1\n\", \"storeUri\": \"{}\"}}",
+            STORE_URI
+        );
+        assert!(parse_response(&text).unwrap().has_online_access);
+    }
+
+    #[test]
+    fn parse_failures() {
+        // The upstream error is embedded without escaping
+        let invalid = r#"{"success": false, "status": "400", "error": "{"error":"invalid_grant","error_description":"code is invalid","code":100100}"}"#;
+        assert!(matches!(
+            parse_response(invalid),
+            Err(ProviderError::InvalidToken)
+        ));
+
+        let expired = r#"{"success": false, "status": "400", "error": "{"error":"invalid_token","error_description":"The access token has Expired"}"}"#;
+        assert!(matches!(
+            parse_response(expired),
+            Err(ProviderError::ExpiredToken)
+        ));
+
+        let unknown = r#"{"success": false, "status": "500", "error": "internal"}"#;
+        assert!(matches!(
+            parse_response(unknown),
+            Err(ProviderError::Unavailable(_))
+        ));
+    }
+
+    #[test]
+    fn parse_malformed_responses() {
+        for text in ["", "<html>Bad Gateway</html>", "{\"success\": true", "{}"] {
+            assert!(matches!(
+                parse_response(text),
+                Err(ProviderError::Unavailable(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_is_an_outage() {
+        // Accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let provider = StryderProvider::with_timeout(url, Duration::from_millis(100));
+        let result = provider.verify(AccountId(1), "token").await;
+        assert!(matches!(result, Err(ProviderError::Unavailable(_))));
+        server.abort();
+    }
+
+    #[test]
+    fn classify_is_case_insensitive() {
+        assert!(matches!(
+            classify_failure("INVALID_GRANT"),
+            ProviderError::InvalidToken
+        ));
+        assert!(matches!(
+            classify_failure("token EXPIRED"),
+            ProviderError::ExpiredToken
+        ));
+    }
+}
@@ -1,28 +1,45 @@
+use std::sync::Arc;
+
 use warp::Filter;
 
 use crate::{
-    accounts::with_accounts, api::api_response, game_servers::with_servers, Database,
-    SharedServerList,
+    accounts::with_accounts,
+    api::{api_response, bearer_token, json_body},
+    game_servers::with_servers,
+    proxy::client_ip,
+    rate_limit::{self, RouteGroup},
+    Database, SharedServerList,
 };
 
+use super::{provider::AuthProvider, username::UsernameLookup};
+
 pub fn routes(
     database: Database,
     servers: SharedServerList,
+    provider: Arc<dyn AuthProvider>,
+    names: Option<Arc<dyn UsernameLookup>>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let base = warp::path("client");
-    base.and(origin_authentication(database.clone()))
+    base.and(origin_authentication(database.clone(), provider, names))
         .or(base.and(authenticate_self(database.clone())))
-        .or(base.and(authenticate(database, servers)))
+        .or(base.and(authenticate(database.clone(), servers)))
+        .or(base.and(refresh_token(database.clone())))
+        .or(base.and(revoke_token(database)))
 }
 
 pub(super) fn origin_authentication(
     database: Database,
+    provider: Arc<dyn AuthProvider>,
+    names: Option<Arc<dyn UsernameLookup>>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("origin_auth")
         .and(warp::get())
+        .and(rate_limit::by_ip(RouteGroup::ClientAuth))
         .and(warp::query::<super::handlers::OriginAuthenticationParam>())
-        .and(warp::addr::remote())
+        .and(client_ip())
         .and(with_accounts(database))
+        .and(with_provider(provider))
+        .and(with_username_lookup(names))
         .then(super::handlers::origin_authentication)
         .map(api_response)
 }
@@ -32,6 +49,7 @@ pub(super) fn authenticate_self(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("auth_with_self")
         .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
         .and(warp::query::<super::handlers::AuthenticateSelfParam>())
         .and(with_accounts(database))
         .then(super::handlers::authenticate_self)
@@ -44,9 +62,133 @@ pub(super) fn authenticate(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("auth_with_server")
         .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientAuth))
         .and(warp::query::<super::handlers::AuthenticateParam>())
         .and(with_accounts(database))
         .and(with_servers(servers))
         .then(super::handlers::authenticate)
         .map(api_response)
 }
+
+pub(super) fn refresh_token(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("refresh_token")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
+        .and(warp::query::<super::handlers::RefreshTokenParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::refresh_token)
+        .map(api_response)
+}
+
+pub(super) fn revoke_token(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("revoke_token")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
+        .and(warp::query::<super::handlers::RevokeTokenParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::revoke_token)
+        .map(api_response)
+}
+
+/// Routes of the versioned api, credentials are sent in JSON bodies and headers.
+pub fn v2_routes(
+    database: Database,
+    servers: SharedServerList,
+    provider: Arc<dyn AuthProvider>,
+    names: Option<Arc<dyn UsernameLookup>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let base = warp::path("client");
+    base.and(origin_authentication_v2(database.clone(), provider, names))
+        .or(base.and(authenticate_self_v2(database.clone())))
+        .or(base.and(authenticate_v2(database.clone(), servers)))
+        .or(base.and(refresh_token_v2(database.clone())))
+        .or(base.and(revoke_token_v2(database)))
+}
+
+pub(super) fn origin_authentication_v2(
+    database: Database,
+    provider: Arc<dyn AuthProvider>,
+    names: Option<Arc<dyn UsernameLookup>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("origin_auth")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientAuth))
+        .and(json_body::<super::handlers::OriginAuthenticationParam>())
+        .and(client_ip())
+        .and(with_accounts(database))
+        .and(with_provider(provider))
+        .and(with_username_lookup(names))
+        .then(super::handlers::origin_authentication)
+        .map(api_response)
+}
+
+pub(super) fn authenticate_self_v2(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("auth_with_self")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
+        .and(json_body::<super::handlers::AuthenticateSelfBody>())
+        .and(bearer_token())
+        .and(with_accounts(database))
+        .then(super::handlers::authenticate_self_v2)
+        .map(api_response)
+}
+
+pub(super) fn authenticate_v2(
+    database: Database,
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("auth_with_server")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientAuth))
+        .and(json_body::<super::handlers::AuthenticateBody>())
+        .and(bearer_token())
+        .and(with_accounts(database))
+        .and(with_servers(servers))
+        .then(super::handlers::authenticate_v2)
+        .map(api_response)
+}
+
+pub(super) fn refresh_token_v2(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("refresh_token")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
+        .and(json_body::<super::handlers::RefreshTokenBody>())
+        .and(bearer_token())
+        .and(with_accounts(database))
+        .then(super::handlers::refresh_token_v2)
+        .map(api_response)
+}
+
+pub(super) fn revoke_token_v2(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("revoke_token")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ClientSession))
+        .and(json_body::<super::handlers::RevokeTokenBody>())
+        .and(bearer_token())
+        .and(with_accounts(database))
+        .then(super::handlers::revoke_token_v2)
+        .map(api_response)
+}
+
+pub fn with_provider(
+    provider: Arc<dyn AuthProvider>,
+) -> impl Filter<Extract = (Arc<dyn AuthProvider>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || provider.clone())
+}
+
+pub fn with_username_lookup(
+    names: Option<Arc<dyn UsernameLookup>>,
+) -> impl Filter<Extract = (Option<Arc<dyn UsernameLookup>>,), Error = std::convert::Infallible> + Clone
+{
+    warp::any().map(move || names.clone())
+}
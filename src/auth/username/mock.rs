@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::accounts::AccountId;
+
+use super::{LookupError, UsernameLookup};
+
+/// Returns configured names without contacting Origin, for local development and testing.
+pub struct MockLookup {
+    names: HashMap<u64, String>,
+}
+
+impl MockLookup {
+    pub fn new(names: HashMap<u64, String>) -> Self {
+        Self { names }
+    }
+
+    /// Names are configured in `MOCK_USERNAMES` as comma separated `id=name` pairs.
+    pub fn from_env() -> Self {
+        let names = std::env::var("MOCK_USERNAMES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (id, name) = pair
+                    .split_once('=')
+                    .expect("MOCK_USERNAMES must contain id=name pairs");
+                let id = id
+                    .trim()
+                    .parse()
+                    .expect("MOCK_USERNAMES must contain valid account ids");
+                (id, name.trim().to_owned())
+            })
+            .collect();
+        Self::new(names)
+    }
+}
+
+#[async_trait::async_trait]
+impl UsernameLookup for MockLookup {
+    async fn lookup(&self, id: AccountId) -> Result<Option<String>, LookupError> {
+        Ok(self.names.get(&id.0).cloned())
+    }
+}
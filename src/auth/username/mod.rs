@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use thiserror::Error;
+
+use crate::accounts::AccountId;
+
+mod mock;
+mod origin;
+
+pub use mock::MockLookup;
+pub use origin::OriginLookup;
+
+#[derive(Error, Debug)]
+pub enum LookupError {
+    #[error("request to the username lookup failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("username lookup returned an unexpected response")]
+    InvalidResponse,
+}
+
+/// Resolves the current name of an origin account.
+#[async_trait::async_trait]
+pub trait UsernameLookup: Send + Sync {
+    /// Returns `None` if the account has no known name.
+    async fn lookup(&self, id: AccountId) -> Result<Option<String>, LookupError>;
+}
+
+/// Creates the lookup selected with `USERNAME_LOOKUP`, either `origin` or `mock`.
+///
+/// Names are not resolved if it is not set.
+pub fn from_env() -> Option<Arc<dyn UsernameLookup>> {
+    match std::env::var("USERNAME_LOOKUP").as_deref() {
+        Ok("origin") => Some(Arc::new(OriginLookup::from_env())),
+        Ok("mock") => Some(Arc::new(MockLookup::from_env())),
+        Ok("none") | Err(_) => {
+            tracing::warn!("USERNAME_LOOKUP is not set, player names will not be resolved");
+            None
+        }
+        Ok(other) => panic!(
+            "USERNAME_LOOKUP must be origin, mock or none, not {}",
+            other
+        ),
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::accounts::AccountId;
+
+use super::{LookupError, UsernameLookup};
+
+const DEFAULT_URL: &str = "https://api1.origin.com/atom/users";
+
+/// The first login of a player waits for the lookup, and lookups shouldn't pile up when origin is slow.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Looks up names with the Origin users api, which requires the auth token of an Origin account.
+pub struct OriginLookup {
+    client: reqwest::Client,
+    url: String,
+    auth_token: String,
+}
+
+impl OriginLookup {
+    pub fn new(url: String, auth_token: String) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .timeout(TIMEOUT)
+                .build()
+                .expect("Unable to create origin client"),
+            url,
+            auth_token,
+        }
+    }
+
+    /// Uses the token in `ORIGIN_AUTH_TOKEN` and the endpoint in `ORIGIN_USERS_URL`, if set.
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("ORIGIN_USERS_URL").unwrap_or_else(|_| DEFAULT_URL.to_owned()),
+            std::env::var("ORIGIN_AUTH_TOKEN")
+                .expect("ORIGIN_AUTH_TOKEN must be set to look up names with origin"),
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl UsernameLookup for OriginLookup {
+    async fn lookup(&self, id: AccountId) -> Result<Option<String>, LookupError> {
+        let text = self
+            .client
+            .get(&self.url)
+            .query(&[("userIds", id.0)])
+            .header("AuthToken", &self.auth_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        debug!(
+            account_id = id.0,
+            response = text.as_str(),
+            "origin users response"
+        );
+
+        parse_response(&text)
+    }
+}
+
+/// Reads the name from a response of the users api.
+///
+/// The response is a small XML document like
+/// `<users><user><userId>1</userId><personaId>2</personaId><EAID>Name</EAID></user></users>`,
+/// unknown accounts are left out of it.
+fn parse_response(text: &str) -> Result<Option<String>, LookupError> {
+    if !text.contains("<users") {
+        return Err(LookupError::InvalidResponse);
+    }
+    let rest = match text.split_once("<EAID>") {
+        Some((_, rest)) => rest,
+        None => return Ok(None),
+    };
+    let (name, _) = rest
+        .split_once("</EAID>")
+        .ok_or(LookupError::InvalidResponse)?;
+    let name = unescape(name.trim()).ok_or(LookupError::InvalidResponse)?;
+    Ok(Some(name).filter(|name| !name.is_empty()))
+}
+
+/// Replaces the entity and character references of XML text, returns `None` for unknown references.
+fn unescape(text: &str) -> Option<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((before, reference)) = rest.split_once('&') {
+        output.push_str(before);
+        let (name, after) = reference.split_once(';')?;
+        let c = match name {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match name.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => name.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        output.push(c);
+        rest = after;
+    }
+    output.push_str(rest);
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_name() {
+        let text = "<users><user><userId>1</userId><personaId>2</personaId><EAID>A&amp;B</EAID></user></users>";
+        assert_eq!(parse_response(text).unwrap().as_deref(), Some("A&B"));
+    }
+
+    #[test]
+    fn parse_missing_user() {
+        for text in [
+            "<users/>",
+            "<users></users>",
+            "<users><user><EAID></EAID></user></users>",
+        ] {
+            assert_eq!(parse_response(text).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn parse_malformed_responses() {
+        for text in [
+            "",
+            "<html>Bad Gateway</html>",
+            "<users><user><EAID>Name",
+            "<users><user><EAID>A&B</EAID></user></users>",
+        ] {
+            assert!(matches!(
+                parse_response(text),
+                Err(LookupError::InvalidResponse)
+            ));
+        }
+    }
+
+    #[test]
+    fn unescape_entities() {
+        assert_eq!(unescape("Plain_Name").as_deref(), Some("Plain_Name"));
+        assert_eq!(
+            unescape("A&amp;B &lt;3 &quot;x&quot; &apos;y&apos; &gt;").as_deref(),
+            Some("A&B <3 \"x\" 'y' >")
+        );
+        assert_eq!(
+            unescape("&#65;&#x42;&#x1F600;").as_deref(),
+            Some("AB\u{1F600}")
+        );
+    }
+
+    #[test]
+    fn unescape_rejects_invalid_references() {
+        assert_eq!(unescape("A&B"), None);
+        assert_eq!(unescape("&unknown;"), None);
+        assert_eq!(unescape("&#xD800;"), None);
+        assert_eq!(unescape("&#;"), None);
+    }
+}
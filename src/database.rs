@@ -0,0 +1,57 @@
+/// Connection pool of the configured database.
+///
+/// The backend is chosen from the scheme of `DATABASE_URL`, `postgres://` (or `postgresql://`)
+/// connects to PostgreSQL and anything else is treated as SQLite.
+#[derive(Clone)]
+pub enum Database {
+    Sqlite(sqlx::SqlitePool),
+    Postgres(sqlx::PgPool),
+}
+
+impl Database {
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Ok(Self::Postgres(sqlx::PgPool::connect(url).await?))
+        } else {
+            Ok(Self::Sqlite(sqlx::SqlitePool::connect(url).await?))
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            Self::Sqlite(pool) => pool.close().await,
+            Self::Postgres(pool) => pool.close().await,
+        }
+    }
+}
+
+/// Databases the repository tests run against, with every migration applied.
+///
+/// A new in-memory SQLite database is always included, PostgreSQL only if `TEST_POSTGRES_URL` is set.
+/// Tests share the PostgreSQL database, so they have to use random account and server ids.
+#[cfg(test)]
+pub async fn test_databases() -> Vec<Database> {
+    // Every connection opens its own in-memory database, so the pool must keep a single one
+    let sqlite = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .expect("Unable to open SQLite database");
+    sqlx::migrate!("./migrations")
+        .run(&sqlite)
+        .await
+        .expect("Unable to migrate SQLite database");
+    let mut databases = vec![Database::Sqlite(sqlite)];
+
+    if let Ok(url) = std::env::var("TEST_POSTGRES_URL") {
+        let postgres = sqlx::PgPool::connect(&url)
+            .await
+            .expect("Unable to connect to TEST_POSTGRES_URL");
+        sqlx::migrate!("./migrations_postgres")
+            .run(&postgres)
+            .await
+            .expect("Unable to migrate PostgreSQL database");
+        databases.push(Database::Postgres(postgres));
+    }
+    databases
+}
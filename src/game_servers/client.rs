@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use once_cell::sync::OnceCell;
+use reqwest_middleware::ClientWithMiddleware;
+use thiserror::Error;
+
+struct ClientConfig {
+    connect_timeout: Duration,
+    timeout: Duration,
+    retries: u32,
+}
+
+/// Limits of requests to game servers.
+///
+/// Configured by `GAME_SERVER_CONNECT_TIMEOUT_MS` (default: 2000), `GAME_SERVER_TIMEOUT_MS` (default: 5000)
+/// and `GAME_SERVER_RETRIES` (default: 2).
+fn config() -> &'static ClientConfig {
+    static INSTANCE: OnceCell<ClientConfig> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        let var = |name: &str, default: u64| {
+            std::env::var(name)
+                .map(|v| {
+                    v.parse::<u64>()
+                        .unwrap_or_else(|_| panic!("{} must be an integer", name))
+                })
+                .unwrap_or(default)
+        };
+
+        ClientConfig {
+            connect_timeout: Duration::from_millis(var("GAME_SERVER_CONNECT_TIMEOUT_MS", 2000)),
+            timeout: Duration::from_millis(var("GAME_SERVER_TIMEOUT_MS", 5000)),
+            retries: var("GAME_SERVER_RETRIES", 2) as u32,
+        }
+    })
+}
+
+/// The client for requests to game servers, shared so connections are reused.
+pub fn client() -> &'static ClientWithMiddleware {
+    static INSTANCE: OnceCell<ClientWithMiddleware> = OnceCell::new();
+    INSTANCE.get_or_init(|| build_client(config()))
+}
+
+fn build_client(config: &ClientConfig) -> ClientWithMiddleware {
+    let client = reqwest::Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.timeout)
+        .build()
+        .expect("Unable to create game server client");
+    reqwest_middleware::ClientBuilder::new(client)
+        .with(reqwest_tracing::TracingMiddleware)
+        .build()
+}
+
+/// A request to a game server that failed.
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    /// A middleware failed, the request may not have reached the game server
+    #[error("game server request failed: {0}")]
+    Middleware(String),
+}
+
+impl SendError {
+    /// Whether the game server couldn't be reached or didn't respond in time.
+    pub fn is_transport_failure(&self) -> bool {
+        match self {
+            SendError::Request(err) => err.is_connect() || err.is_timeout(),
+            SendError::Middleware(_) => true,
+        }
+    }
+}
+
+/// Sends a request to a game server, retrying connection failures and timeouts.
+///
+/// Requests that aren't idempotent, such as announcing a joining player, are only retried when the connection
+/// failed, as a timed out request may still have been handled by the game server.
+/// The request must have a buffered body, so it can be sent again.
+pub async fn send(request: reqwest::Request) -> Result<reqwest::Response, SendError> {
+    send_with(client(), config().retries, request).await
+}
+
+async fn send_with(
+    client: &ClientWithMiddleware,
+    retries: u32,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, SendError> {
+    let idempotent = request.method().is_idempotent();
+    let mut attempt = 0;
+    loop {
+        let retry = request
+            .try_clone()
+            .expect("Game server requests must have a buffered body");
+        let err = match client.execute(retry).await {
+            Ok(response) => return Ok(response),
+            Err(reqwest_middleware::Error::Middleware(err)) => {
+                return Err(SendError::Middleware(err.to_string()))
+            }
+            Err(reqwest_middleware::Error::Reqwest(err)) => err,
+        };
+
+        let retryable = err.is_connect() || (idempotent && err.is_timeout());
+        if attempt >= retries || !retryable {
+            return Err(err.into());
+        }
+        attempt += 1;
+        tracing::debug!(%err, attempt, url = %request.url(), "retrying game server request");
+        tokio::time::sleep(Duration::from_millis(250) * attempt).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const RETRIES: u32 = 2;
+
+    /// A client with a short timeout, independent of the configuration of the master server.
+    fn test_client() -> ClientWithMiddleware {
+        build_client(&ClientConfig {
+            connect_timeout: Duration::from_millis(200),
+            timeout: Duration::from_millis(200),
+            retries: RETRIES,
+        })
+    }
+
+    /// Starts a game server that accepts connections but never responds, returning its url and connection count.
+    async fn unresponsive_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                open.push(stream);
+            }
+        });
+        (url, connections)
+    }
+
+    #[tokio::test]
+    async fn retries_idempotent_timeouts() {
+        let (url, connections) = unresponsive_server().await;
+        let client = test_client();
+        let request = client.get(url).build().unwrap();
+
+        let err = send_with(&client, RETRIES, request).await.unwrap_err();
+        assert!(err.is_transport_failure());
+        assert_eq!(connections.load(Ordering::SeqCst), RETRIES as usize + 1);
+    }
+
+    #[tokio::test]
+    async fn sends_other_requests_once() {
+        let (url, connections) = unresponsive_server().await;
+        let client = test_client();
+        let request = client.post(url).body("data").build().unwrap();
+
+        let err = send_with(&client, RETRIES, request).await.unwrap_err();
+        assert!(err.is_transport_failure());
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    /// Fails every request before it is sent.
+    struct FailingMiddleware;
+
+    #[async_trait::async_trait]
+    impl reqwest_middleware::Middleware for FailingMiddleware {
+        async fn handle(
+            &self,
+            _: reqwest::Request,
+            _: &mut task_local_extensions::Extensions,
+            _: reqwest_middleware::Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            Err(reqwest_middleware::Error::Middleware(anyhow::anyhow!(
+                "middleware failed"
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_errors_are_transport_failures() {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(FailingMiddleware)
+            .build();
+        let request = client.get("http://127.0.0.1:1/").build().unwrap();
+
+        let err = send_with(&client, RETRIES, request).await.unwrap_err();
+        assert!(matches!(err, SendError::Middleware(_)));
+        assert!(err.is_transport_failure());
+    }
+}
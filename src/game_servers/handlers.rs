@@ -1,21 +1,20 @@
-use std::{
-    borrow::Cow,
-    net::SocketAddr,
-    time::{Duration, Instant},
-};
+use std::{borrow::Cow, future::Future, net::IpAddr, time::Instant};
 
+use once_cell::sync::OnceCell;
 use serde_derive::{Deserialize, Serialize};
 use thiserror::Error;
-use warp::multipart::FormData;
+use warp::{http::StatusCode, multipart::FormData};
 
 use crate::{
     api::{api_response, ApiErrorKind},
     id::UniqueId,
+    rate_limit::{self, RateLimitError, RouteGroup},
     SharedServerList,
 };
 
 use super::{
-    verify::VerifyServerError, AddServerError, ModInfo, Server, ServerSettings, MAX_PLAYERS_LIMIT,
+    snapshot::ServerListCache, verify::VerifyServerError, AddServerError, ModInfo, Server,
+    ServerSettings, LISTED_TIMEOUT, MAX_PLAYERS_LIMIT,
 };
 
 #[derive(Error, Debug)]
@@ -39,6 +38,15 @@ impl ApiErrorKind for CreateServerError {
             CreateServerError::ConflictingAuthPort => "AUTH_PORT_CONFLICT",
         }
     }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            CreateServerError::Verification(e) => e.status(),
+            CreateServerError::InvalidModInfo => StatusCode::BAD_REQUEST,
+            CreateServerError::MaximumServersForHost => StatusCode::FORBIDDEN,
+            CreateServerError::ConflictingAuthPort => StatusCode::CONFLICT,
+        }
+    }
 }
 
 impl From<AddServerError> for CreateServerError {
@@ -59,19 +67,42 @@ pub(super) struct CreateServerResponse {
 
 pub(super) async fn create_server_entry(
     settings: ServerSettings,
-    remote: Option<SocketAddr>,
+    ip: IpAddr,
     servers: SharedServerList,
     form: FormData,
 ) -> Result<CreateServerResponse, CreateServerError> {
-    let ip = remote
-        .ok_or(CreateServerError::Verification(VerifyServerError::Unknown))?
-        .ip();
-
-    super::verify::verify_server(ip, settings.auth_port).await?;
-
     let mod_info = ModInfo::from_form(form)
         .await
         .map_err(|_| CreateServerError::InvalidModInfo)?;
+    register_server(settings, mod_info, ip, servers).await
+}
+
+/// Body of the versioned `add_server` route.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CreateServerBody {
+    #[serde(flatten)]
+    settings: ServerSettings,
+    #[serde(default)]
+    mod_info: Option<ModInfo>,
+}
+
+pub(super) async fn create_server_entry_v2(
+    body: CreateServerBody,
+    ip: IpAddr,
+    servers: SharedServerList,
+) -> Result<CreateServerResponse, CreateServerError> {
+    register_server(body.settings, body.mod_info, ip, servers).await
+}
+
+async fn register_server(
+    settings: ServerSettings,
+    mod_info: Option<ModInfo>,
+    ip: IpAddr,
+    servers: SharedServerList,
+) -> Result<CreateServerResponse, CreateServerError> {
+    super::verify::verify_server(ip, settings.auth_port).await?;
+
     let server = Server::new(ip, settings, mod_info);
     let response = CreateServerResponse {
         id: server.id.to_string(),
@@ -96,8 +127,8 @@ pub(super) async fn create_server_entry(
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct ServerListEntry<'a> {
-    id: &'a UniqueId,
+pub(super) struct ServerListEntry<'a> {
+    pub(super) id: &'a UniqueId,
     name: &'a str,
     description: &'a str,
     map: &'a str,
@@ -131,23 +162,224 @@ impl<'a> From<&'a Server> for ServerListEntry<'a> {
     }
 }
 
-// TODO: Cache this
-pub(super) async fn list_servers(servers: SharedServerList) -> impl warp::Reply {
-    let mut servers = servers.write().await;
-    servers.remove_inactive();
-    // Create server entries for those we have seen in the last minute
-    let list: Vec<ServerListEntry> = servers
+/// Maximum number of servers returned at once when paginating.
+const MAX_PAGE_SIZE: usize = 100;
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum ServerSort {
+    /// Most players first
+    Players,
+    Name,
+}
+
+/// Optional filters of the server list, without any the full list is returned.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ServerListParam {
+    map: Option<String>,
+    playlist: Option<String>,
+    has_password: Option<bool>,
+    #[serde(default)]
+    not_full: bool,
+    /// Comma separated names of mods the server has to run
+    mods: Option<String>,
+    /// Part of the server name, ignoring case
+    name: Option<String>,
+    sort: Option<ServerSort>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+}
+
+impl ServerListParam {
+    /// Whether the full list is requested, which is answered from the snapshot.
+    fn is_unfiltered(&self) -> bool {
+        self.map.is_none()
+            && self.playlist.is_none()
+            && self.has_password.is_none()
+            && !self.not_full
+            && self.mods.is_none()
+            && self.name.is_none()
+            && self.sort.is_none()
+            && self.page.is_none()
+            && self.page_size.is_none()
+    }
+
+    fn matches(&self, entry: &ServerListEntry, name: Option<&str>, mods: &[&str]) -> bool {
+        self.map.as_ref().is_none_or(|map| entry.map == map)
+            && self
+                .playlist
+                .as_ref()
+                .is_none_or(|playlist| entry.playlist == playlist)
+            && self
+                .has_password
+                .is_none_or(|has_password| entry.has_password == has_password)
+            && (!self.not_full || entry.player_count < entry.max_players)
+            && name.is_none_or(|name| entry.name.to_lowercase().contains(name))
+            && mods
+                .iter()
+                .all(|required| entry.mod_info.mods.iter().any(|m| m.name == *required))
+    }
+}
+
+#[derive(Error, Debug)]
+pub(super) enum ServerListError {
+    #[error("page size must be between 1 and {MAX_PAGE_SIZE}")]
+    InvalidPageSize,
+}
+
+impl ApiErrorKind for ServerListError {
+    fn kind(&self) -> &'static str {
+        match self {
+            ServerListError::InvalidPageSize => "INVALID_PAGE_SIZE",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ServerListError::InvalidPageSize => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+pub(super) async fn list_servers(
+    param: ServerListParam,
+    if_none_match: Option<String>,
+    accept_encoding: Option<String>,
+    servers: SharedServerList,
+    cache: ServerListCache,
+) -> Box<dyn warp::Reply> {
+    if param.is_unfiltered() {
+        return Box::new(
+            cache
+                .current()
+                .reply(if_none_match.as_deref(), accept_encoding.as_deref()),
+        );
+    }
+
+    let page_size = param.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return Box::new(api_response::<(), _>(Err(ServerListError::InvalidPageSize)));
+    }
+
+    let name = param.name.as_ref().map(|name| name.to_lowercase());
+    let mods: Vec<&str> = param
+        .mods
         .iter()
-        .filter(|s| s.last_seen_age() < Duration::from_secs(60))
-        .map(|s| s.into())
+        .flat_map(|mods| mods.split(','))
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .collect();
+
+    let servers = servers.read().await;
+    let mut list: Vec<ServerListEntry> = servers
+        .listed()
+        .map(ServerListEntry::from)
+        .filter(|entry| param.matches(entry, name.as_deref(), &mods))
         .collect();
-    warp::reply::json(&list)
+
+    match param.sort {
+        Some(ServerSort::Players) => list.sort_by(|a, b| {
+            b.player_count
+                .cmp(&a.player_count)
+                .then_with(|| a.name.cmp(b.name))
+        }),
+        Some(ServerSort::Name) => list.sort_by_cached_key(|entry| entry.name.to_lowercase()),
+        // Pages need a stable order
+        None if param.page.is_some() => list.sort_by_key(|entry| *entry.id.bytes()),
+        None => {}
+    }
+
+    match param.page {
+        Some(page) => {
+            let total = list.len();
+            let page: Vec<ServerListEntry> = list
+                .into_iter()
+                .skip(page.saturating_mul(page_size))
+                .take(page_size)
+                .collect();
+            Box::new(warp::reply::with_header(
+                warp::reply::json(&page),
+                "x-total-count",
+                total,
+            ))
+        }
+        None => Box::new(warp::reply::json(&list)),
+    }
+}
+
+#[derive(Error, Debug)]
+pub(super) enum ServerAuthError {
+    #[error("server auth token is missing or invalid")]
+    InvalidToken,
+    #[error("request was not sent from the address of the server")]
+    AddressMismatch,
+}
+
+impl ApiErrorKind for ServerAuthError {
+    fn kind(&self) -> &'static str {
+        match self {
+            ServerAuthError::InvalidToken => "INVALID_SERVER_TOKEN",
+            ServerAuthError::AddressMismatch => "SERVER_ADDRESS_MISMATCH",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ServerAuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            ServerAuthError::AddressMismatch => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// Whether the legacy routes still accept requests without a server auth token, checking only their address.
+///
+/// Enabled by `SERVER_TOKEN_LEGACY`, for servers that don't send their token yet. Deprecated: anyone on the
+/// same host or behind the same NAT can change those servers, the fallback will be removed.
+pub fn legacy_token_fallback() -> bool {
+    static INSTANCE: OnceCell<bool> = OnceCell::new();
+    *INSTANCE.get_or_init(|| {
+        let enabled = std::env::var("SERVER_TOKEN_LEGACY")
+            .map(|v| {
+                v.parse::<bool>()
+                    .expect("SERVER_TOKEN_LEGACY must be true or false")
+            })
+            .unwrap_or(false);
+        if enabled {
+            tracing::warn!("SERVER_TOKEN_LEGACY is deprecated, servers without an auth token are only checked by their address");
+        }
+        enabled
+    })
+}
+
+/// Checks that a request was sent by the server itself.
+///
+/// It must contain the auth token the server received from `add_server` and come from the address of the server,
+/// so other processes on the same host or behind the same NAT can't change it.
+/// With `allow_missing_token` a request without a token only has to come from that address, a wrong token is still refused.
+fn authorize(
+    server: &Server,
+    token: Option<UniqueId>,
+    ip: IpAddr,
+    allow_missing_token: bool,
+) -> Result<(), ServerAuthError> {
+    if token.is_none() && allow_missing_token {
+        tracing::debug!(id = %server.id, "server authorized without auth token");
+    } else if !server.has_auth_token(token) {
+        return Err(ServerAuthError::InvalidToken);
+    }
+    if server.ip != ip {
+        return Err(ServerAuthError::AddressMismatch);
+    }
+    Ok(())
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct UpdateServerParam {
     id: UniqueId,
+    server_auth_token: Option<UniqueId>,
     port: Option<u16>,
     auth_port: Option<u16>,
     name: Option<String>,
@@ -183,7 +415,7 @@ impl UpdateServerParam {
 
         if let Some(mut password) = self.password {
             // Remove password if empty string passed
-            if password.as_ref().map_or(false, |p| p.is_empty()) {
+            if password.as_ref().is_some_and(|p| p.is_empty()) {
                 password = None;
             }
             server.settings.password = password;
@@ -212,17 +444,88 @@ impl TryFrom<UpdateServerParam> for ServerSettings {
     }
 }
 
+#[derive(Error, Debug)]
+pub(super) enum UpdateServerError {
+    #[error("server is not registered, it has to register again")]
+    NotRegistered,
+    #[error(transparent)]
+    Auth(#[from] ServerAuthError),
+    #[error(transparent)]
+    Create(#[from] CreateServerError),
+    #[error(transparent)]
+    RateLimited(#[from] RateLimitError),
+}
+
+impl ApiErrorKind for UpdateServerError {
+    fn kind(&self) -> &'static str {
+        match self {
+            UpdateServerError::NotRegistered => "SERVER_NOT_REGISTERED",
+            UpdateServerError::Auth(e) => e.kind(),
+            UpdateServerError::Create(e) => e.kind(),
+            UpdateServerError::RateLimited(e) => e.kind(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            UpdateServerError::NotRegistered => StatusCode::NOT_FOUND,
+            UpdateServerError::Auth(e) => e.status(),
+            UpdateServerError::Create(e) => e.status(),
+            UpdateServerError::RateLimited(e) => e.status(),
+        }
+    }
+}
+
+/// Updates the settings of a server, servers that aren't registered are created if all settings are given.
+///
+/// Responds with the id and token of created servers.
 pub(super) async fn update_server(
     param: UpdateServerParam,
-    remote: Option<SocketAddr>,
+    ip: IpAddr,
     server_list: SharedServerList,
     form: FormData,
-) -> Box<dyn warp::Reply> {
-    let ip = match remote {
-        Some(addr) => addr.ip(),
-        None => return Box::new(warp::reply()),
+) -> Result<Option<CreateServerResponse>, UpdateServerError> {
+    let mod_info = async {
+        ModInfo::from_form(form)
+            .await
+            .map_err(|_| CreateServerError::InvalidModInfo)
     };
+    update_or_register(param, mod_info, ip, server_list, legacy_token_fallback()).await
+}
+
+/// Body of the versioned `update_values` route, the server auth token is sent as bearer token.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct UpdateServerBody {
+    #[serde(flatten)]
+    values: UpdateServerParam,
+    #[serde(default)]
+    mod_info: Option<ModInfo>,
+}
 
+pub(super) async fn update_server_v2(
+    body: UpdateServerBody,
+    token: Option<UniqueId>,
+    ip: IpAddr,
+    server_list: SharedServerList,
+) -> Result<Option<CreateServerResponse>, UpdateServerError> {
+    let param = UpdateServerParam {
+        server_auth_token: token,
+        ..body.values
+    };
+    update_or_register(param, async { Ok(body.mod_info) }, ip, server_list, false).await
+}
+
+/// Updates a registered server, or registers it if it isn't registered and `param` contains all settings.
+///
+/// The mod info is only read when registering.
+async fn update_or_register(
+    param: UpdateServerParam,
+    mod_info: impl Future<Output = Result<Option<ModInfo>, CreateServerError>>,
+    ip: IpAddr,
+    server_list: SharedServerList,
+    allow_missing_token: bool,
+) -> Result<Option<CreateServerResponse>, UpdateServerError> {
     let exists = {
         let servers = server_list.read().await;
         servers.servers.contains_key(&param.id)
@@ -231,52 +534,646 @@ pub(super) async fn update_server(
     // Create server entry if none exists
     if !exists {
         // The request must contain all the necessary data
-        if let Ok(settings) = param.try_into() {
-            return Box::new(api_response(
-                create_server_entry(settings, remote, server_list, form).await,
-            ));
-        } else {
-            return Box::new(warp::reply());
-        }
+        let settings = param
+            .try_into()
+            .map_err(|_| UpdateServerError::NotRegistered)?;
+        // Registering verifies the server, like add_server
+        rate_limit::check_ip(RouteGroup::ServerRegistration, ip)?;
+        let mod_info = mod_info.await?;
+        return Ok(Some(
+            register_server(settings, mod_info, ip, server_list).await?,
+        ));
     }
 
     let mut servers = server_list.write().await;
+    let server = servers
+        .servers
+        .get_mut(&param.id)
+        .ok_or(UpdateServerError::NotRegistered)?;
+    authorize(server, param.server_auth_token, ip, allow_missing_token)?;
+
+    let id = param.id;
+    server.last_seen = Instant::now();
+    param.apply(server);
+    servers.save(&id);
+
+    Ok(None)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct HeartbeatParam {
+    id: UniqueId,
+    server_auth_token: Option<UniqueId>,
+    player_count: Option<u32>,
+}
+
+/// Whether clients see a server, as reported to its heartbeats.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ListingStatus {
+    /// Shown in the server list
+    Listed,
+    /// Hadn't sent a heartbeat for too long so clients weren't shown it, it is listed again from now on
+    Hidden,
+    /// Not registered (anymore), the server has to register again with `add_server`
+    Unknown,
+}
+
+#[derive(Serialize)]
+pub(super) struct HeartbeatResponse {
+    status: ListingStatus,
+}
+
+/// Keeps a server listed without sending its settings again.
+pub(super) async fn heartbeat(
+    param: HeartbeatParam,
+    ip: IpAddr,
+    servers: SharedServerList,
+) -> Result<HeartbeatResponse, ServerAuthError> {
+    record_heartbeat(param, ip, servers, legacy_token_fallback()).await
+}
+
+/// Versioned `heartbeat` route, the parameters are sent as JSON and the server auth token as bearer token.
+pub(super) async fn heartbeat_v2(
+    param: HeartbeatParam,
+    token: Option<UniqueId>,
+    ip: IpAddr,
+    servers: SharedServerList,
+) -> Result<HeartbeatResponse, ServerAuthError> {
+    let param = HeartbeatParam {
+        server_auth_token: token,
+        ..param
+    };
+    record_heartbeat(param, ip, servers, false).await
+}
+
+async fn record_heartbeat(
+    param: HeartbeatParam,
+    ip: IpAddr,
+    servers: SharedServerList,
+    allow_missing_token: bool,
+) -> Result<HeartbeatResponse, ServerAuthError> {
+    let mut servers = servers.write().await;
     let server = match servers.servers.get_mut(&param.id) {
         Some(s) => s,
-        None => return Box::new(warp::reply()),
+        None => {
+            return Ok(HeartbeatResponse {
+                status: ListingStatus::Unknown,
+            })
+        }
     };
 
-    // Only allow update from server address
-    if server.ip != ip {
-        return Box::new(warp::reply());
-    }
+    authorize(server, param.server_auth_token, ip, allow_missing_token)?;
 
+    let status = if server.last_seen_age() < LISTED_TIMEOUT {
+        ListingStatus::Listed
+    } else {
+        ListingStatus::Hidden
+    };
     server.last_seen = Instant::now();
-    param.apply(server);
+    if let Some(player_count) = param.player_count {
+        server.player_count = Some(player_count);
+    }
+    servers.mark_seen(param.id);
 
-    Box::new(warp::reply())
+    Ok(HeartbeatResponse { status })
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub(super) struct RemoveServerParam {
     id: UniqueId,
+    server_auth_token: Option<UniqueId>,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum RemoveServerError {
+    #[error("server is not registered")]
+    NotRegistered,
+    #[error(transparent)]
+    Auth(#[from] ServerAuthError),
+}
+
+impl ApiErrorKind for RemoveServerError {
+    fn kind(&self) -> &'static str {
+        match self {
+            RemoveServerError::NotRegistered => "SERVER_NOT_REGISTERED",
+            RemoveServerError::Auth(e) => e.kind(),
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            RemoveServerError::NotRegistered => StatusCode::NOT_FOUND,
+            RemoveServerError::Auth(e) => e.status(),
+        }
+    }
 }
 
 pub(super) async fn remove_server(
     param: RemoveServerParam,
-    remote: Option<SocketAddr>,
+    ip: IpAddr,
+    servers: SharedServerList,
+) -> Result<(), RemoveServerError> {
+    remove_registered(param, ip, servers, legacy_token_fallback()).await
+}
+
+/// Versioned `remove_server` route, the id is sent as JSON and the server auth token as bearer token.
+pub(super) async fn remove_server_v2(
+    param: RemoveServerParam,
+    token: Option<UniqueId>,
+    ip: IpAddr,
     servers: SharedServerList,
-) -> impl warp::Reply {
-    let ip = match remote {
-        Some(addr) => addr.ip(),
-        None => return warp::reply(),
+) -> Result<(), RemoveServerError> {
+    let param = RemoveServerParam {
+        server_auth_token: token,
+        ..param
     };
+    remove_registered(param, ip, servers, false).await
+}
 
+async fn remove_registered(
+    param: RemoveServerParam,
+    ip: IpAddr,
+    servers: SharedServerList,
+    allow_missing_token: bool,
+) -> Result<(), RemoveServerError> {
     let mut servers = servers.write().await;
-    // Remove server if IP matches
-    if servers.get(&param.id).map(|s| s.ip == ip) == Some(true) {
-        servers.remove(&param.id);
+    let server = servers
+        .get(&param.id)
+        .ok_or(RemoveServerError::NotRegistered)?;
+    authorize(server, param.server_auth_token, ip, allow_missing_token)?;
+    servers.remove(&param.id);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::sync::RwLock;
+    use warp::Reply;
+
+    use super::*;
+    use crate::{
+        database::test_databases,
+        game_servers::{server_repository, Mod, ServerList, ServerRepository},
+    };
+
+    fn server(name: &str, map: &str, player_count: u32, password: bool, mods: &[&str]) -> Server {
+        let settings = ServerSettings {
+            port: 37015,
+            auth_port: 8081,
+            name: name.to_owned(),
+            description: String::new(),
+            map: map.to_owned(),
+            playlist: "aitdm".to_owned(),
+            max_players: 16,
+            password: password.then(|| "secret".to_owned()),
+        };
+        let mods = mods
+            .iter()
+            .map(|&name| Mod {
+                required_on_client: true,
+                name: name.to_owned(),
+                version: "1.0.0".to_owned(),
+            })
+            .collect();
+        let mut server = Server::new([10, 0, 0, 1].into(), settings, Some(ModInfo { mods }));
+        server.player_count = Some(player_count);
+        server
+    }
+
+    fn servers() -> Vec<Server> {
+        vec![
+            server("Alpha Attrition", "mp_glitch", 16, false, &["Example.Mod"]),
+            server("bravo Frontier", "mp_forwardbase_kodai", 4, true, &[]),
+            server(
+                "Charlie Attrition",
+                "mp_forwardbase_kodai",
+                8,
+                false,
+                &["Example.Mod", "Other.Mod"],
+            ),
+        ]
+    }
+
+    /// Parses the parameters of a route from a query string.
+    async fn param<T: serde::de::DeserializeOwned + Send + 'static>(query: &str) -> T {
+        warp::test::request()
+            .path(&format!("/client/servers?{}", query))
+            .filter(&warp::query())
+            .await
+            .unwrap()
     }
 
-    warp::reply()
+    /// Names of the servers `param` matches, `name` and `mods` are already parsed like in [`list_servers`].
+    fn matching<'a>(
+        servers: &'a [Server],
+        param: &ServerListParam,
+        name: Option<&str>,
+        mods: &[&str],
+    ) -> Vec<&'a str> {
+        servers
+            .iter()
+            .map(ServerListEntry::from)
+            .filter(|entry| param.matches(entry, name, mods))
+            .map(|entry| entry.name)
+            .collect()
+    }
+
+    /// A fresh in-memory database, which doesn't contain servers of other tests.
+    async fn repository() -> Arc<dyn ServerRepository> {
+        server_repository(test_databases().await.remove(0))
+    }
+
+    /// Lists `servers` without storing them.
+    async fn server_list(servers: Vec<Server>) -> SharedServerList {
+        let mut list = ServerList::load(repository().await).await.unwrap();
+        for server in servers {
+            list.insert(server);
+        }
+        Arc::new(RwLock::new(list))
+    }
+
+    async fn list(servers: &SharedServerList, query: &str) -> warp::reply::Response {
+        let cache = ServerListCache::new(servers).await;
+        list_servers(param(query).await, None, None, servers.clone(), cache)
+            .await
+            .into_response()
+    }
+
+    async fn names(response: warp::reply::Response) -> Vec<String> {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let list: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        list.iter()
+            .map(|entry| entry["name"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    fn total_count(response: &warp::reply::Response) -> Option<&str> {
+        response
+            .headers()
+            .get("x-total-count")
+            .map(|count| count.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn matches_each_filter() {
+        let servers = servers();
+        let all = ["Alpha Attrition", "bravo Frontier", "Charlie Attrition"];
+
+        let unfiltered: ServerListParam = param("").await;
+        assert!(unfiltered.is_unfiltered());
+        assert_eq!(matching(&servers, &unfiltered, None, &[]), all);
+
+        let by_map: ServerListParam = param("map=mp_forwardbase_kodai").await;
+        assert!(!by_map.is_unfiltered());
+        assert_eq!(
+            matching(&servers, &by_map, None, &[]),
+            ["bravo Frontier", "Charlie Attrition"]
+        );
+        assert!(matching(&servers, &param("playlist=ctf").await, None, &[]).is_empty());
+        assert_eq!(
+            matching(&servers, &param("playlist=aitdm").await, None, &[]),
+            all
+        );
+        assert_eq!(
+            matching(&servers, &param("hasPassword=true").await, None, &[]),
+            ["bravo Frontier"]
+        );
+        assert_eq!(
+            matching(&servers, &param("hasPassword=false").await, None, &[]),
+            ["Alpha Attrition", "Charlie Attrition"]
+        );
+        assert_eq!(
+            matching(&servers, &param("notFull=true").await, None, &[]),
+            ["bravo Frontier", "Charlie Attrition"]
+        );
+
+        // The name and mods are parsed by the caller, the name is compared in lower case
+        assert_eq!(
+            matching(&servers, &unfiltered, Some("attrition"), &[]),
+            ["Alpha Attrition", "Charlie Attrition"]
+        );
+        assert_eq!(
+            matching(&servers, &unfiltered, None, &["Example.Mod"]),
+            ["Alpha Attrition", "Charlie Attrition"]
+        );
+        assert_eq!(
+            matching(&servers, &unfiltered, None, &["Example.Mod", "Other.Mod"]),
+            ["Charlie Attrition"]
+        );
+
+        // Every filter has to match
+        assert_eq!(
+            matching(
+                &servers,
+                &param("map=mp_forwardbase_kodai&notFull=true&hasPassword=false").await,
+                Some("attrition"),
+                &["Other.Mod"],
+            ),
+            ["Charlie Attrition"]
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_by_name_and_mods() {
+        let servers = server_list(servers()).await;
+
+        let response = list(&servers, "name=ATTRITION&sort=name").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(total_count(&response), None);
+        assert_eq!(
+            names(response).await,
+            ["Alpha Attrition", "Charlie Attrition"]
+        );
+
+        // Spaces and empty names in the mod list are ignored
+        let response = list(&servers, "mods=Other.Mod,%20Example.Mod,").await;
+        assert_eq!(names(response).await, ["Charlie Attrition"]);
+    }
+
+    #[tokio::test]
+    async fn sorts_servers() {
+        let servers = server_list(servers()).await;
+
+        let response = list(&servers, "sort=players").await;
+        assert_eq!(
+            names(response).await,
+            ["Alpha Attrition", "Charlie Attrition", "bravo Frontier"]
+        );
+
+        // Names are sorted ignoring case
+        let response = list(&servers, "sort=name").await;
+        assert_eq!(
+            names(response).await,
+            ["Alpha Attrition", "bravo Frontier", "Charlie Attrition"]
+        );
+
+        // Equal player counts are sorted by name
+        let tied = server_list(vec![
+            server("B", "mp_glitch", 4, false, &[]),
+            server("A", "mp_glitch", 4, false, &[]),
+        ])
+        .await;
+        assert_eq!(names(list(&tied, "sort=players").await).await, ["A", "B"]);
+    }
+
+    #[tokio::test]
+    async fn paginates_servers() {
+        let servers = server_list(servers()).await;
+
+        let response = list(&servers, "sort=name&pageSize=2&page=0").await;
+        assert_eq!(total_count(&response), Some("3"));
+        assert_eq!(names(response).await, ["Alpha Attrition", "bravo Frontier"]);
+
+        let response = list(&servers, "sort=name&pageSize=2&page=1").await;
+        assert_eq!(total_count(&response), Some("3"));
+        assert_eq!(names(response).await, ["Charlie Attrition"]);
+
+        // Pages past the end are empty, but still report the total
+        let response = list(&servers, "pageSize=2&page=2").await;
+        assert_eq!(total_count(&response), Some("3"));
+        assert!(names(response).await.is_empty());
+
+        // The total only counts servers matching the filters
+        let response = list(&servers, "map=mp_forwardbase_kodai&page=0").await;
+        assert_eq!(total_count(&response), Some("2"));
+        assert_eq!(names(response).await.len(), 2);
+
+        // Pages without a sort order are sorted by id, so they don't overlap
+        let mut paged = Vec::new();
+        for page in 0..3 {
+            let response = list(&servers, &format!("pageSize=1&page={}", page)).await;
+            paged.extend(names(response).await);
+        }
+        paged.sort();
+        assert_eq!(
+            paged,
+            ["Alpha Attrition", "Charlie Attrition", "bravo Frontier"]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_page_sizes() {
+        let servers = server_list(servers()).await;
+
+        for query in ["pageSize=0", "pageSize=101", "page=0&pageSize=0"] {
+            let response = list(&servers, query).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        }
+
+        let response = list(&servers, "page=0&pageSize=100").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(names(response).await.len(), 3);
+    }
+
+    async fn heartbeat_status(
+        servers: &SharedServerList,
+        id: UniqueId,
+        token: UniqueId,
+        player_count: Option<u32>,
+    ) -> String {
+        let param = HeartbeatParam {
+            id,
+            server_auth_token: Some(token),
+            player_count,
+        };
+        let response = heartbeat(param, [10, 0, 0, 1].into(), servers.clone())
+            .await
+            .unwrap();
+        serde_json::to_value(response).unwrap()["status"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    #[tokio::test]
+    async fn heartbeat_keeps_server_listed() {
+        let server = server("Test", "mp_glitch", 0, false, &[]);
+        let (id, token) = (server.id, server.auth_token);
+        let servers = server_list(vec![server]).await;
+
+        assert_eq!(
+            heartbeat_status(&servers, id, token, Some(5)).await,
+            "listed"
+        );
+        let servers = servers.read().await;
+        assert_eq!(servers.get(&id).unwrap().player_count, Some(5));
+    }
+
+    #[tokio::test]
+    async fn heartbeat_lists_hidden_server_again() {
+        let mut server = server("Test", "mp_glitch", 0, false, &[]);
+        server.last_seen = Instant::now() - LISTED_TIMEOUT - Duration::from_secs(1);
+        let (id, token) = (server.id, server.auth_token);
+        let servers = server_list(vec![server]).await;
+        assert_eq!(servers.read().await.listed().count(), 0);
+
+        assert_eq!(heartbeat_status(&servers, id, token, None).await, "hidden");
+        assert_eq!(servers.read().await.listed().count(), 1);
+        assert_eq!(heartbeat_status(&servers, id, token, None).await, "listed");
+    }
+
+    #[tokio::test]
+    async fn heartbeat_reports_unknown_server() {
+        let servers = server_list(servers()).await;
+        let mut rng = rand::thread_rng();
+        let (id, token) = (UniqueId::new(&mut rng), UniqueId::new(&mut rng));
+
+        assert_eq!(
+            heartbeat_status(&servers, id, token, Some(1)).await,
+            "unknown"
+        );
+        assert_eq!(servers.read().await.servers.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn heartbeats_are_stored() {
+        let repository = repository().await;
+        let mut server = server("Test", "mp_glitch", 0, false, &[]);
+        server.last_seen = Instant::now() - Duration::from_secs(120);
+        let (id, token) = (server.id, server.auth_token);
+        let servers = Arc::new(RwLock::new(
+            ServerList::load(repository.clone()).await.unwrap(),
+        ));
+        if servers.write().await.push(server).is_err() {
+            panic!("server could not be added");
+        }
+
+        heartbeat_status(&servers, id, token, Some(7)).await;
+        servers.write().await.store_seen();
+
+        // Changes are written in the background
+        for _ in 0..50 {
+            let stored = repository.all().await.unwrap();
+            let stored = stored.iter().find(|s| s.id == id);
+            if let Some(stored) = stored.filter(|s| s.player_count == Some(7)) {
+                assert!(stored.last_seen_age() < Duration::from_secs(5));
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("heartbeat was not stored");
+    }
+
+    /// Kinds of the errors of the legacy `heartbeat`, `update_values` and `remove_server` routes, in that order.
+    async fn legacy_errors(
+        servers: &SharedServerList,
+        query: &str,
+        ip: IpAddr,
+    ) -> [Option<&'static str>; 3] {
+        let heartbeat = heartbeat(param(query).await, ip, servers.clone()).await;
+        let mod_info = async { Ok(None) };
+        let update = update_or_register(
+            param(&format!("{}&name=Renamed", query)).await,
+            mod_info,
+            ip,
+            servers.clone(),
+            legacy_token_fallback(),
+        )
+        .await;
+        let remove = remove_server(param(query).await, ip, servers.clone()).await;
+        [
+            heartbeat.err().map(|e| e.kind()),
+            update.err().map(|e| e.kind()),
+            remove.err().map(|e| e.kind()),
+        ]
+    }
+
+    #[tokio::test]
+    async fn legacy_routes_require_token() {
+        let server = server("Test", "mp_glitch", 0, false, &[]);
+        let (id, token) = (server.id, server.auth_token);
+        let servers = server_list(vec![server]).await;
+        let ip = [10, 0, 0, 1].into();
+        let wrong_token = UniqueId::new(&mut rand::thread_rng());
+
+        let invalid = Some("INVALID_SERVER_TOKEN");
+        assert_eq!(
+            legacy_errors(&servers, &format!("id={}", id), ip).await,
+            [invalid; 3]
+        );
+        assert_eq!(
+            legacy_errors(
+                &servers,
+                &format!("id={}&serverAuthToken={}", id, wrong_token),
+                ip
+            )
+            .await,
+            [invalid; 3]
+        );
+        assert_eq!(
+            legacy_errors(
+                &servers,
+                &format!("id={}&serverAuthToken={}", id, token),
+                [10, 0, 0, 2].into()
+            )
+            .await,
+            [Some("SERVER_ADDRESS_MISMATCH"); 3]
+        );
+
+        let servers = servers.read().await;
+        assert_eq!(servers.get(&id).unwrap().settings.name, "Test");
+    }
+
+    #[tokio::test]
+    async fn legacy_fallback_checks_address() {
+        let server = server("Test", "mp_glitch", 0, false, &[]);
+        let id = server.id;
+        let servers = server_list(vec![server]).await;
+        let ip: IpAddr = [10, 0, 0, 1].into();
+        let other_ip: IpAddr = [10, 0, 0, 2].into();
+        let query = format!("id={}", id);
+
+        let heartbeat = record_heartbeat(param(&query).await, other_ip, servers.clone(), true);
+        assert!(matches!(
+            heartbeat.await,
+            Err(ServerAuthError::AddressMismatch)
+        ));
+        let wrong_token = format!(
+            "{}&serverAuthToken={}",
+            query,
+            UniqueId::new(&mut rand::thread_rng())
+        );
+        let heartbeat = record_heartbeat(param(&wrong_token).await, ip, servers.clone(), true);
+        assert!(matches!(
+            heartbeat.await,
+            Err(ServerAuthError::InvalidToken)
+        ));
+        assert!(
+            record_heartbeat(param(&query).await, ip, servers.clone(), true)
+                .await
+                .is_ok()
+        );
+
+        let update = param(&format!("{}&name=Renamed", query)).await;
+        let mod_info = async { Ok(None) };
+        assert!(
+            update_or_register(update, mod_info, ip, servers.clone(), true)
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            servers.read().await.get(&id).unwrap().settings.name,
+            "Renamed"
+        );
+
+        // Versioned routes never fall back
+        let heartbeat = heartbeat_v2(param(&query).await, None, ip, servers.clone());
+        assert!(matches!(
+            heartbeat.await,
+            Err(ServerAuthError::InvalidToken)
+        ));
+
+        assert!(
+            remove_registered(param(&query).await, ip, servers.clone(), true)
+                .await
+                .is_ok()
+        );
+        assert!(servers.read().await.get(&id).is_none());
+    }
 }
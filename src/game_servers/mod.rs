@@ -4,23 +4,36 @@ use serde_derive::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     net::{IpAddr, SocketAddr},
+    sync::Arc,
     time::{Duration, Instant},
 };
+use subtle::ConstantTimeEq;
+use tokio::sync::mpsc;
 use warp::{multipart::FormData, Filter};
 
 use crate::SharedServerList;
 
 use crate::id::UniqueId;
-pub use routes::{routes, with_servers};
+pub use handlers::legacy_token_fallback;
+pub use repository::{server_repository, ServerRepository};
+use repository::{spawn_writer, SeenServer, StoredChange};
+pub use routes::{routes, v2_routes, with_servers};
+pub use snapshot::{maintain_server_list, ServerListCache};
 
+pub mod client;
 mod handlers;
+mod repository;
 mod routes;
+mod snapshot;
 mod verify;
 
 /// A server registered with the master server.
+#[derive(Clone)]
 pub struct Server {
     id: UniqueId,
     ip: IpAddr,
+    /// Stored in plain text unlike session tokens: the master server sends it to the game server
+    /// with every joining player, so it has to be known again after a restart
     auth_token: UniqueId,
     settings: ServerSettings,
     last_seen: Instant,
@@ -69,11 +82,27 @@ impl Server {
         self.auth_token
     }
 
+    /// Whether `token` is the auth token the server received from `add_server`.
+    ///
+    /// Compared in constant time, so response times don't reveal how much of a guessed token is right.
+    #[must_use]
+    pub fn has_auth_token(&self, token: Option<UniqueId>) -> bool {
+        token.is_some_and(|token| bool::from(token.bytes().ct_eq(self.auth_token.bytes())))
+    }
+
     #[must_use]
     pub fn auth_address(&self) -> SocketAddr {
         SocketAddr::new(self.ip, self.settings.auth_port)
     }
 
+    /// Names of the mods running on the server, in load order.
+    pub fn mod_names(&self) -> impl Iterator<Item = &str> {
+        self.mod_info
+            .iter()
+            .flat_map(|info| info.mods.iter())
+            .map(|m| m.name.as_str())
+    }
+
     #[must_use]
     fn last_seen_age(&self) -> Duration {
         Instant::now().duration_since(self.last_seen)
@@ -88,16 +117,51 @@ enum AddServerError {
     ConflictingAuthPort,
 }
 
+/// Time after which a server that hasn't sent an update is removed.
+const INACTIVE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time after which a server that hasn't sent an update is no longer shown to clients.
+const LISTED_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Stores all listed servers.
-#[derive(Default)]
+///
+/// Changes are also written to the database, so servers stay listed when the master server restarts.
+/// They are written in the background, after the lock on the list is released.
+/// Heartbeats are only stored periodically by [`ServerList::store_seen`], as they are sent often.
 pub struct ServerList {
     servers: HashMap<UniqueId, Server>,
     addresses: HashMap<IpAddr, HashSet<UniqueId>>,
+    changes: mpsc::UnboundedSender<StoredChange>,
+    /// Servers that sent a heartbeat since heartbeats were last stored
+    seen: HashSet<UniqueId>,
 }
 
 impl ServerList {
+    /// Loads the servers stored in the database.
+    pub async fn load(repository: Arc<dyn ServerRepository>) -> Result<Self, sqlx::Error> {
+        let stored = repository.all().await?;
+        let mut list = Self {
+            servers: HashMap::new(),
+            addresses: HashMap::new(),
+            changes: spawn_writer(repository),
+            seen: HashSet::new(),
+        };
+        for server in stored {
+            list.insert(server);
+        }
+        list.remove_inactive();
+
+        tracing::info!(count = list.servers.len(), "loaded stored servers");
+        Ok(list)
+    }
+
     fn iter(&self) -> impl std::iter::Iterator<Item = &Server> {
-        self.servers.iter().map(|(_, v)| v)
+        self.servers.values()
+    }
+
+    /// Servers shown to clients, those we have seen in the last minute.
+    fn listed(&self) -> impl std::iter::Iterator<Item = &Server> {
+        self.iter().filter(|s| s.last_seen_age() < LISTED_TIMEOUT)
     }
 
     fn push(&mut self, server: Server) -> Result<&Server, AddServerError> {
@@ -133,6 +197,11 @@ impl ServerList {
             return Err(AddServerError::ConflictingAuthPort);
         }
 
+        self.store(StoredChange::Save(Box::new(server.clone())));
+        Ok(self.insert(server))
+    }
+
+    fn insert(&mut self, server: Server) -> &Server {
         match self.servers.entry(server.id) {
             std::collections::hash_map::Entry::Occupied(_) => {
                 panic!("Conflicting server unique id, this should never happen.")
@@ -144,7 +213,7 @@ impl ServerList {
                     .or_default()
                     .insert(server.id);
                 //  Store server
-                Ok(v.insert(server))
+                v.insert(server)
             }
         }
     }
@@ -153,7 +222,33 @@ impl ServerList {
         self.servers.get(k)
     }
 
+    /// Writes the current state of a server to the database, call after modifying it.
+    fn save(&self, k: &UniqueId) {
+        if let Some(server) = self.servers.get(k) {
+            self.store(StoredChange::Save(Box::new(server.clone())));
+        }
+    }
+
+    /// Marks a server as seen by a heartbeat, to be stored with the next [`ServerList::store_seen`].
+    fn mark_seen(&mut self, k: UniqueId) {
+        self.seen.insert(k);
+    }
+
+    /// Stores when the servers marked by heartbeats were last seen, and their player count.
+    pub fn store_seen(&mut self) {
+        let seen: Vec<SeenServer> = self
+            .seen
+            .drain()
+            .filter_map(|id| self.servers.get(&id))
+            .map(SeenServer::from)
+            .collect();
+        if !seen.is_empty() {
+            self.store(StoredChange::Seen(seen));
+        }
+    }
+
     pub fn remove(&mut self, k: &UniqueId) {
+        self.seen.remove(k);
         if let Some(server) = self.servers.remove(k) {
             let ip = &server.ip;
             let host_servers = self.addresses.get_mut(ip).unwrap();
@@ -161,6 +256,15 @@ impl ServerList {
             if host_servers.is_empty() {
                 self.addresses.remove(ip);
             }
+
+            self.store(StoredChange::Delete(*k));
+        }
+    }
+
+    /// Queues a change to be written to the database.
+    fn store(&self, change: StoredChange) {
+        if self.changes.send(change).is_err() {
+            tracing::error!("Server list changes can no longer be stored");
         }
     }
 
@@ -170,7 +274,7 @@ impl ServerList {
         let inactive: Vec<_> = self
             .servers
             .iter()
-            .filter(|(_, s)| s.last_seen_age() > Duration::from_secs(5 * 60))
+            .filter(|(_, s)| s.last_seen_age() > INACTIVE_TIMEOUT)
             .map(|(_, s)| s.id)
             .collect();
         for id in inactive.into_iter() {
@@ -181,7 +285,7 @@ impl ServerList {
 
 const MAX_PLAYERS_LIMIT: u32 = 32;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 struct ServerSettings {
     port: u16,
@@ -191,7 +295,7 @@ struct ServerSettings {
     map: String,
     playlist: String,
     max_players: u32,
-    #[serde(with = "serde_with::rust::string_empty_as_none")]
+    #[serde(default, with = "serde_with::rust::string_empty_as_none")]
     password: Option<String>,
 }
 
@@ -238,3 +342,22 @@ impl ModInfo {
         .map_err(|_| ())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_auth_token() {
+        let settings = serde_json::from_str(
+            r#"{"port": 37015, "authPort": 8081, "name": "Test", "description": "",
+            "map": "mp_forwardbase_kodai", "playlist": "aitdm", "maxPlayers": 16}"#,
+        )
+        .unwrap();
+        let server = Server::new([10, 0, 0, 1].into(), settings, None);
+
+        assert!(server.has_auth_token(Some(server.auth_token)));
+        assert!(!server.has_auth_token(Some(UniqueId::new(rand::thread_rng()))));
+        assert!(!server.has_auth_token(None));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::mpsc;
+
+use crate::{id::UniqueId, Database};
+
+use super::{ModInfo, Server, ServerSettings};
+
+mod postgres;
+mod sqlite;
+
+/// Stores listed servers, so they survive a restart of the master server.
+///
+/// Implemented for every supported database.
+#[async_trait::async_trait]
+pub trait ServerRepository: Send + Sync {
+    /// Returns every stored server, rows that can't be read are logged and deleted.
+    async fn all(&self) -> Result<Vec<Server>, sqlx::Error>;
+
+    /// Inserts a new server or updates an existing one.
+    async fn save(&self, server: &Server) -> Result<(), sqlx::Error>;
+
+    /// Updates only the values refreshed by heartbeats, servers that aren't stored are ignored.
+    async fn save_seen(&self, servers: &[SeenServer]) -> Result<(), sqlx::Error>;
+
+    async fn delete(&self, id: &UniqueId) -> Result<(), sqlx::Error>;
+}
+
+/// Creates the server repository for the configured database.
+pub fn server_repository(database: Database) -> Arc<dyn ServerRepository> {
+    match database {
+        Database::Sqlite(pool) => Arc::new(sqlite::SqliteServerRepository::new(pool)),
+        Database::Postgres(pool) => Arc::new(postgres::PostgresServerRepository::new(pool)),
+    }
+}
+
+/// A stored server, as read from either database.
+#[derive(sqlx::FromRow)]
+struct ServerRow {
+    id: Vec<u8>,
+    ip: String,
+    auth_token: Vec<u8>,
+    port: i32,
+    auth_port: i32,
+    name: String,
+    description: String,
+    map: String,
+    playlist: String,
+    max_players: i32,
+    password: Option<String>,
+    player_count: Option<i32>,
+    mod_info: Option<String>,
+    last_seen: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<ServerRow> for Server {
+    type Error = &'static str;
+
+    fn try_from(row: ServerRow) -> Result<Self, Self::Error> {
+        // Convert the stored time back to an instant, as if the master server never stopped
+        let age = chrono::Utc::now()
+            .signed_duration_since(row.last_seen)
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+        let last_seen = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+
+        Ok(Server {
+            id: UniqueId::existing(row.id.try_into().map_err(|_| "invalid id")?),
+            ip: row.ip.parse().map_err(|_| "invalid ip address")?,
+            auth_token: UniqueId::existing(
+                row.auth_token
+                    .try_into()
+                    .map_err(|_| "invalid auth token")?,
+            ),
+            settings: ServerSettings {
+                port: row.port.try_into().map_err(|_| "invalid port")?,
+                auth_port: row.auth_port.try_into().map_err(|_| "invalid auth port")?,
+                name: row.name,
+                description: row.description,
+                map: row.map,
+                playlist: row.playlist,
+                max_players: row
+                    .max_players
+                    .try_into()
+                    .map_err(|_| "invalid max players")?,
+                password: row.password,
+            },
+            last_seen,
+            player_count: row.player_count.and_then(|c| c.try_into().ok()),
+            mod_info: row
+                .mod_info
+                .and_then(|info| serde_json::from_str::<ModInfo>(&info).ok()),
+        })
+    }
+}
+
+/// Converts stored rows to servers, skipping the rows that can't be read so the other servers still load.
+///
+/// Returns the ids of the skipped rows too, they are deleted as they would otherwise be skipped on every start.
+/// The servers re-register once they notice the master server doesn't know them.
+fn read_rows(rows: Vec<ServerRow>) -> (Vec<Server>, Vec<Vec<u8>>) {
+    let mut servers = Vec::with_capacity(rows.len());
+    let mut unreadable = Vec::new();
+    for row in rows {
+        let id = row.id.clone();
+        match Server::try_from(row) {
+            Ok(server) => servers.push(server),
+            Err(reason) => {
+                tracing::warn!(
+                    id = hex::encode(&id).as_str(),
+                    reason,
+                    "removing unreadable stored server"
+                );
+                unreadable.push(id);
+            }
+        }
+    }
+    (servers, unreadable)
+}
+
+/// The time a server was last seen, as stored in the database.
+fn last_seen_time(server: &Server) -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now()
+        - chrono::Duration::from_std(server.last_seen_age())
+            .unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+fn serialize_mod_info(server: &Server) -> Option<String> {
+    server
+        .mod_info
+        .as_ref()
+        .map(|info| serde_json::to_string(info).expect("Unable to serialize mod info"))
+}
+
+/// The values of a server that heartbeats refresh.
+pub struct SeenServer {
+    id: UniqueId,
+    last_seen: chrono::DateTime<chrono::Utc>,
+    player_count: Option<u32>,
+}
+
+impl From<&Server> for SeenServer {
+    fn from(server: &Server) -> Self {
+        Self {
+            id: server.id,
+            last_seen: last_seen_time(server),
+            player_count: server.player_count,
+        }
+    }
+}
+
+/// A change of the server list that still has to be written to the database.
+pub(super) enum StoredChange {
+    Save(Box<Server>),
+    Seen(Vec<SeenServer>),
+    Delete(UniqueId),
+}
+
+/// Writes changes of the server list to the database in the background, in the order they were made.
+///
+/// This keeps database requests out of the lock on the server list.
+pub(super) fn spawn_writer(
+    repository: Arc<dyn ServerRepository>,
+) -> mpsc::UnboundedSender<StoredChange> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(change) = receiver.recv().await {
+            match change {
+                StoredChange::Save(server) => {
+                    if let Err(err) = repository.save(&server).await {
+                        tracing::error!(%err, "Failed storing server");
+                    }
+                }
+                StoredChange::Seen(servers) => {
+                    if let Err(err) = repository.save_seen(&servers).await {
+                        tracing::error!(%err, "Failed storing server heartbeats");
+                    }
+                }
+                StoredChange::Delete(id) => {
+                    if let Err(err) = repository.delete(&id).await {
+                        tracing::error!(%err, "Failed removing stored server");
+                    }
+                }
+            }
+        }
+    });
+    sender
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_databases;
+
+    fn server() -> Server {
+        let settings = serde_json::from_str(
+            r#"{"port": 37015, "authPort": 8081, "name": "Test", "description": "A test server",
+            "map": "mp_forwardbase_kodai", "playlist": "aitdm", "maxPlayers": 16, "password": "secret"}"#,
+        )
+        .unwrap();
+        let mod_info = serde_json::from_str(
+            r#"{"Mods": [{"RequiredOnClient": true, "Name": "Example.Mod", "Version": "1.0.0"}]}"#,
+        )
+        .unwrap();
+        Server::new([10, 0, 0, 1].into(), settings, Some(mod_info))
+    }
+
+    async fn find(servers: &dyn ServerRepository, id: &UniqueId) -> Option<Server> {
+        servers
+            .all()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|s| s.id == *id)
+    }
+
+    #[tokio::test]
+    async fn save_and_load() {
+        for database in test_databases().await {
+            let servers = server_repository(database);
+            let server = server();
+            servers.save(&server).await.unwrap();
+
+            let stored = find(servers.as_ref(), &server.id).await.unwrap();
+            assert_eq!(stored.ip, server.ip);
+            assert_eq!(stored.auth_token, server.auth_token);
+            assert_eq!(stored.settings.auth_port, 8081);
+            assert_eq!(stored.settings.password.as_deref(), Some("secret"));
+            assert_eq!(stored.mod_names().collect::<Vec<_>>(), ["Example.Mod"]);
+            assert!(stored.last_seen_age() < Duration::from_secs(5));
+        }
+    }
+
+    #[tokio::test]
+    async fn save_updates() {
+        for database in test_databases().await {
+            let servers = server_repository(database);
+            let mut server = server();
+            servers.save(&server).await.unwrap();
+
+            server.settings.name = "Renamed".to_owned();
+            server.player_count = Some(5);
+            servers.save(&server).await.unwrap();
+
+            let stored = find(servers.as_ref(), &server.id).await.unwrap();
+            assert_eq!(stored.settings.name, "Renamed");
+            assert_eq!(stored.player_count, Some(5));
+        }
+    }
+
+    #[tokio::test]
+    async fn save_seen() {
+        for database in test_databases().await {
+            let servers = server_repository(database);
+            let mut server = server();
+            server.last_seen = Instant::now() - Duration::from_secs(120);
+            servers.save(&server).await.unwrap();
+
+            server.last_seen = Instant::now();
+            server.player_count = Some(3);
+            server.settings.name = "Not stored".to_owned();
+            let unknown = self::server();
+            servers
+                .save_seen(&[SeenServer::from(&server), SeenServer::from(&unknown)])
+                .await
+                .unwrap();
+
+            let stored = find(servers.as_ref(), &server.id).await.unwrap();
+            assert!(stored.last_seen_age() < Duration::from_secs(5));
+            assert_eq!(stored.player_count, Some(3));
+            assert_eq!(stored.settings.name, "Test");
+            assert!(find(servers.as_ref(), &unknown.id).await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn delete() {
+        for database in test_databases().await {
+            let servers = server_repository(database);
+            let server = server();
+            servers.save(&server).await.unwrap();
+
+            servers.delete(&server.id).await.unwrap();
+            assert!(find(servers.as_ref(), &server.id).await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn all_deletes_corrupt_rows() {
+        for database in test_databases().await {
+            let servers = server_repository(database.clone());
+            let server = server();
+            servers.save(&server).await.unwrap();
+
+            let query = "UPDATE servers SET ip = 'not an address' WHERE id = $1";
+            let id = &server.id.bytes()[..];
+            match &database {
+                Database::Sqlite(pool) => {
+                    sqlx::query(query).bind(id).execute(pool).await.unwrap();
+                }
+                Database::Postgres(pool) => {
+                    sqlx::query(query).bind(id).execute(pool).await.unwrap();
+                }
+            }
+
+            assert!(find(servers.as_ref(), &server.id).await.is_none());
+
+            // The row was deleted, not only skipped
+            let query = "SELECT COUNT(*) FROM servers WHERE id = $1";
+            let (count,): (i64,) = match &database {
+                Database::Sqlite(pool) => sqlx::query_as(query).bind(id).fetch_one(pool).await,
+                Database::Postgres(pool) => sqlx::query_as(query).bind(id).fetch_one(pool).await,
+            }
+            .unwrap();
+            assert_eq!(count, 0);
+        }
+    }
+}
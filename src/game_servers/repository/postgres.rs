@@ -0,0 +1,90 @@
+use sqlx::PgPool;
+
+use crate::id::UniqueId;
+
+use super::{
+    last_seen_time, read_rows, serialize_mod_info, SeenServer, Server, ServerRepository, ServerRow,
+};
+
+pub struct PostgresServerRepository {
+    database: PgPool,
+}
+
+impl PostgresServerRepository {
+    pub fn new(database: PgPool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl ServerRepository for PostgresServerRepository {
+    async fn all(&self) -> Result<Vec<Server>, sqlx::Error> {
+        let rows: Vec<ServerRow> = sqlx::query_as(
+            r#"SELECT id, ip, auth_token, port, auth_port, name, description, map, playlist,
+            max_players, password, player_count, mod_info, last_seen FROM servers"#,
+        )
+        .fetch_all(&self.database)
+        .await?;
+
+        let (servers, unreadable) = read_rows(rows);
+        for id in unreadable {
+            sqlx::query(r#"DELETE FROM servers WHERE id = $1"#)
+                .bind(id)
+                .execute(&self.database)
+                .await?;
+        }
+        Ok(servers)
+    }
+
+    async fn save(&self, server: &Server) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"INSERT INTO servers (id, ip, auth_token, port, auth_port, name, description,
+            map, playlist, max_players, password, player_count, mod_info, last_seen)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            ON CONFLICT (id) DO UPDATE SET ip = excluded.ip, auth_token = excluded.auth_token,
+            port = excluded.port, auth_port = excluded.auth_port, name = excluded.name,
+            description = excluded.description, map = excluded.map, playlist = excluded.playlist,
+            max_players = excluded.max_players, password = excluded.password,
+            player_count = excluded.player_count, mod_info = excluded.mod_info,
+            last_seen = excluded.last_seen"#,
+        )
+        .bind(&server.id.bytes()[..])
+        .bind(server.ip.to_string())
+        .bind(&server.auth_token.bytes()[..])
+        .bind(i32::from(server.settings.port))
+        .bind(i32::from(server.settings.auth_port))
+        .bind(&server.settings.name)
+        .bind(&server.settings.description)
+        .bind(&server.settings.map)
+        .bind(&server.settings.playlist)
+        .bind(server.settings.max_players as i32)
+        .bind(&server.settings.password)
+        .bind(server.player_count.map(|c| c as i32))
+        .bind(serialize_mod_info(server))
+        .bind(last_seen_time(server))
+        .execute(&self.database)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_seen(&self, servers: &[SeenServer]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+        for server in servers {
+            sqlx::query(r#"UPDATE servers SET last_seen = $1, player_count = $2 WHERE id = $3"#)
+                .bind(server.last_seen)
+                .bind(server.player_count.map(|c| c as i32))
+                .bind(&server.id.bytes()[..])
+                .execute(&mut transaction)
+                .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn delete(&self, id: &UniqueId) -> Result<(), sqlx::Error> {
+        sqlx::query(r#"DELETE FROM servers WHERE id = $1"#)
+            .bind(&id.bytes()[..])
+            .execute(&self.database)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use sqlx::SqlitePool;
+
+use crate::id::UniqueId;
+
+use super::{
+    last_seen_time, read_rows, serialize_mod_info, SeenServer, Server, ServerRepository, ServerRow,
+};
+
+pub struct SqliteServerRepository {
+    database: SqlitePool,
+}
+
+impl SqliteServerRepository {
+    pub fn new(database: SqlitePool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl ServerRepository for SqliteServerRepository {
+    async fn all(&self) -> Result<Vec<Server>, sqlx::Error> {
+        let rows = sqlx::query_as!(
+            ServerRow,
+            r#"SELECT id, ip, auth_token, port as "port: i32", auth_port as "auth_port: i32", name,
+            description, map, playlist, max_players as "max_players: i32", password,
+            player_count as "player_count: i32", mod_info,
+            last_seen as "last_seen: chrono::DateTime<chrono::Utc>" FROM servers"#
+        )
+        .fetch_all(&self.database)
+        .await?;
+
+        let (servers, unreadable) = read_rows(rows);
+        for id in unreadable {
+            sqlx::query!(r#"DELETE FROM servers WHERE id = ?"#, id)
+                .execute(&self.database)
+                .await?;
+        }
+        Ok(servers)
+    }
+
+    async fn save(&self, server: &Server) -> Result<(), sqlx::Error> {
+        let id = &server.id.bytes()[..];
+        let ip = server.ip.to_string();
+        let auth_token = &server.auth_token.bytes()[..];
+        let mod_info = serialize_mod_info(server);
+        let last_seen = last_seen_time(server);
+
+        sqlx::query!(
+            r#"INSERT OR REPLACE INTO servers (id, ip, auth_token, port, auth_port, name, description,
+            map, playlist, max_players, password, player_count, mod_info, last_seen)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            id,
+            ip,
+            auth_token,
+            server.settings.port,
+            server.settings.auth_port,
+            server.settings.name,
+            server.settings.description,
+            server.settings.map,
+            server.settings.playlist,
+            server.settings.max_players,
+            server.settings.password,
+            server.player_count,
+            mod_info,
+            last_seen
+        )
+        .execute(&self.database)
+        .await?;
+        Ok(())
+    }
+
+    async fn save_seen(&self, servers: &[SeenServer]) -> Result<(), sqlx::Error> {
+        let mut transaction = self.database.begin().await?;
+        for server in servers {
+            let id = &server.id.bytes()[..];
+            sqlx::query!(
+                r#"UPDATE servers SET last_seen = ?, player_count = ? WHERE id = ?"#,
+                server.last_seen,
+                server.player_count,
+                id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        transaction.commit().await
+    }
+
+    async fn delete(&self, id: &UniqueId) -> Result<(), sqlx::Error> {
+        let id = &id.bytes()[..];
+        sqlx::query!(r#"DELETE FROM servers WHERE id = ?"#, id)
+            .execute(&self.database)
+            .await?;
+        Ok(())
+    }
+}
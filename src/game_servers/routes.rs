@@ -1,15 +1,34 @@
-use crate::api::api_response;
+use crate::{
+    api::{api_response, bearer_token, json_body},
+    proxy::client_ip,
+    rate_limit::{self, RouteGroup},
+};
 
 use super::*;
 
 pub fn routes(
     servers: SharedServerList,
+    cache: ServerListCache,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let base = warp::path("server");
     base.and(routes::create_server_entry(servers.clone()))
         .or(base.and(routes::remove_server(servers.clone())))
         .or(base.and(routes::update_server(servers.clone())))
-        .or(routes::list_servers(servers))
+        .or(base.and(routes::heartbeat(servers.clone())))
+        .or(routes::list_servers(servers, cache))
+}
+
+/// Routes of the versioned api, game servers send JSON bodies and their auth token as bearer token.
+pub fn v2_routes(
+    servers: SharedServerList,
+    cache: ServerListCache,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let base = warp::path("server");
+    base.and(routes::create_server_entry_v2(servers.clone()))
+        .or(base.and(routes::remove_server_v2(servers.clone())))
+        .or(base.and(routes::update_server_v2(servers.clone())))
+        .or(base.and(routes::heartbeat_v2(servers.clone())))
+        .or(routes::list_servers(servers, cache))
 }
 
 pub(super) fn create_server_entry(
@@ -17,32 +36,91 @@ pub(super) fn create_server_entry(
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path("add_server")
         .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ServerRegistration))
         .and(warp::query::<ServerSettings>())
-        .and(warp::addr::remote())
+        .and(client_ip())
         .and(with_servers(servers))
         .and(warp::multipart::form())
         .then(super::handlers::create_server_entry)
         .map(api_response)
 }
 
+pub(super) fn create_server_entry_v2(
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("add_server")
+        .and(warp::post())
+        .and(rate_limit::by_ip(RouteGroup::ServerRegistration))
+        .and(json_body::<handlers::CreateServerBody>())
+        .and(client_ip())
+        .and(with_servers(servers))
+        .then(super::handlers::create_server_entry_v2)
+        .map(api_response)
+}
+
 pub(super) fn update_server(
     servers: SharedServerList,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path("update_values")
         .and(warp::post())
         .and(warp::query::<handlers::UpdateServerParam>())
-        .and(warp::addr::remote())
+        .and(client_ip())
         .and(with_servers(servers))
         .and(warp::multipart::form())
         .then(super::handlers::update_server)
+        .map(api_response)
+}
+
+pub(super) fn update_server_v2(
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("update_values")
+        .and(warp::post())
+        .and(json_body::<handlers::UpdateServerBody>())
+        .and(bearer_token())
+        .and(client_ip())
+        .and(with_servers(servers))
+        .then(super::handlers::update_server_v2)
+        .map(api_response)
+}
+
+pub(super) fn heartbeat(
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("heartbeat")
+        .and(warp::post())
+        .and(warp::query::<handlers::HeartbeatParam>())
+        .and(client_ip())
+        .and(with_servers(servers))
+        .then(super::handlers::heartbeat)
+        .map(api_response)
+}
+
+pub(super) fn heartbeat_v2(
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("heartbeat")
+        .and(warp::post())
+        .and(json_body::<handlers::HeartbeatParam>())
+        .and(bearer_token())
+        .and(client_ip())
+        .and(with_servers(servers))
+        .then(super::handlers::heartbeat_v2)
+        .map(api_response)
 }
 
 pub(super) fn list_servers(
     servers: SharedServerList,
+    cache: ServerListCache,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("client" / "servers")
         .and(warp::get())
+        .and(rate_limit::by_ip(RouteGroup::ServerList))
+        .and(warp::query::<handlers::ServerListParam>())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(warp::header::optional::<String>("accept-encoding"))
         .and(with_servers(servers))
+        .and(warp::any().map(move || cache.clone()))
         .then(super::handlers::list_servers)
 }
 
@@ -52,9 +130,23 @@ pub(super) fn remove_server(
     warp::path!("remove_server")
         .and(warp::delete())
         .and(warp::query::<handlers::RemoveServerParam>())
-        .and(warp::addr::remote())
+        .and(client_ip())
         .and(with_servers(servers))
         .then(super::handlers::remove_server)
+        .map(api_response)
+}
+
+pub(super) fn remove_server_v2(
+    servers: SharedServerList,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("remove_server")
+        .and(warp::delete())
+        .and(json_body::<handlers::RemoveServerParam>())
+        .and(bearer_token())
+        .and(client_ip())
+        .and(with_servers(servers))
+        .then(super::handlers::remove_server_v2)
+        .map(api_response)
 }
 
 pub fn with_servers(
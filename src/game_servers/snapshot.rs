@@ -0,0 +1,195 @@
+use std::{
+    io::Write,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use warp::http::{header, Response, StatusCode};
+
+use crate::SharedServerList;
+
+use super::{handlers::ServerListEntry, ServerList};
+
+/// Time between rebuilds of the server list snapshot, clients may see changes this much later.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Time between removals of inactive servers and storing heartbeats, which need exclusive access to the server list.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The full server list, serialized ahead of time.
+pub struct ServerListSnapshot {
+    json: Bytes,
+    gzip: Bytes,
+    etag: String,
+    /// The compressed body is a different representation, so it needs its own tag
+    gzip_etag: String,
+}
+
+impl ServerListSnapshot {
+    /// Serializes the listed servers, the only part that needs access to the server list.
+    fn serialize(servers: &ServerList) -> Vec<u8> {
+        let mut list: Vec<ServerListEntry> = servers.listed().map(ServerListEntry::from).collect();
+        // Keep the order stable, so the same servers produce the same ETag
+        list.sort_by_key(|entry| *entry.id.bytes());
+
+        serde_json::to_vec(&list).expect("Unable to serialize server list")
+    }
+
+    fn new(json: Vec<u8>) -> Self {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&json)
+            .expect("Unable to compress server list");
+        let gzip = encoder.finish().expect("Unable to compress server list");
+
+        let digest = hex::encode(&Sha256::digest(&json)[..16]);
+
+        Self {
+            json: json.into(),
+            gzip: gzip.into(),
+            etag: format!("\"{}\"", digest),
+            gzip_etag: format!("\"{}-gz\"", digest),
+        }
+    }
+
+    /// Responds with the snapshot, compressed when the client accepts gzip.
+    pub(super) fn reply(
+        &self,
+        if_none_match: Option<&str>,
+        accept_encoding: Option<&str>,
+    ) -> Response<hyper::Body> {
+        let gzip = accepts_gzip(accept_encoding);
+        let etag = if gzip { &self.gzip_etag } else { &self.etag };
+        let response = Response::builder()
+            .header(header::ETAG, etag)
+            .header(header::VARY, "accept-encoding");
+
+        if matches(if_none_match, etag) {
+            return response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(hyper::Body::empty())
+                .unwrap();
+        }
+
+        let response = response.header(header::CONTENT_TYPE, "application/json");
+        if gzip {
+            response
+                .header(header::CONTENT_ENCODING, "gzip")
+                .body(self.gzip.clone().into())
+                .unwrap()
+        } else {
+            response.body(self.json.clone().into()).unwrap()
+        }
+    }
+}
+
+/// Whether a client already has the version with `etag`, from its `If-None-Match` header.
+fn matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    if_none_match.is_some_and(|tags| {
+        tags.split(',').map(str::trim).any(|tag| {
+            // Proxies that compress responses turn the tag into a weak one
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        })
+    })
+}
+
+/// Whether an `Accept-Encoding` header allows gzip, ignoring preferences between encodings.
+fn accepts_gzip(accept_encoding: Option<&str>) -> bool {
+    accept_encoding.is_some_and(|encodings| {
+        encodings.split(',').any(|encoding| {
+            let mut params = encoding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default();
+            let refused = params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+    })
+}
+
+/// Holds the latest server list snapshot, reading it only briefly locks the snapshot itself.
+#[derive(Clone)]
+pub struct ServerListCache(Arc<RwLock<Arc<ServerListSnapshot>>>);
+
+impl ServerListCache {
+    pub async fn new(servers: &SharedServerList) -> Self {
+        let json = ServerListSnapshot::serialize(&*servers.read().await);
+        Self(Arc::new(RwLock::new(Arc::new(ServerListSnapshot::new(
+            json,
+        )))))
+    }
+
+    pub(super) fn current(&self) -> Arc<ServerListSnapshot> {
+        self.0.read().unwrap().clone()
+    }
+
+    fn replace(&self, snapshot: ServerListSnapshot) {
+        *self.0.write().unwrap() = Arc::new(snapshot);
+    }
+}
+
+/// Removes inactive servers and keeps the server list snapshot up to date, runs until the master server stops.
+pub async fn maintain_server_list(servers: SharedServerList, cache: ServerListCache) {
+    let mut interval = tokio::time::interval(SNAPSHOT_INTERVAL);
+    let mut last_pruned = Instant::now();
+    loop {
+        interval.tick().await;
+
+        if last_pruned.elapsed() >= PRUNE_INTERVAL {
+            let mut servers = servers.write().await;
+            servers.remove_inactive();
+            servers.store_seen();
+            last_pruned = Instant::now();
+        }
+
+        let json = ServerListSnapshot::serialize(&*servers.read().await);
+        cache.replace(ServerListSnapshot::new(json));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn etag(response: &Response<hyper::Body>) -> &str {
+        response.headers()[header::ETAG].to_str().unwrap()
+    }
+
+    #[test]
+    fn gzip_has_own_etag() {
+        let snapshot = ServerListSnapshot::new(b"[]".to_vec());
+        let json = snapshot.reply(None, None);
+        let gzip = snapshot.reply(None, Some("gzip, deflate"));
+
+        assert_eq!(json.status(), StatusCode::OK);
+        assert_eq!(gzip.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_ne!(etag(&json), etag(&gzip));
+    }
+
+    #[test]
+    fn not_modified_only_for_same_representation() {
+        let snapshot = ServerListSnapshot::new(b"[]".to_vec());
+        let json_etag = etag(&snapshot.reply(None, None)).to_owned();
+        let gzip_etag = etag(&snapshot.reply(None, Some("gzip"))).to_owned();
+
+        let status = |if_none_match: &str, accept_encoding| {
+            snapshot
+                .reply(Some(if_none_match), accept_encoding)
+                .status()
+        };
+        assert_eq!(status(&json_etag, None), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            status(&format!("W/{}", json_etag), None),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(status(&gzip_etag, Some("gzip")), StatusCode::NOT_MODIFIED);
+        assert_eq!(status(&json_etag, Some("gzip")), StatusCode::OK);
+        assert_eq!(status(&gzip_etag, None), StatusCode::OK);
+    }
+}
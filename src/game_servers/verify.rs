@@ -1,7 +1,10 @@
 use std::net::IpAddr;
+
+use once_cell::sync::OnceCell;
 use thiserror::Error;
+use warp::http::StatusCode;
 
-use crate::api::ApiErrorKind;
+use crate::{api::ApiErrorKind, id::UniqueId};
 
 #[derive(Error, Debug)]
 pub enum VerifyServerError {
@@ -11,13 +14,21 @@ pub enum VerifyServerError {
     WrongProtocol,
     #[error("server did not respond with the correct message")]
     WrongResponse,
-    #[error("an unexpected error occured")]
-    Unknown,
 }
 
 impl From<reqwest::Error> for VerifyServerError {
     fn from(error: reqwest::Error) -> Self {
-        if error.is_connect() {
+        if error.is_connect() || error.is_timeout() {
+            Self::ConnectionFailed
+        } else {
+            Self::WrongProtocol
+        }
+    }
+}
+
+impl From<super::client::SendError> for VerifyServerError {
+    fn from(error: super::client::SendError) -> Self {
+        if error.is_transport_failure() {
             Self::ConnectionFailed
         } else {
             Self::WrongProtocol
@@ -32,23 +43,58 @@ impl ApiErrorKind for VerifyServerError {
             VerifyServerError::WrongResponse | VerifyServerError::WrongProtocol => {
                 "BAD_GAMESERVER_RESPONSE"
             }
-            VerifyServerError::Unknown => "UNKNOWN",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            VerifyServerError::ConnectionFailed => StatusCode::GATEWAY_TIMEOUT,
+            VerifyServerError::WrongResponse | VerifyServerError::WrongProtocol => {
+                StatusCode::BAD_GATEWAY
+            }
         }
     }
 }
 
+/// Response of game servers that don't support challenges.
 static SERVER_VERIFY_TEXT: &str = "I am a northstar server!";
 
-/// Tries to confirm that a Northstar server is running at the specified address
+/// Whether servers may still answer with [`SERVER_VERIFY_TEXT`] instead of echoing the challenge.
+///
+/// Enabled unless `SERVER_VERIFY_LEGACY` is `false`. This is temporary: the default becomes `false`
+/// once `LAUNCHER_VERSION` requires a Northstar release whose servers echo the challenge.
+fn legacy_verification() -> bool {
+    static INSTANCE: OnceCell<bool> = OnceCell::new();
+    *INSTANCE.get_or_init(|| {
+        let var = std::env::var("SERVER_VERIFY_LEGACY");
+        if var.is_err() {
+            tracing::warn!("SERVER_VERIFY_LEGACY is not set, servers can still be verified without a challenge");
+        }
+        var.map(|v| {
+            v.parse::<bool>()
+                .expect("SERVER_VERIFY_LEGACY must be true or false")
+        })
+        .unwrap_or(true)
+    })
+}
+
+/// Tries to confirm that a Northstar server is running at the specified address.
+///
+/// The server has to echo a random nonce, so a web server serving a fixed text can't pass.
+/// The nonce isn't signed with the server auth token, as servers only receive their token after being verified.
 pub async fn verify_server(address: IpAddr, auth_port: u16) -> Result<(), VerifyServerError> {
-    let client = reqwest::Client::new();
-    let response = client
+    let nonce = UniqueId::new(&mut rand::thread_rng()).to_string();
+    let request = super::client::client()
         .get(format!("http://{address}:{auth_port}/verify"))
-        .send()
-        .await?;
+        .query(&[("nonce", &nonce)])
+        .build()?;
+    let response = super::client::send(request).await?;
 
     let text = response.text().await?;
-    if text == SERVER_VERIFY_TEXT {
+    if text.trim() == nonce {
+        Ok(())
+    } else if text == SERVER_VERIFY_TEXT && legacy_verification() {
+        tracing::debug!(%address, "server verified without challenge");
         Ok(())
     } else {
         Err(VerifyServerError::WrongResponse)
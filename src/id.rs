@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use thiserror::Error;
 
@@ -27,21 +27,35 @@ impl UniqueId {
 impl fmt::Display for UniqueId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut output = [0u8; 32];
-        hex::encode_to_slice(&self.0, &mut output).unwrap();
+        hex::encode_to_slice(self.0, &mut output).unwrap();
         f.write_str(std::str::from_utf8(&output).unwrap())?;
         Ok(())
     }
 }
 
-/// Possible errors when deserializing a [`UniqueId`].
+/// Possible errors when parsing a [`UniqueId`].
 #[derive(Error, Debug)]
-enum Error {
+pub enum Error {
     #[error("id is not hexadecimal")]
     NotHex,
     #[error("id is not 16 bytes long")]
     InvalidLength,
 }
 
+impl FromStr for UniqueId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut id = [0u8; 16];
+        hex::decode_to_slice(s, &mut id).map_err(|err| match err {
+            hex::FromHexError::InvalidStringLength => Error::InvalidLength,
+            _ => Error::NotHex,
+        })?;
+
+        Ok(UniqueId(id))
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for UniqueId {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -64,15 +78,7 @@ impl<'de> serde::de::Visitor<'de> for UniqueIdVisitor {
     where
         E: serde::de::Error,
     {
-        let mut id = [0u8; 16];
-        hex::decode_to_slice(v, &mut id)
-            .map_err(|err| match err {
-                hex::FromHexError::InvalidStringLength => Error::InvalidLength,
-                _ => Error::NotHex,
-            })
-            .map_err(serde::de::Error::custom)?;
-
-        Ok(UniqueId(id))
+        v.parse().map_err(serde::de::Error::custom)
     }
 }
 
@@ -82,7 +88,7 @@ impl serde::Serialize for UniqueId {
         S: serde::Serializer,
     {
         let mut output = [0u8; 32];
-        hex::encode_to_slice(&self.0, &mut output).unwrap();
+        hex::encode_to_slice(self.0, &mut output).unwrap();
         serializer.serialize_str(std::str::from_utf8(&output).unwrap())
     }
 }
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use warp::http::StatusCode;
+
+use crate::{accounts::AccountId, api::ApiErrorKind};
+
+use super::LeaderboardRepository;
+
+/// Maximum number of entries returned at once.
+const MAX_PAGE_SIZE: u32 = 100;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct LeaderboardParam {
+    #[serde(default)]
+    page: u32,
+    page_size: Option<u32>,
+}
+
+#[derive(Error, Debug)]
+pub(super) enum LeaderboardError {
+    #[error("no leaderboard exists for this metric")]
+    UnknownMetric,
+    #[error("page size must be between 1 and {MAX_PAGE_SIZE}")]
+    InvalidPageSize,
+    #[error("leaderboard could not be read from the database")]
+    Database(#[from] sqlx::Error),
+}
+
+impl ApiErrorKind for LeaderboardError {
+    fn kind(&self) -> &'static str {
+        match self {
+            LeaderboardError::UnknownMetric => "UNKNOWN_METRIC",
+            LeaderboardError::InvalidPageSize => "INVALID_PAGE_SIZE",
+            LeaderboardError::Database(_) => "DATABASE_ERROR",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            LeaderboardError::UnknownMetric => StatusCode::NOT_FOUND,
+            LeaderboardError::InvalidPageSize => StatusCode::BAD_REQUEST,
+            LeaderboardError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct LeaderboardEntry {
+    rank: i64,
+    id: AccountId,
+    name: Option<String>,
+    value: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct LeaderboardResponse {
+    metric: String,
+    page: u32,
+    page_size: u32,
+    total: i64,
+    entries: Vec<LeaderboardEntry>,
+}
+
+pub(super) async fn leaderboard(
+    metric: String,
+    param: LeaderboardParam,
+    leaderboard: Arc<dyn LeaderboardRepository>,
+) -> Result<LeaderboardResponse, LeaderboardError> {
+    if !super::metrics().iter().any(|m| m.name == metric) {
+        return Err(LeaderboardError::UnknownMetric);
+    }
+
+    let page_size = param.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size == 0 || page_size > MAX_PAGE_SIZE {
+        return Err(LeaderboardError::InvalidPageSize);
+    }
+
+    let offset = i64::from(param.page) * i64::from(page_size);
+    let total = leaderboard.count(&metric).await?;
+    let entries = leaderboard
+        .page(&metric, offset, page_size.into())
+        .await?
+        .into_iter()
+        .zip(offset + 1..)
+        .map(|(row, rank)| LeaderboardEntry {
+            rank,
+            id: row.account_id,
+            name: row.username,
+            value: row.value,
+        })
+        .collect();
+
+    Ok(LeaderboardResponse {
+        metric,
+        page: param.page,
+        page_size,
+        total,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::test_databases, leaderboard::leaderboard_repository};
+
+    fn param() -> LeaderboardParam {
+        LeaderboardParam {
+            page: 0,
+            page_size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_database_errors() {
+        for database in test_databases().await {
+            let leaderboard = leaderboard_repository(database.clone());
+            database.close().await;
+
+            let err = match super::leaderboard("netWorth".to_owned(), param(), leaderboard).await {
+                Err(err) => err,
+                Ok(_) => panic!("read a leaderboard from a closed database"),
+            };
+            assert_eq!(err.kind(), "DATABASE_ERROR");
+            assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use std::fmt;
+
+use player_data::PlayerData;
+use serde::{
+    ser::{self, Impossible},
+    Serialize,
+};
+
+/// A step in the path of a metric.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+/// A value in the player data that players are ranked by.
+pub struct Metric {
+    /// Path of the value, for example `killStats.totalPVP` or `gameStats.modesWon[0]`
+    pub name: String,
+    path: Vec<Segment>,
+}
+
+impl Metric {
+    /// Parses the path of a metric, returns `None` if an index is not a number.
+    pub(super) fn parse(name: &str) -> Option<Self> {
+        let mut path = Vec::new();
+        for part in name.split('.') {
+            let mut indices = part.split('[');
+            path.push(Segment::Field(indices.next()?.to_owned()));
+            for index in indices {
+                path.push(Segment::Index(index.strip_suffix(']')?.parse().ok()?));
+            }
+        }
+
+        Some(Self {
+            name: name.to_owned(),
+            path,
+        })
+    }
+
+    /// Reads the value of the metric, returns `None` if the path doesn't lead to a number.
+    pub fn extract(&self, player_data: &PlayerData) -> Option<f64> {
+        // Only the fields along the path are visited, nothing is copied
+        player_data
+            .serialize(Lookup { path: &self.path })
+            .ok()
+            .flatten()
+    }
+}
+
+#[derive(Debug)]
+struct LookupError;
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unsupported type")
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+impl ser::Error for LookupError {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        LookupError
+    }
+}
+
+/// Serializer that finds the number at the end of a path.
+struct Lookup<'a> {
+    path: &'a [Segment],
+}
+
+impl<'a> Lookup<'a> {
+    fn number(self, value: f64) -> Result<Option<f64>, LookupError> {
+        Ok(self.path.is_empty().then_some(value))
+    }
+
+    fn compound(self) -> Result<Compound<'a>, LookupError> {
+        Ok(Compound {
+            path: self.path,
+            index: 0,
+            found: None,
+        })
+    }
+}
+
+impl<'a> ser::Serializer for Lookup<'a> {
+    type Ok = Option<f64>;
+    type Error = LookupError;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Impossible<Option<f64>, LookupError>;
+    type SerializeMap = Impossible<Option<f64>, LookupError>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Impossible<Option<f64>, LookupError>;
+
+    fn serialize_bool(self, _: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.number(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.number(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.number(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.number(v)
+    }
+
+    fn serialize_char(self, _: char) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        // Enum values are names, not numbers
+        Ok(None)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Ok(None)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.compound()
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.compound()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.compound()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(LookupError)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(LookupError)
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.compound()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(LookupError)
+    }
+}
+
+/// Visits the elements of a struct or array, descending only into the next segment of the path.
+struct Compound<'a> {
+    path: &'a [Segment],
+    index: usize,
+    found: Option<f64>,
+}
+
+impl Compound<'_> {
+    fn element<T>(&mut self, value: &T) -> Result<(), LookupError>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.path.first() == Some(&Segment::Index(self.index)) {
+            self.found = value.serialize(Lookup {
+                path: &self.path[1..],
+            })?;
+        }
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = Option<f64>;
+    type Error = LookupError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.found)
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = Option<f64>;
+    type Error = LookupError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.found)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = Option<f64>;
+    type Error = LookupError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.found)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = Option<f64>;
+    type Error = LookupError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Some(Segment::Field(name)) = self.path.first() {
+            if name == key {
+                self.found = value.serialize(Lookup {
+                    path: &self.path[1..],
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_data() -> Box<PlayerData> {
+        player_data::from_u8(crate::accounts::default_persistent_data()).unwrap()
+    }
+
+    fn extract(name: &str, player_data: &PlayerData) -> Option<f64> {
+        Metric::parse(name).unwrap().extract(player_data)
+    }
+
+    #[test]
+    fn parse_paths() {
+        assert_eq!(
+            Metric::parse("gameStats.modesWon[3]").unwrap().path,
+            [
+                Segment::Field("gameStats".to_owned()),
+                Segment::Field("modesWon".to_owned()),
+                Segment::Index(3)
+            ]
+        );
+        assert!(Metric::parse("gameStats.modesWon[x]").is_none());
+        assert!(Metric::parse("gameStats.modesWon[3").is_none());
+    }
+
+    #[test]
+    fn extract_numbers() {
+        let mut player_data = default_data();
+        player_data.netWorth = 1234;
+        player_data.killStats.totalPVP = 56;
+        player_data.gameStats.modesWon[2] = 7;
+        player_data.kdratio_lifetime_pvp = 1.5;
+
+        assert_eq!(extract("netWorth", &player_data), Some(1234.0));
+        assert_eq!(extract("killStats.totalPVP", &player_data), Some(56.0));
+        assert_eq!(extract("gameStats.modesWon[2]", &player_data), Some(7.0));
+        assert_eq!(extract("kdratio_lifetime_pvp", &player_data), Some(1.5));
+    }
+
+    #[test]
+    fn extract_rejects_other_values() {
+        let player_data = default_data();
+        assert_eq!(extract("unknownField", &player_data), None);
+        assert_eq!(extract("killStats", &player_data), None);
+        assert_eq!(extract("gameStats.modesWon[1000]", &player_data), None);
+        assert_eq!(extract("netWorth.value", &player_data), None);
+        // Enums and strings are not numbers
+        assert_eq!(extract("activePilotLoadout.suit", &player_data), None);
+        assert_eq!(extract("activePilotLoadout.name", &player_data), None);
+    }
+}
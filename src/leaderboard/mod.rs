@@ -0,0 +1,126 @@
+use metric::Metric;
+use once_cell::sync::OnceCell;
+pub use repository::{leaderboard_repository, LeaderboardRepository};
+pub use routes::routes;
+
+use crate::accounts::AccountRepository;
+
+mod handlers;
+mod metric;
+mod repository;
+mod routes;
+
+/// Metrics that are ranked if `LEADERBOARD_METRICS` is not set.
+const DEFAULT_METRICS: &str =
+    "netWorth,gen,killStats.totalPVP,highestWinStreakEver,kdratio_lifetime_pvp";
+
+/// The ranked metrics, configured with a comma separated list in `LEADERBOARD_METRICS`.
+pub fn metrics() -> &'static [Metric] {
+    static INSTANCE: OnceCell<Vec<Metric>> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        let names =
+            std::env::var("LEADERBOARD_METRICS").unwrap_or_else(|_| DEFAULT_METRICS.to_owned());
+        let metrics: Vec<_> = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Metric::parse(name).unwrap_or_else(|| {
+                    panic!(
+                        "LEADERBOARD_METRICS contains {}, which is not a valid path",
+                        name
+                    )
+                })
+            })
+            .collect();
+
+        // Every metric must point to a number
+        let default_data = player_data::from_u8(crate::accounts::default_persistent_data())
+            .expect("Default player data is invalid");
+        for metric in &metrics {
+            if metric.extract(&default_data).is_none() {
+                panic!(
+                    "LEADERBOARD_METRICS contains {}, which is not a number in the player data",
+                    metric.name
+                );
+            }
+        }
+
+        metrics
+    })
+}
+
+/// Extracts the value of every ranked metric from the player data.
+///
+/// Non-finite values (NaN or infinite ratios) can't be ranked, the previous value of the player is kept instead.
+pub fn extract(player_data: &player_data::PlayerData) -> Vec<(&'static str, f64)> {
+    metrics()
+        .iter()
+        .filter_map(|metric| {
+            let number = metric.extract(player_data).filter(|n| n.is_finite())?;
+            Some((metric.name.as_str(), number))
+        })
+        .collect()
+}
+
+/// Indexes the ranked values of every account with stored player data, returning how many were indexed.
+///
+/// Players are otherwise only indexed when a game server writes their data, this fills the leaderboards
+/// for accounts that haven't played since the leaderboards or a metric were added.
+pub async fn rebuild(
+    accounts: &dyn AccountRepository,
+    leaderboard: &dyn LeaderboardRepository,
+) -> Result<u64, sqlx::Error> {
+    let mut indexed = 0;
+    for id in accounts.ids_with_data().await? {
+        let data = accounts.get_data(id).await?;
+        match player_data::from_u8(&data) {
+            Ok(player_data) => {
+                leaderboard.set_values(id, &extract(&player_data)).await?;
+                indexed += 1;
+            }
+            Err(err) => tracing::warn!(%err, account_id = id.0, "skipping undecodable player data"),
+        }
+    }
+    Ok(indexed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accounts::account_repository, database::test_databases};
+
+    #[tokio::test]
+    async fn rebuild_indexes_stored_data() {
+        for database in test_databases().await {
+            let accounts = account_repository(database.clone());
+            let leaderboard = leaderboard_repository(database);
+            let id = crate::accounts::AccountId(rand::random::<u32>().into());
+            accounts.create(id).await.unwrap();
+            // Stored without ranked values, like data written before the leaderboards existed
+            let mut data =
+                player_data::from_u8(crate::accounts::default_persistent_data()).unwrap();
+            data.netWorth = 1_000_000_000;
+            let data = player_data::to_vec(&data).unwrap();
+            accounts.set_data(id, &data, &[], &[]).await.unwrap();
+            let broken = crate::accounts::AccountId(rand::random::<u32>().into());
+            accounts.create(broken).await.unwrap();
+            accounts
+                .set_data(broken, &[1, 2, 3], &[], &[])
+                .await
+                .unwrap();
+
+            assert!(
+                rebuild(accounts.as_ref(), leaderboard.as_ref())
+                    .await
+                    .unwrap()
+                    >= 1
+            );
+            // Other tests may have ranked players on the shared PostgreSQL database too
+            let total = leaderboard.count("netWorth").await.unwrap();
+            let page = leaderboard.page("netWorth", 0, total).await.unwrap();
+            let row = page.iter().find(|row| row.account_id == id).unwrap();
+            assert_eq!(row.value, 1_000_000_000.0);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use crate::{accounts::AccountId, Database};
+
+mod postgres;
+mod sqlite;
+
+#[derive(sqlx::FromRow)]
+pub struct LeaderboardRow {
+    pub account_id: AccountId,
+    pub username: Option<String>,
+    pub value: f64,
+}
+
+/// Storage of the ranked values of every player, implemented for every supported database.
+#[async_trait::async_trait]
+pub trait LeaderboardRepository: Send + Sync {
+    async fn set_values(&self, id: AccountId, values: &[(&str, f64)]) -> Result<(), sqlx::Error>;
+
+    async fn count(&self, metric: &str) -> Result<i64, sqlx::Error>;
+
+    /// Returns the highest ranked entries for a metric, starting at `offset`.
+    async fn page(
+        &self,
+        metric: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<LeaderboardRow>, sqlx::Error>;
+}
+
+/// Creates the leaderboard repository for the configured database.
+pub fn leaderboard_repository(database: Database) -> Arc<dyn LeaderboardRepository> {
+    match database {
+        Database::Sqlite(pool) => Arc::new(sqlite::SqliteLeaderboardRepository::new(pool)),
+        Database::Postgres(pool) => Arc::new(postgres::PostgresLeaderboardRepository::new(pool)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accounts::account_repository, database::test_databases};
+
+    #[tokio::test]
+    async fn ranking() {
+        for database in test_databases().await {
+            let accounts = account_repository(database.clone());
+            let leaderboard = leaderboard_repository(database);
+            // Tests share the PostgreSQL database, a new metric keeps the ranking separate
+            let metric = format!("test.{}", rand::random::<u32>());
+
+            let mut ids = Vec::new();
+            for value in [10.0, 30.0, 20.0] {
+                let id = AccountId(rand::random::<u32>().into());
+                accounts.create(id).await.unwrap();
+                leaderboard
+                    .set_values(id, &[(&metric, value), ("other", 1.0)])
+                    .await
+                    .unwrap();
+                ids.push(id);
+            }
+            accounts.set_name(ids[1], "best").await.unwrap();
+            // Updating a value replaces it
+            leaderboard
+                .set_values(ids[0], &[(&metric, 25.0)])
+                .await
+                .unwrap();
+
+            assert_eq!(leaderboard.count(&metric).await.unwrap(), 3);
+            let page = leaderboard.page(&metric, 0, 2).await.unwrap();
+            let ranking: Vec<_> = page.iter().map(|row| (row.account_id, row.value)).collect();
+            assert_eq!(ranking, [(ids[1], 30.0), (ids[0], 25.0)]);
+            assert_eq!(page[0].username.as_deref(), Some("best"));
+
+            let page = leaderboard.page(&metric, 2, 2).await.unwrap();
+            assert_eq!(page.len(), 1);
+            assert_eq!((page[0].account_id, page[0].value), (ids[2], 20.0));
+        }
+    }
+}
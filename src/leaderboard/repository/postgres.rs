@@ -0,0 +1,62 @@
+use sqlx::PgPool;
+
+use crate::accounts::AccountId;
+
+use super::{LeaderboardRepository, LeaderboardRow};
+
+pub struct PostgresLeaderboardRepository {
+    database: PgPool,
+}
+
+impl PostgresLeaderboardRepository {
+    pub fn new(database: PgPool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl LeaderboardRepository for PostgresLeaderboardRepository {
+    async fn set_values(&self, id: AccountId, values: &[(&str, f64)]) -> Result<(), sqlx::Error> {
+        for (metric, value) in values {
+            sqlx::query(
+                r#"INSERT INTO leaderboard (account_id, metric, value) VALUES ($1, $2, $3)
+                ON CONFLICT (account_id, metric) DO UPDATE SET value = excluded.value"#,
+            )
+            .bind(id)
+            .bind(metric)
+            .bind(value)
+            .execute(&self.database)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn count(&self, metric: &str) -> Result<i64, sqlx::Error> {
+        let (count,): (i64,) =
+            sqlx::query_as(r#"SELECT COUNT(*) FROM leaderboard WHERE metric = $1"#)
+                .bind(metric)
+                .fetch_one(&self.database)
+                .await?;
+        Ok(count)
+    }
+
+    async fn page(
+        &self,
+        metric: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<LeaderboardRow>, sqlx::Error> {
+        sqlx::query_as(
+            r#"SELECT leaderboard.account_id, accounts.username, leaderboard.value
+            FROM leaderboard JOIN accounts ON accounts.id = leaderboard.account_id
+            WHERE leaderboard.metric = $1
+            ORDER BY leaderboard.value DESC, leaderboard.account_id
+            LIMIT $2 OFFSET $3"#,
+        )
+        .bind(metric)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.database)
+        .await
+    }
+}
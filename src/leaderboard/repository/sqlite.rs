@@ -0,0 +1,65 @@
+use sqlx::SqlitePool;
+
+use crate::accounts::AccountId;
+
+use super::{LeaderboardRepository, LeaderboardRow};
+
+pub struct SqliteLeaderboardRepository {
+    database: SqlitePool,
+}
+
+impl SqliteLeaderboardRepository {
+    pub fn new(database: SqlitePool) -> Self {
+        Self { database }
+    }
+}
+
+#[async_trait::async_trait]
+impl LeaderboardRepository for SqliteLeaderboardRepository {
+    async fn set_values(&self, id: AccountId, values: &[(&str, f64)]) -> Result<(), sqlx::Error> {
+        for (metric, value) in values {
+            sqlx::query!(
+                r#"INSERT INTO leaderboard (account_id, metric, value) VALUES (?, ?, ?)
+                ON CONFLICT (account_id, metric) DO UPDATE SET value = excluded.value"#,
+                id,
+                metric,
+                value
+            )
+            .execute(&self.database)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn count(&self, metric: &str) -> Result<i64, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT COUNT(*) as count FROM leaderboard WHERE metric = ?"#,
+            metric
+        )
+        .fetch_one(&self.database)
+        .await?
+        .count
+        .into())
+    }
+
+    async fn page(
+        &self,
+        metric: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<LeaderboardRow>, sqlx::Error> {
+        sqlx::query_as!(
+            LeaderboardRow,
+            r#"SELECT leaderboard.account_id as "account_id: AccountId", accounts.username, leaderboard.value as "value: f64"
+            FROM leaderboard JOIN accounts ON accounts.id = leaderboard.account_id
+            WHERE leaderboard.metric = ?
+            ORDER BY leaderboard.value DESC, leaderboard.account_id
+            LIMIT ? OFFSET ?"#,
+            metric,
+            limit,
+            offset
+        )
+        .fetch_all(&self.database)
+        .await
+    }
+}
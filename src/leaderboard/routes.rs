@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use percent_encoding::percent_decode_str;
+use warp::Filter;
+
+use crate::{api::api_response, Database};
+
+use super::{leaderboard_repository, LeaderboardRepository};
+
+pub fn routes(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    leaderboard(database)
+}
+
+pub(super) fn leaderboard(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("leaderboard" / String)
+        // Clients encode the brackets of array indices, `modesWon[0]` arrives as `modesWon%5B0%5D`
+        .map(|metric: String| percent_decode_str(&metric).decode_utf8_lossy().into_owned())
+        .and(warp::get())
+        .and(warp::query::<super::handlers::LeaderboardParam>())
+        .and(with_leaderboard(database))
+        .then(super::handlers::leaderboard)
+        .map(api_response)
+}
+
+pub fn with_leaderboard(
+    database: Database,
+) -> impl Filter<Extract = (Arc<dyn LeaderboardRepository>,), Error = std::convert::Infallible> + Clone
+{
+    let leaderboard = leaderboard_repository(database);
+    warp::any().map(move || leaderboard.clone())
+}
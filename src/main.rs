@@ -3,29 +3,41 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use warp::Filter;
 
+use database::Database;
+
 mod accounts;
+mod admin;
 mod api;
 mod auth;
+mod database;
 mod game_servers;
 mod id;
+mod leaderboard;
 mod players;
 mod promos;
+mod proxy;
+mod rate_limit;
 
 #[macro_use]
 mod routes_macro;
 
 type SharedServerList = Arc<RwLock<game_servers::ServerList>>;
-type Database = sqlx::Pool<sqlx::Sqlite>;
 
 #[tokio::main]
 async fn main() {
     // Load database
-    let database = sqlx::SqlitePool::connect(
+    let database = Database::connect(
         &std::env::var("DATABASE_URL").expect("DATABASE_URL env var must be set"),
     )
     .await
     .expect("Failed opening database");
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if admin::run(&args, &database).await {
+        database.close().await;
+        return;
+    }
+
     // Set up logging
     let filter = std::env::var("RUST_LOG")
         .unwrap_or_else(|_| "northstar_master_server=info,warp=debug".to_owned());
@@ -34,19 +46,102 @@ async fn main() {
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
         .init();
 
-    let servers: SharedServerList = Arc::new(RwLock::default());
-    let routes = api::northstar_version()
-        .and(balanced_or_tree!(
-            game_servers::routes(servers.clone()),
-            auth::routes(database.clone(), servers.clone()),
-            accounts::routes(database.clone(), servers),
-            promos::routes(),
-            players::routes(database.clone())
+    // Invalid pdiffs or settings stop the master server here instead of failing the first request using them
+    accounts::pdiff::definitions();
+    accounts::persistent_data_limits();
+    accounts::token_secret();
+    leaderboard::metrics();
+    game_servers::legacy_token_fallback();
+
+    let servers = game_servers::ServerList::load(game_servers::server_repository(database.clone()))
+        .await
+        .expect("Failed loading stored servers");
+    let servers: SharedServerList = Arc::new(RwLock::new(servers));
+    let server_list_cache = game_servers::ServerListCache::new(&servers).await;
+    tokio::spawn(game_servers::maintain_server_list(
+        servers.clone(),
+        server_list_cache.clone(),
+    ));
+    tokio::spawn(accounts::prune_sessions(accounts::account_repository(
+        database.clone(),
+    )));
+    tokio::spawn(rate_limit::prune_limiters());
+    let auth_provider = auth::provider::from_env();
+    let username_lookup = auth::username::from_env();
+    // Versioned routes respond with proper status codes, legacy routes keep the original behaviour
+    let versioned = warp::path!("api" / "v2" / ..)
+        .and(api::northstar_version())
+        .and(v2_routes(
+            database.clone(),
+            servers.clone(),
+            server_list_cache.clone(),
+            auth_provider.clone(),
+            username_lookup.clone(),
         ))
-        .with(warp::trace::request())
-        .recover(api::version_error_handler);
+        .recover(api::rejection_handler);
+    let legacy = api::northstar_version()
+        .and(routes(
+            database.clone(),
+            servers,
+            server_list_cache,
+            auth_provider,
+            username_lookup,
+        ))
+        .recover(api::rejection_handler)
+        .map(api::legacy_status);
+    let routes = versioned.or(legacy).with(warp::trace::request());
 
-    warp::serve(routes).run(([0, 0, 0, 0], 33998)).await;
+    let address = ([0, 0, 0, 0], 33998).into();
+    if proxy::proxy_protocol() {
+        proxy::serve_proxy_protocol(warp::service(routes), address).await;
+    } else {
+        warp::serve(routes).run(address).await;
+    }
 
     database.close().await;
 }
+
+fn routes(
+    database: Database,
+    servers: SharedServerList,
+    server_list_cache: game_servers::ServerListCache,
+    auth_provider: Arc<dyn auth::provider::AuthProvider>,
+    username_lookup: Option<Arc<dyn auth::username::UsernameLookup>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        game_servers::routes(servers.clone(), server_list_cache),
+        auth::routes(
+            database.clone(),
+            servers.clone(),
+            auth_provider,
+            username_lookup
+        ),
+        accounts::routes(database.clone(), servers),
+        promos::routes(),
+        players::routes(database.clone()),
+        leaderboard::routes(database)
+    )
+}
+
+/// Routes of the versioned api, sharing handlers with the legacy routes where their contract is the same.
+fn v2_routes(
+    database: Database,
+    servers: SharedServerList,
+    server_list_cache: game_servers::ServerListCache,
+    auth_provider: Arc<dyn auth::provider::AuthProvider>,
+    username_lookup: Option<Arc<dyn auth::username::UsernameLookup>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    balanced_or_tree!(
+        game_servers::v2_routes(servers.clone(), server_list_cache),
+        auth::v2_routes(
+            database.clone(),
+            servers.clone(),
+            auth_provider,
+            username_lookup
+        ),
+        accounts::v2_routes(database.clone(), servers),
+        promos::routes(),
+        players::routes(database.clone()),
+        leaderboard::routes(database)
+    )
+}
@@ -1,5 +1,13 @@
+use std::sync::Arc;
+
+use player_data::{
+    DeathStats, FdStats, GameMode, GameStats, HoursPlayed, KillStats, LoadoutWeaponsAbilities, Map,
+    MapStats, MilesTraveled, MiscStats, PilotLoadout, PlayerData, TitanClass, TitanLoadout,
+    TitanStats, WeaponKillStats, WeaponStats,
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use warp::http::StatusCode;
 
 use crate::{
     accounts::{AccountId, AccountRepository},
@@ -16,16 +24,49 @@ pub(super) struct PlayerInfoParam {
 pub(super) enum PlayerError {
     #[error("player not found")]
     NotFound,
+    #[error("stored persistent data could not be decoded")]
+    InvalidData(#[from] player_data::Error),
+    #[error("player could not be loaded from the database")]
+    Database(#[source] sqlx::Error),
+}
+
+impl From<sqlx::Error> for PlayerError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => PlayerError::NotFound,
+            error => PlayerError::Database(error),
+        }
+    }
 }
 
 impl ApiErrorKind for PlayerError {
     fn kind(&self) -> &'static str {
         match self {
             PlayerError::NotFound => "PLAYER_NOT_FOUND",
+            PlayerError::InvalidData(_) => "INVALID_PERSISTENT_DATA",
+            PlayerError::Database(_) => "DATABASE_ERROR",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            PlayerError::NotFound => StatusCode::NOT_FOUND,
+            // The stored data is broken, not the request
+            PlayerError::InvalidData(_) | PlayerError::Database(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
         }
     }
 }
 
+async fn load_player_data(
+    id: AccountId,
+    accounts: &dyn AccountRepository,
+) -> Result<Box<PlayerData>, PlayerError> {
+    let raw_data = accounts.get_data(id).await?;
+    Ok(player_data::from_u8(&raw_data)?)
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct PlayerInfoResponse {
@@ -41,17 +82,14 @@ pub(super) struct PlayerInfoResponse {
 
 pub(super) async fn player_info(
     param: PlayerInfoParam,
-    accounts: AccountRepository,
+    accounts: Arc<dyn AccountRepository>,
 ) -> Result<PlayerInfoResponse, PlayerError> {
-    let raw_data = accounts
-        .get_data(param.id)
-        .await
-        .map_err(|_| PlayerError::NotFound)?;
-    let player_data = player_data::from_u8(&raw_data).unwrap();
+    let player_data = load_player_data(param.id, accounts.as_ref()).await?;
+    let name = accounts.get_name(param.id).await?;
 
     Ok(PlayerInfoResponse {
         id: param.id,
-        name: accounts.get_name(param.id).await.unwrap(),
+        name,
         gen: player_data.gen,
         xp: player_data.xp,
         active_calling_card_index: player_data.activeCallingCardIndex,
@@ -60,3 +98,354 @@ pub(super) async fn player_info(
         net_worth: player_data.netWorth,
     })
 }
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct NameChangeEntry {
+    name: String,
+    /// RFC 3339 timestamp of when the name was first seen
+    changed: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PlayerNamesResponse {
+    id: AccountId,
+    name: Option<String>,
+    /// RFC 3339 timestamp of when the name was last confirmed
+    updated: Option<String>,
+    /// Every name of the player, newest first
+    history: Vec<NameChangeEntry>,
+}
+
+pub(super) async fn player_names(
+    param: PlayerInfoParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<PlayerNamesResponse, PlayerError> {
+    let history = accounts.get_name_history(param.id).await?;
+
+    Ok(PlayerNamesResponse {
+        id: param.id,
+        name: history.username,
+        updated: history.updated.map(|time| time.to_rfc3339()),
+        history: history
+            .changes
+            .into_iter()
+            .map(|change| NameChangeEntry {
+                name: change.username,
+                changed: change.changed.to_rfc3339(),
+            })
+            .collect(),
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TitanEntry {
+    titan: &'static str,
+    stats: TitanStats,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PlayerStatsResponse {
+    id: AccountId,
+    game_stats: GameStats,
+    kill_stats: KillStats,
+    death_stats: DeathStats,
+    misc_stats: MiscStats,
+    fd_stats: FdStats,
+    time_stats: HoursPlayed,
+    distance_stats: MilesTraveled,
+    titan_stats: Vec<TitanEntry>,
+    kd_ratio_lifetime: f32,
+    kd_ratio_lifetime_pvp: f32,
+    win_streak: i32,
+    highest_win_streak_ever: i32,
+}
+
+pub(super) async fn player_stats(
+    param: PlayerInfoParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<PlayerStatsResponse, PlayerError> {
+    let player_data = *load_player_data(param.id, accounts.as_ref()).await?;
+
+    // Stats are stored in the order of the titan class enum
+    let titan_stats = player_data::enum_variants::<TitanClass>()
+        .iter()
+        .zip(player_data.titanStats)
+        .map(|(&titan, stats)| TitanEntry { titan, stats })
+        .collect();
+
+    Ok(PlayerStatsResponse {
+        id: param.id,
+        game_stats: player_data.gameStats,
+        kill_stats: player_data.killStats,
+        death_stats: player_data.deathStats,
+        misc_stats: player_data.miscStats,
+        fd_stats: player_data.fdStats,
+        time_stats: player_data.timeStats,
+        distance_stats: player_data.distanceStats,
+        titan_stats,
+        kd_ratio_lifetime: player_data.kdratio_lifetime,
+        kd_ratio_lifetime_pvp: player_data.kdratio_lifetime_pvp,
+        win_streak: player_data.winStreak,
+        highest_win_streak_ever: player_data.highestWinStreakEver,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct WeaponEntry {
+    weapon: &'static str,
+    stats: WeaponStats,
+    kill_stats: WeaponKillStats,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PlayerWeaponsResponse {
+    id: AccountId,
+    weapons: Vec<WeaponEntry>,
+}
+
+pub(super) async fn player_weapons(
+    param: PlayerInfoParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<PlayerWeaponsResponse, PlayerError> {
+    let player_data = *load_player_data(param.id, accounts.as_ref()).await?;
+
+    // Stats are stored in the order of the weapon enum
+    let weapons = player_data::enum_variants::<LoadoutWeaponsAbilities>()
+        .iter()
+        .zip(player_data.weaponStats)
+        .zip(player_data.weaponKillStats)
+        .filter(|((&weapon, _), _)| weapon != "NULL")
+        .map(|((&weapon, stats), kill_stats)| WeaponEntry {
+            weapon,
+            stats,
+            kill_stats,
+        })
+        .collect();
+
+    Ok(PlayerWeaponsResponse {
+        id: param.id,
+        weapons,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ModeStatsEntry {
+    mode: &'static str,
+    games_joined: i32,
+    games_completed: i32,
+    games_won: i32,
+    games_lost: i32,
+    top_player_on_team: i32,
+    top3_on_team: i32,
+    hours_played: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct MapStatsEntry {
+    map: &'static str,
+    modes: Vec<ModeStatsEntry>,
+    times_scored100_attrition_points: i32,
+    wins_by_difficulty: [i32; 5],
+    matches_by_difficulty: [i32; 5],
+    perfect_matches_by_difficulty: [i32; 5],
+}
+
+impl MapStatsEntry {
+    fn new(map: &'static str, stats: MapStats) -> Self {
+        // Per mode stats are stored in the order of the game mode enum
+        let modes = player_data::enum_variants::<GameMode>()
+            .iter()
+            .enumerate()
+            .map(|(i, &mode)| ModeStatsEntry {
+                mode,
+                games_joined: stats.gamesJoined[i],
+                games_completed: stats.gamesCompleted[i],
+                games_won: stats.gamesWon[i],
+                games_lost: stats.gamesLost[i],
+                top_player_on_team: stats.topPlayerOnTeam[i],
+                top3_on_team: stats.top3OnTeam[i],
+                hours_played: stats.hoursPlayed[i],
+            })
+            .collect();
+
+        Self {
+            map,
+            modes,
+            times_scored100_attrition_points: stats.timesScored100AttritionPoints_byMap,
+            wins_by_difficulty: stats.winsByDifficulty,
+            matches_by_difficulty: stats.matchesByDifficulty,
+            perfect_matches_by_difficulty: stats.perfectMatchesByDifficulty,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PlayerMapsResponse {
+    id: AccountId,
+    maps: Vec<MapStatsEntry>,
+}
+
+pub(super) async fn player_maps(
+    param: PlayerInfoParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<PlayerMapsResponse, PlayerError> {
+    let player_data = *load_player_data(param.id, accounts.as_ref()).await?;
+
+    // Stats are stored in the order of the map enum
+    let maps = player_data::enum_variants::<Map>()
+        .iter()
+        .zip(player_data.mapStats)
+        .map(|(&map, stats)| MapStatsEntry::new(map, stats))
+        .collect();
+
+    Ok(PlayerMapsResponse { id: param.id, maps })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PlayerLoadoutsResponse {
+    id: AccountId,
+    active_pilot_loadout: PilotLoadout,
+    active_titan_loadout: TitanLoadout,
+    active_titan_loadout_index: i32,
+    pilot_spawn_loadout_index: i32,
+    titan_spawn_loadout_index: i32,
+    pilot_loadouts: Vec<PilotLoadout>,
+    titan_loadouts: Vec<TitanLoadout>,
+}
+
+pub(super) async fn player_loadouts(
+    param: PlayerInfoParam,
+    accounts: Arc<dyn AccountRepository>,
+) -> Result<PlayerLoadoutsResponse, PlayerError> {
+    let player_data = *load_player_data(param.id, accounts.as_ref()).await?;
+
+    Ok(PlayerLoadoutsResponse {
+        id: param.id,
+        active_pilot_loadout: player_data.activePilotLoadout,
+        active_titan_loadout: player_data.activeTitanLoadout,
+        active_titan_loadout_index: player_data.activeTitanLoadoutIndex,
+        pilot_spawn_loadout_index: player_data.pilotSpawnLoadout.index,
+        titan_spawn_loadout_index: player_data.titanSpawnLoadout.index,
+        pilot_loadouts: player_data.pilotLoadouts.into(),
+        titan_loadouts: player_data.titanLoadouts.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        accounts::{account_repository, default_persistent_data},
+        database::test_databases,
+    };
+
+    /// A player whose data differs from the defaults in a few stats.
+    async fn players() -> Vec<(Arc<dyn AccountRepository>, AccountId)> {
+        let mut players = Vec::new();
+        for database in test_databases().await {
+            let accounts = account_repository(database);
+            let id = AccountId(rand::random::<u32>().into());
+            accounts.create(id).await.unwrap();
+            accounts.set_name(id, "player").await.unwrap();
+
+            let mut data = player_data::from_u8(default_persistent_data()).unwrap();
+            data.titanStats[2].pilots = 3;
+            data.weaponStats[2].shotsFired = 5;
+            data.mapStats[2].gamesWon[1] = 7;
+            data.pilotLoadouts[1].name = player_data::FixedString::new("second").unwrap();
+            let data = player_data::to_vec(&data).unwrap();
+            accounts.set_data(id, &data, &[], &[]).await.unwrap();
+
+            players.push((accounts, id));
+        }
+        players
+    }
+
+    fn param(id: AccountId) -> PlayerInfoParam {
+        PlayerInfoParam { id }
+    }
+
+    fn json(value: impl Serialize) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unknown_player() {
+        for database in test_databases().await {
+            let accounts = account_repository(database);
+            let id = AccountId(rand::random::<u32>().into());
+
+            let err = match player_info(param(id), accounts.clone()).await {
+                Err(err) => err,
+                Ok(_) => panic!("found a player that doesn't exist"),
+            };
+            assert!(matches!(err, PlayerError::NotFound));
+            assert_eq!(err.status(), StatusCode::NOT_FOUND);
+            assert!(matches!(
+                player_stats(param(id), accounts).await,
+                Err(PlayerError::NotFound)
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn info() {
+        for (accounts, id) in players().await {
+            let info = json(player_info(param(id), accounts).await.unwrap());
+            assert_eq!(info["name"], "player");
+            assert_eq!(info["gen"], 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn stats() {
+        for (accounts, id) in players().await {
+            let stats = json(player_stats(param(id), accounts).await.unwrap());
+            let titans = stats["titanStats"].as_array().unwrap();
+            assert_eq!(titans.len(), player_data::TITAN_COUNT);
+            assert_eq!(titans[2]["titan"], "ronin");
+            assert_eq!(titans[2]["stats"]["pilots"], 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn weapons() {
+        for (accounts, id) in players().await {
+            let weapons = json(player_weapons(param(id), accounts).await.unwrap());
+            let weapons = weapons["weapons"].as_array().unwrap();
+            // The NULL weapon is left out, so the second stored weapon comes first
+            assert!(weapons.iter().all(|weapon| weapon["weapon"] != "NULL"));
+            assert_eq!(weapons[1]["weapon"], "melee_pilot_sword");
+            assert_eq!(weapons[1]["stats"]["shotsFired"], 5);
+        }
+    }
+
+    #[tokio::test]
+    async fn maps() {
+        for (accounts, id) in players().await {
+            let maps = json(player_maps(param(id), accounts).await.unwrap());
+            let map = &maps["maps"][2];
+            assert_eq!(map["modes"][1]["mode"], "Cp");
+            assert_eq!(map["modes"][1]["gamesWon"], 7);
+        }
+    }
+
+    #[tokio::test]
+    async fn loadouts() {
+        for (accounts, id) in players().await {
+            let loadouts = json(player_loadouts(param(id), accounts).await.unwrap());
+            assert_eq!(loadouts["pilotLoadouts"].as_array().unwrap().len(), 10);
+            assert_eq!(loadouts["pilotLoadouts"][1]["name"], "second");
+        }
+    }
+}
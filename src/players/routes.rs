@@ -6,7 +6,12 @@ pub fn routes(
     database: Database,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let base = warp::path("player");
-    base.and(player_info(database))
+    base.and(player_info(database.clone()))
+        .or(base.and(player_names(database.clone())))
+        .or(base.and(player_stats(database.clone())))
+        .or(base.and(player_weapons(database.clone())))
+        .or(base.and(player_maps(database.clone())))
+        .or(base.and(player_loadouts(database)))
 }
 
 pub(super) fn player_info(
@@ -19,3 +24,58 @@ pub(super) fn player_info(
         .then(super::handlers::player_info)
         .map(api_response)
 }
+
+pub(super) fn player_names(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("names")
+        .and(warp::get())
+        .and(warp::query::<super::handlers::PlayerInfoParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::player_names)
+        .map(api_response)
+}
+
+pub(super) fn player_stats(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("stats")
+        .and(warp::get())
+        .and(warp::query::<super::handlers::PlayerInfoParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::player_stats)
+        .map(api_response)
+}
+
+pub(super) fn player_weapons(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("weapons")
+        .and(warp::get())
+        .and(warp::query::<super::handlers::PlayerInfoParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::player_weapons)
+        .map(api_response)
+}
+
+pub(super) fn player_maps(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("maps")
+        .and(warp::get())
+        .and(warp::query::<super::handlers::PlayerInfoParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::player_maps)
+        .map(api_response)
+}
+
+pub(super) fn player_loadouts(
+    database: Database,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("loadouts")
+        .and(warp::get())
+        .and(warp::query::<super::handlers::PlayerInfoParam>())
+        .and(with_accounts(database))
+        .then(super::handlers::player_loadouts)
+        .map(api_response)
+}
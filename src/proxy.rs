@@ -0,0 +1,497 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use hyper::{server::conn::Http, service::Service, Body, Request, Response};
+use ipnet::IpNet;
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::TcpListener,
+};
+use tracing::{debug, warn};
+use warp::{http::StatusCode, Filter};
+
+use crate::api::ApiErrorKind;
+
+/// Networks of the reverse proxies whose forwarding headers are trusted, configured by `TRUSTED_PROXIES`.
+///
+/// `TRUSTED_PROXIES` is a comma separated list of addresses and CIDR ranges, for example `127.0.0.1,10.0.0.0/8`.
+fn trusted_proxies() -> &'static [IpNet] {
+    static INSTANCE: OnceCell<Vec<IpNet>> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<IpNet>()
+                    .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                    .unwrap_or_else(|_| panic!("TRUSTED_PROXIES contains invalid network {}", s))
+            })
+            .collect()
+    })
+}
+
+fn is_trusted(ip: IpAddr, trusted: &[IpNet]) -> bool {
+    let ip = canonical(ip);
+    trusted.iter().any(|net| net.contains(&ip))
+}
+
+/// Maps IPv4 addresses in IPv6 notation to plain IPv4 addresses, as sockets listening on both report them like that.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// Whether connections from trusted proxies start with a PROXY protocol header, enabled by `PROXY_PROTOCOL=true`.
+pub fn proxy_protocol() -> bool {
+    std::env::var("PROXY_PROTOCOL")
+        .map(|v| {
+            v.parse::<bool>()
+                .expect("PROXY_PROTOCOL must be true or false")
+        })
+        .unwrap_or(false)
+}
+
+#[derive(Error, Debug)]
+pub enum ClientAddressError {
+    #[error("client address could not be determined")]
+    Missing,
+}
+
+impl ApiErrorKind for ClientAddressError {
+    fn kind(&self) -> &'static str {
+        match self {
+            ClientAddressError::Missing => "NO_CLIENT_ADDRESS",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            ClientAddressError::Missing => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl warp::reject::Reject for ClientAddressError {}
+
+/// Address of the connection peer, as reported by a PROXY protocol header.
+#[derive(Clone, Copy)]
+struct ConnectionAddress(SocketAddr);
+
+/// Extracts the IP address of the client.
+///
+/// Forwarding headers are only followed while the request passed through trusted proxies,
+/// so clients can't choose their own address.
+pub fn client_ip() -> impl Filter<Extract = (IpAddr,), Error = warp::Rejection> + Clone {
+    warp::ext::optional::<ConnectionAddress>()
+        .and(warp::addr::remote())
+        .and(warp::header::headers_cloned())
+        .and_then(
+            |connection: Option<ConnectionAddress>,
+             remote: Option<SocketAddr>,
+             headers: warp::http::HeaderMap| async move {
+                let peer = connection
+                    .map(|c| c.0)
+                    .or(remote)
+                    .ok_or(ClientAddressError::Missing)?;
+                Ok::<_, warp::Rejection>(resolve_client_ip(
+                    canonical(peer.ip()),
+                    &headers,
+                    trusted_proxies(),
+                ))
+            },
+        )
+}
+
+/// Follows the forwarding chain from the peer towards the client, until an address isn't a trusted proxy.
+fn resolve_client_ip(peer: IpAddr, headers: &warp::http::HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    if !is_trusted(peer, trusted) {
+        return peer;
+    }
+
+    // Each proxy appends the address it received the request from
+    let hops: Vec<Option<IpAddr>> = if headers.contains_key("forwarded") {
+        headers
+            .get_all("forwarded")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(forwarded_for)
+            .collect()
+    } else {
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(parse_address)
+            .collect()
+    };
+
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = canonical(ip);
+                if !is_trusted(client, trusted) {
+                    break;
+                }
+            }
+            // Obfuscated or malformed entries can't be followed
+            None => break,
+        }
+    }
+    client
+}
+
+/// Reads the `for` parameter of an element of the `Forwarded` header.
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element.split(';').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case("for") {
+            parse_address(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Parses an address that may include a port, IPv6 addresses with a port are enclosed in brackets.
+fn parse_address(address: &str) -> Option<IpAddr> {
+    let address = address.trim();
+    address
+        .parse::<IpAddr>()
+        .or_else(|_| address.parse::<SocketAddr>().map(|a| a.ip()))
+        .or_else(|_| {
+            address
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+        })
+        .ok()
+}
+
+/// Signature at the start of a version 2 PROXY protocol header.
+const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Longest possible version 1 PROXY protocol header.
+const PROXY_V1_MAX_LENGTH: usize = 107;
+
+/// How long a trusted proxy may take to send the PROXY protocol header, so stalled connections are closed.
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads the PROXY protocol header at the start of a connection, returning the source address it reports.
+///
+/// Headers for connections that weren't proxied (like health checks) don't contain an address.
+async fn read_proxy_header<R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> std::io::Result<Option<SocketAddr>> {
+    let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+
+    if start == PROXY_V2_SIGNATURE {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await?;
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let mut addresses = vec![0u8; length];
+        stream.read_exact(&mut addresses).await?;
+
+        if header[0] >> 4 != 2 {
+            return Err(invalid("unsupported PROXY protocol version"));
+        }
+        // LOCAL connections are sent by the proxy itself
+        if header[0] & 0x0F == 0 {
+            return Ok(None);
+        }
+        return match header[1] >> 4 {
+            // IPv4: source, destination address, source, destination port
+            1 if length >= 12 => Ok(Some(SocketAddr::new(
+                IpAddr::from(<[u8; 4]>::try_from(&addresses[0..4]).unwrap()),
+                u16::from_be_bytes([addresses[8], addresses[9]]),
+            ))),
+            2 if length >= 36 => Ok(Some(SocketAddr::new(
+                IpAddr::from(<[u8; 16]>::try_from(&addresses[0..16]).unwrap()),
+                u16::from_be_bytes([addresses[32], addresses[33]]),
+            ))),
+            _ => Ok(None),
+        };
+    }
+
+    if !start.starts_with(b"PROXY ") {
+        return Err(invalid("missing PROXY protocol header"));
+    }
+
+    // Read the rest of the text header byte by byte, as the request follows it directly
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= PROXY_V1_MAX_LENGTH {
+            return Err(invalid("PROXY protocol header is too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+
+    let line =
+        std::str::from_utf8(&line).map_err(|_| invalid("PROXY protocol header is not text"))?;
+    let parts: Vec<&str> = line.trim_end().split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "TCP4" | "TCP6", source, _, source_port, _] => {
+            let ip = source
+                .parse()
+                .map_err(|_| invalid("invalid PROXY protocol source address"))?;
+            let port = source_port
+                .parse()
+                .map_err(|_| invalid("invalid PROXY protocol source port"))?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        _ => Err(invalid("malformed PROXY protocol header")),
+    }
+}
+
+/// Serves requests like [`warp::serve`], reading a PROXY protocol header from connections of trusted proxies.
+pub async fn serve_proxy_protocol<S>(service: S, address: SocketAddr)
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let listener = TcpListener::bind(address)
+        .await
+        .expect("Unable to bind server address");
+
+    loop {
+        let (mut stream, mut peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!(%err, "unable to accept connection");
+                continue;
+            }
+        };
+        let service = service.clone();
+
+        tokio::spawn(async move {
+            if is_trusted(peer.ip(), trusted_proxies()) {
+                let header =
+                    tokio::time::timeout(PROXY_HEADER_TIMEOUT, read_proxy_header(&mut stream))
+                        .await;
+                match header {
+                    Ok(Ok(Some(source))) => peer = source,
+                    Ok(Ok(None)) => {}
+                    Ok(Err(err)) => {
+                        debug!(%err, %peer, "rejected connection with invalid PROXY protocol header");
+                        return;
+                    }
+                    Err(_) => {
+                        debug!(%peer, "rejected connection without PROXY protocol header in time");
+                        return;
+                    }
+                }
+            }
+
+            let service = hyper::service::service_fn(move |mut request: Request<Body>| {
+                request.extensions_mut().insert(ConnectionAddress(peer));
+                service.clone().call(request)
+            });
+            if let Err(err) = Http::new().serve_connection(stream, service).await {
+                debug!(%err, %peer, "error while serving connection");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::http::{HeaderMap, HeaderValue};
+
+    use super::*;
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn headers(name: &'static str, values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn resolve(peer: &str, headers: &HeaderMap) -> IpAddr {
+        resolve_client_ip(ip(peer), headers, &trusted())
+    }
+
+    #[test]
+    fn ignores_headers_of_untrusted_peers() {
+        let headers = headers("x-forwarded-for", &["1.2.3.4"]);
+        assert_eq!(resolve("203.0.113.7", &headers), ip("203.0.113.7"));
+        let headers = self::headers("forwarded", &["for=1.2.3.4"]);
+        assert_eq!(resolve("203.0.113.7", &headers), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn follows_trusted_hops() {
+        // The client spoofed the first entry, the last untrusted hop is the client
+        let headers = headers("x-forwarded-for", &["6.6.6.6, 1.2.3.4", "10.0.0.2"]);
+        assert_eq!(resolve("10.0.0.1", &headers), ip("1.2.3.4"));
+
+        // Every hop is trusted, the first one is the client
+        let headers = self::headers("x-forwarded-for", &["10.0.0.3, 10.0.0.2"]);
+        assert_eq!(resolve("10.0.0.1", &headers), ip("10.0.0.3"));
+
+        // No header, the proxy itself is the client
+        assert_eq!(resolve("10.0.0.1", &HeaderMap::new()), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn forwarded_header() {
+        let headers = headers(
+            "forwarded",
+            &[
+                r#"for=1.2.3.4;proto=https, for="[2001:db8::1]:4711""#,
+                "for=10.0.0.2",
+            ],
+        );
+        assert_eq!(resolve("10.0.0.1", &headers), ip("2001:db8::1"));
+
+        // The header is preferred over X-Forwarded-For
+        let mut headers = self::headers("forwarded", &["For=1.2.3.4"]);
+        headers.append("x-forwarded-for", HeaderValue::from_static("5.6.7.8"));
+        assert_eq!(resolve("10.0.0.1", &headers), ip("1.2.3.4"));
+    }
+
+    #[test]
+    fn stops_at_obfuscated_entries() {
+        let headers = headers("forwarded", &["for=1.2.3.4, for=_hidden, for=10.0.0.2"]);
+        assert_eq!(resolve("10.0.0.1", &headers), ip("10.0.0.2"));
+        let headers = self::headers("forwarded", &["for=unknown"]);
+        assert_eq!(resolve("10.0.0.1", &headers), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn maps_ipv4_mapped_addresses() {
+        let headers = headers("x-forwarded-for", &["::ffff:1.2.3.4", "::ffff:10.0.0.2"]);
+        assert_eq!(resolve("::1", &headers), ip("1.2.3.4"));
+        assert!(is_trusted(ip("::ffff:10.0.0.1"), &trusted()));
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(
+            forwarded_for(r#" for="[2001:db8::1]""#),
+            Some(ip("2001:db8::1"))
+        );
+        assert_eq!(
+            forwarded_for("by=10.0.0.1;for=1.2.3.4:80"),
+            Some(ip("1.2.3.4"))
+        );
+        assert_eq!(forwarded_for("by=10.0.0.1"), None);
+        assert_eq!(parse_address(" 1.2.3.4 "), Some(ip("1.2.3.4")));
+        assert_eq!(parse_address("[2001:db8::1]:80"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_address("2001:db8::1"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_address("not an address"), None);
+    }
+
+    async fn read(header: &[u8]) -> std::io::Result<Option<SocketAddr>> {
+        let mut stream = header;
+        read_proxy_header(&mut stream).await
+    }
+
+    #[tokio::test]
+    async fn proxy_v1() {
+        let header = b"PROXY TCP4 1.2.3.4 10.0.0.1 4711 80\r\nGET / HTTP/1.1\r\n";
+        assert_eq!(
+            read(header).await.unwrap(),
+            Some("1.2.3.4:4711".parse().unwrap())
+        );
+        let header = b"PROXY TCP6 2001:db8::1 ::1 4711 80\r\n";
+        assert_eq!(
+            read(header).await.unwrap(),
+            Some("[2001:db8::1]:4711".parse().unwrap())
+        );
+        assert_eq!(read(b"PROXY UNKNOWN\r\n").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn proxy_v1_invalid() {
+        for header in [
+            &b"GET / HTTP/1.1\r\n\r\n"[..],
+            b"PROXY TCP4 1.2.3.4 10.0.0.1 4711\r\n",
+            b"PROXY TCP4 not.an.ip 10.0.0.1 4711 80\r\n",
+            // Truncated before the end of the line
+            b"PROXY TCP4 1.2.3.4 10.0.0.1",
+            b"PROXY",
+        ] {
+            assert!(read(header).await.is_err());
+        }
+
+        let oversized = format!("PROXY TCP4 {}\r\n", "1".repeat(PROXY_V1_MAX_LENGTH));
+        assert!(read(oversized.as_bytes()).await.is_err());
+    }
+
+    /// A version 2 header with the given version and command byte, address family and addresses.
+    fn proxy_v2(version_command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = PROXY_V2_SIGNATURE.to_vec();
+        header.push(version_command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    #[tokio::test]
+    async fn proxy_v2_addresses() {
+        let mut v4 = vec![1, 2, 3, 4, 10, 0, 0, 1];
+        v4.extend_from_slice(&4711u16.to_be_bytes());
+        v4.extend_from_slice(&80u16.to_be_bytes());
+        assert_eq!(
+            read(&proxy_v2(0x21, 0x11, &v4)).await.unwrap(),
+            Some("1.2.3.4:4711".parse().unwrap())
+        );
+
+        let mut v6 = "2001:db8::1"
+            .parse::<std::net::Ipv6Addr>()
+            .unwrap()
+            .octets()
+            .to_vec();
+        v6.extend_from_slice(&[0; 16]);
+        v6.extend_from_slice(&4711u16.to_be_bytes());
+        v6.extend_from_slice(&80u16.to_be_bytes());
+        assert_eq!(
+            read(&proxy_v2(0x21, 0x21, &v6)).await.unwrap(),
+            Some("[2001:db8::1]:4711".parse().unwrap())
+        );
+
+        // LOCAL connections and unknown families don't have a client address
+        assert_eq!(read(&proxy_v2(0x20, 0x00, &[])).await.unwrap(), None);
+        assert_eq!(read(&proxy_v2(0x21, 0x31, &[0; 216])).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn proxy_v2_invalid() {
+        // Only version 2 exists
+        assert!(read(&proxy_v2(0x11, 0x11, &[0; 12])).await.is_err());
+
+        // Shorter than announced
+        let mut truncated = proxy_v2(0x21, 0x11, &[0; 12]);
+        truncated.truncate(truncated.len() - 4);
+        assert!(read(&truncated).await.is_err());
+        assert!(read(&PROXY_V2_SIGNATURE[..8]).await.is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv6Addr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures_util::future::ready;
+use once_cell::sync::OnceCell;
+use thiserror::Error;
+use warp::{http::StatusCode, Filter};
+
+use crate::{accounts::AccountId, api::ApiErrorKind, proxy::client_ip};
+
+/// Routes sharing a rate limit.
+#[derive(Clone, Copy)]
+pub enum RouteGroup {
+    /// Player authentication that contacts other services, origin logins (checked with Stryder) and joining servers
+    ClientAuth,
+    /// Session routes that only use the database: authenticating with the master server, refreshing and revoking tokens
+    ClientSession,
+    /// Server registration, which sends a verification request to the game server
+    ServerRegistration,
+    /// The server list
+    ServerList,
+}
+
+impl RouteGroup {
+    const ALL: [RouteGroup; 4] = [
+        RouteGroup::ClientAuth,
+        RouteGroup::ClientSession,
+        RouteGroup::ServerRegistration,
+        RouteGroup::ServerList,
+    ];
+
+    fn env_var(self) -> &'static str {
+        match self {
+            RouteGroup::ClientAuth => "RATE_LIMIT_CLIENT_AUTH",
+            RouteGroup::ClientSession => "RATE_LIMIT_CLIENT_SESSION",
+            RouteGroup::ServerRegistration => "RATE_LIMIT_SERVER_REGISTRATION",
+            RouteGroup::ServerList => "RATE_LIMIT_SERVER_LIST",
+        }
+    }
+
+    /// Requests allowed per period, so a client can make this many requests at once.
+    pub(crate) fn default_limit(self) -> (u32, Duration) {
+        match self {
+            RouteGroup::ClientAuth => (20, Duration::from_secs(60)),
+            RouteGroup::ClientSession => (60, Duration::from_secs(60)),
+            RouteGroup::ServerRegistration => (10, Duration::from_secs(60)),
+            RouteGroup::ServerList => (60, Duration::from_secs(60)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("too many requests, try again later")]
+    Limited,
+}
+
+impl ApiErrorKind for RateLimitError {
+    fn kind(&self) -> &'static str {
+        match self {
+            RateLimitError::Limited => "RATE_LIMITED",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            RateLimitError::Limited => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+}
+
+impl warp::reject::Reject for RateLimitError {}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum RateLimitKey {
+    Ip(IpAddr),
+    Account(AccountId),
+}
+
+impl RateLimitKey {
+    /// Key of a client address, IPv6 clients are limited per /64 network as they usually get a whole one.
+    fn ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => RateLimitKey::Ip(ip),
+            IpAddr::V6(ip) => {
+                let network = u128::from(ip) & !(u128::from(u64::MAX));
+                RateLimitKey::Ip(Ipv6Addr::from(network).into())
+            }
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// How often clients with a full bucket are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket rate limiter, every client can make `capacity` requests at once which are refilled over time.
+pub struct RateLimiter {
+    capacity: f64,
+    /// Tokens refilled per second
+    rate: f64,
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+    fn new(requests: u32, period: Duration) -> Self {
+        Self {
+            capacity: requests as f64,
+            rate: requests as f64 / period.as_secs_f64(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The limiter of a route group.
+    ///
+    /// Limits are configured as `<requests>/<seconds>` in the variable of the group (for example `RATE_LIMIT_CLIENT_AUTH=20/60`),
+    /// `off` disables the limit.
+    pub fn group(group: RouteGroup) -> &'static Option<RateLimiter> {
+        static CLIENT_AUTH: OnceCell<Option<RateLimiter>> = OnceCell::new();
+        static CLIENT_SESSION: OnceCell<Option<RateLimiter>> = OnceCell::new();
+        static SERVER_REGISTRATION: OnceCell<Option<RateLimiter>> = OnceCell::new();
+        static SERVER_LIST: OnceCell<Option<RateLimiter>> = OnceCell::new();
+
+        let instance = match group {
+            RouteGroup::ClientAuth => &CLIENT_AUTH,
+            RouteGroup::ClientSession => &CLIENT_SESSION,
+            RouteGroup::ServerRegistration => &SERVER_REGISTRATION,
+            RouteGroup::ServerList => &SERVER_LIST,
+        };
+        instance.get_or_init(|| {
+            let name = group.env_var();
+            let (requests, period) = match std::env::var(name).as_deref() {
+                Ok("off") => return None,
+                Ok(limit) => limit
+                    .split_once('/')
+                    .and_then(|(requests, seconds)| {
+                        Some((
+                            requests.trim().parse().ok()?,
+                            Duration::from_secs(seconds.trim().parse().ok()?),
+                        ))
+                    })
+                    .filter(|(requests, period)| *requests > 0 && !period.is_zero())
+                    .unwrap_or_else(|| panic!("{} must be <requests>/<seconds> or off", name)),
+                Err(_) => group.default_limit(),
+            };
+            Some(RateLimiter::new(requests, period))
+        })
+    }
+
+    fn check(&self, key: RateLimitKey) -> Result<(), RateLimitError> {
+        self.take(key, true)
+    }
+
+    /// Checks whether a client has requests left without using one.
+    fn peek(&self, key: RateLimitKey) -> Result<(), RateLimitError> {
+        self.take(key, false)
+    }
+
+    fn take(&self, key: RateLimitKey, charge: bool) -> Result<(), RateLimitError> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            if charge {
+                bucket.tokens -= 1.0;
+            }
+            Ok(())
+        } else {
+            Err(RateLimitError::Limited)
+        }
+    }
+
+    /// Forgets clients whose bucket has been refilled, they are in the same state as new clients.
+    fn prune(&self) {
+        let now = Instant::now();
+        let (capacity, rate) = (self.capacity, self.rate);
+        self.buckets.lock().unwrap().retain(|_, bucket| {
+            bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+        });
+    }
+}
+
+/// Periodically forgets clients that haven't been limited recently, so the limiters don't grow forever.
+pub async fn prune_limiters() {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        for group in RouteGroup::ALL {
+            if let Some(limiter) = RateLimiter::group(group) {
+                limiter.prune();
+            }
+        }
+    }
+}
+
+/// Limits requests of a route group per client IP, for handlers that only limit some requests.
+pub fn check_ip(group: RouteGroup, ip: IpAddr) -> Result<(), RateLimitError> {
+    match RateLimiter::group(group) {
+        Some(limiter) => limiter.check(RateLimitKey::ip(ip)),
+        None => Ok(()),
+    }
+}
+
+/// Limits requests of a route group per client IP.
+pub fn by_ip(group: RouteGroup) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    client_ip()
+        .and_then(move |ip| ready(check_ip(group, ip).map_err(warp::reject::custom)))
+        .untuple_one()
+}
+
+/// Rejects requests for an account whose failed attempts of a route group reached the limit.
+///
+/// Only failures count against the account (see [`failed_account_attempt`]), as anyone can send requests
+/// for any account: counting every request would let them lock its owner out.
+pub fn check_account(group: RouteGroup, account: AccountId) -> Result<(), RateLimitError> {
+    match RateLimiter::group(group) {
+        Some(limiter) => limiter.peek(RateLimitKey::Account(account)),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed attempt of a route group against an account, such as a login with an invalid token.
+pub fn failed_account_attempt(group: RouteGroup, account: AccountId) {
+    if let Some(limiter) = RateLimiter::group(group) {
+        // A limited account only keeps being limited, there is nothing left to reject
+        let _ = limiter.check(RateLimitKey::Account(account));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_requests() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let ip = || RateLimitKey::ip([127, 0, 0, 1].into());
+
+        assert!(limiter.check(ip()).is_ok());
+        assert!(limiter.check(ip()).is_ok());
+        assert!(limiter.check(ip()).is_err());
+        assert!(limiter.check(RateLimitKey::Account(AccountId(1))).is_ok());
+    }
+
+    #[test]
+    fn peeking_uses_no_requests() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        let account = || RateLimitKey::Account(AccountId(1));
+
+        assert!(limiter.peek(account()).is_ok());
+        assert!(limiter.peek(account()).is_ok());
+        assert!(limiter.check(account()).is_ok());
+        assert!(limiter.peek(account()).is_err());
+    }
+
+    #[test]
+    fn groups_ipv6_networks() {
+        let ip = |s: &str| RateLimitKey::ip(s.parse().unwrap());
+        assert_eq!(ip("2001:db8:1:2:3:4:5:6"), ip("2001:db8:1:2::1"));
+        assert_ne!(ip("2001:db8:1:2::1"), ip("2001:db8:1:3::1"));
+        assert_eq!(
+            ip("192.0.2.1"),
+            RateLimitKey::Ip("192.0.2.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn prunes_full_buckets() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        limiter.check(RateLimitKey::Account(AccountId(1))).unwrap();
+        limiter.buckets.lock().unwrap().insert(
+            RateLimitKey::Account(AccountId(2)),
+            Bucket {
+                tokens: 2.0,
+                updated: Instant::now(),
+            },
+        );
+
+        limiter.prune();
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key(&RateLimitKey::Account(AccountId(1))));
+        assert!(!buckets.contains_key(&RateLimitKey::Account(AccountId(2))));
+    }
+}